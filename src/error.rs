@@ -0,0 +1,129 @@
+use std::{
+    error,
+    fmt,
+    path::PathBuf,
+};
+
+use crate::{
+    languages::headers::dependencies::Conflict,
+    util::NodeType,
+};
+
+/// Errors that can occur while reading the logic, settings, headers and seeds or while generating a seed
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// A file couldn't be read
+    ReadFile { path: PathBuf, message: String },
+    /// A file couldn't be written
+    CreateFile { path: PathBuf, message: String },
+    /// One of the logic files couldn't be parsed
+    ///
+    /// `line` and `column` are 1-based and only available if the error could be traced to a token
+    ParseLogic { file: PathBuf, line: Option<usize>, column: Option<usize>, message: String },
+    /// The logic files were parsed, but don't fit together
+    InvalidLogic(String),
+    /// Settings couldn't be read in any of the known formats
+    ReadSettings(String),
+    /// Something couldn't be serialized, `what` names the thing
+    Serialize { what: &'static str, message: String },
+    /// A header argument wasn't of the form `<header>.<parameter>=<value>`
+    HeaderArgument { argument: String },
+    /// A line in a header couldn't be parsed or applied, `line` is 1-based
+    ParseHeader { header: PathBuf, line: usize, message: String },
+    /// An item code couldn't be parsed
+    ParseItem { item: String, message: String },
+    /// A header expression couldn't be evaluated
+    Expression { expression: String, message: String },
+    /// A header's `!!if`, `!!elif`, `!!else` and `!!endif` don't pair up
+    Condition(String),
+    /// Some of the active headers exclude each other or write to the same uber states
+    HeaderConflicts(Vec<Conflict>),
+    /// A header package or package archive is malformed
+    Package(String),
+    /// The spawn location doesn't exist
+    UnknownSpawn { identifier: String },
+    /// The spawn location isn't an anchor
+    InvalidSpawn { identifier: String, node_type: NodeType },
+    /// The spawn location has no coordinates to teleport to
+    MissingSpawnPosition { identifier: String },
+    /// None of the spawn locations are valid on the current settings
+    NoSpawnAvailable,
+    /// A state referenced by name doesn't exist
+    UnknownState { identifier: String },
+    /// A node referenced by name doesn't exist in the logic
+    UnknownNode { identifier: String },
+    /// A seed couldn't be read, `line` is 1-based
+    ParseSeed { line: Option<usize>, message: String },
+    /// Placing items failed, `world_index` names the world it failed in if that is known
+    Generation { world_index: Option<usize>, message: String },
+    /// Every attempt to generate a seed failed
+    GenerationFailed { attempts: usize },
+    /// The placement rules in the settings can't be satisfied
+    PlacementRules(String),
+    /// The language server couldn't talk to its client
+    LanguageServer(String),
+    /// The generation server couldn't accept connections or talk to its client
+    Server(String),
+    /// Multiworld seeds aren't supported for this
+    Multiworld(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ReadFile { path, message } => write!(f, "Failed to read file {}: {}", path.display(), message),
+            Error::CreateFile { path, message } => write!(f, "Failed to create {}: {}", path.display(), message),
+            Error::ParseLogic { file, message, .. } => write!(f, "Error parsing {}: {}", file.display(), message),
+            Error::InvalidLogic(message) => write!(f, "Error building the logic: {}", message),
+            Error::ReadSettings(message) => write!(f, "Failed to read settings: {}", message),
+            Error::Serialize { what, message } => write!(f, "Failed to serialize {}: {}", what, message),
+            Error::HeaderArgument { argument } => write!(f, "Expected <header>.<parameter> in header arg {}", argument),
+            Error::ParseHeader { header, message, .. } => write!(f, "{} in header {}", message, header.display()),
+            Error::ParseItem { item, message } => write!(f, "{} in item {}", message, item),
+            Error::Expression { message, .. } => write!(f, "{}", message),
+            Error::Condition(message) => write!(f, "{}", message),
+            Error::HeaderConflicts(conflicts) => {
+                let conflicts = conflicts.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "Incompatible headers: {}", conflicts.join("; "))
+            },
+            Error::Package(message) => write!(f, "Invalid header package: {}", message),
+            Error::UnknownSpawn { identifier } => write!(f, "Spawn {} not found", identifier),
+            Error::InvalidSpawn { identifier, node_type } => write!(f, "Spawn has to be an anchor, {} is a {:?}", identifier, node_type),
+            Error::MissingSpawnPosition { identifier } => write!(f, "Tried to spawn on {} which has no specified coordinates", identifier),
+            Error::NoSpawnAvailable => write!(f, "No valid spawn locations available"),
+            Error::UnknownState { identifier } => write!(f, "target {} not found", identifier),
+            Error::UnknownNode { identifier } => write!(f, "{} not found in the logic", identifier),
+            Error::ParseSeed { line: Some(line), message } => write!(f, "{} in line {}", message, line),
+            Error::ParseSeed { line: None, message } => write!(f, "{}", message),
+            Error::Generation { message, .. } => write!(f, "{}", message),
+            Error::GenerationFailed { attempts } => write!(f, "All {} attempts to generate a seed failed :(", attempts),
+            Error::PlacementRules(message) => write!(f, "Invalid placement rules: {}", message),
+            Error::LanguageServer(message) => write!(f, "Language server error: {}", message),
+            Error::Server(message) => write!(f, "Server error: {}", message),
+            Error::Multiworld(what) => write!(f, "Multiworld seeds can't be {}", what),
+        }
+    }
+}
+
+impl Error {
+    /// Whether generating again with a different rng might succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Error::Generation { .. })
+    }
+}
+
+impl error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let err = Error::ParseLogic { file: PathBuf::from("areas.wotw"), line: Some(3), column: Some(5), message: String::from("Expected Anchor at line 3, instead found Requirement: test") };
+        assert_eq!(err.to_string(), "Error parsing areas.wotw: Expected Anchor at line 3, instead found Requirement: test");
+        let err = Error::InvalidSpawn { identifier: String::from("MarshSpawn.Main"), node_type: NodeType::Pickup };
+        assert_eq!(err.to_string(), "Spawn has to be an anchor, MarshSpawn.Main is a Pickup");
+        assert_eq!(Error::NoSpawnAvailable.to_string(), "No valid spawn locations available");
+    }
+}
@@ -3,17 +3,20 @@ use std::{
     collections::HashMap,
 };
 
+use rustc_hash::FxHashSet;
+
 use rand::{
     Rng,
     seq::{SliceRandom, IteratorRandom},
-    distributions::{Distribution, Uniform, Bernoulli},
+    distributions::{Distribution, Uniform, Bernoulli, WeightedIndex},
 };
 
 use crate::{
     ItemDetails,
+    error::Error,
     inventory::Inventory,
     item::{Item, Resource, Skill, Teleporter, Command, ShopCommand},
-    settings::Settings, util::{
+    settings::{Settings, rules::{self, PlacementRule}, weights::{self, PlacementWeight}}, util::{
         self,
         GoalMode, UberState, UberType, Difficulty,
         constants::{RELIC_ZONES, KEYSTONE_DOORS, RESERVE_SLOTS, PLACEHOLDER_SLOTS, SHOP_PRICES, DEFAULT_SPAWN, RANDOM_PROGRESSION},
@@ -36,6 +39,29 @@ impl fmt::Display for Placement<'_> {
     }
 }
 
+/// A progression item and the location it was placed on
+#[derive(Debug, Clone)]
+pub struct SphereItem<'a> {
+    pub node: &'a Node,
+    pub target_world_index: usize,
+    pub item: Item,
+}
+
+/// One reach step of the generator: the locations that first became reachable in it and the progression items placed on them
+#[derive(Debug, Clone)]
+pub struct Sphere<'a> {
+    pub locations: Vec<&'a Node>,
+    pub progressions: Vec<SphereItem<'a>>,
+}
+
+pub type Placements<'a> = Vec<Vec<Placement<'a>>>;
+pub type Spheres<'a> = Vec<Vec<Sphere<'a>>>;
+
+#[inline]
+fn generation_error(world_index: usize, message: String) -> Error {
+    Error::Generation { world_index: Some(world_index), message }
+}
+
 #[derive(Debug)]
 pub enum PartialItem {
     Placeholder,
@@ -55,6 +81,9 @@ struct WorldContext<'a> {
     spirit_light_rng: SpiritLightAmounts,  // TODO this can get kinda weird maybe have a shared spirit light rng instead
     random_spirit_light: Bernoulli,
     shop_slots: usize,
+    reached_indices: FxHashSet<usize>,
+    reach_steps: Vec<Vec<&'a Node>>,
+    placed_progressions: Vec<SphereItem<'a>>,
 }
 
 struct GeneratorContext<'a, 'b, R, I>
@@ -65,12 +94,41 @@ where
     world_count: usize,
     total_reachable_count: usize,
     custom_items: &'b HashMap<String, ItemDetails>,
+    bias: PlacementBias<'b>,
     multiworld_state_index: I,
     price_range: Uniform<f32>,
     random_progression: Bernoulli,
     rng: &'a mut R,
 }
 
+/// The placement weights and rules from the settings
+#[derive(Clone, Copy)]
+struct PlacementBias<'b> {
+    weights: &'b [PlacementWeight],
+    rules: &'b [PlacementRule],
+    difficulty: Difficulty,
+}
+impl PlacementBias<'_> {
+    fn is_empty(&self) -> bool {
+        self.weights.is_empty() && self.rules.is_empty()
+    }
+
+    fn allows(&self, item: &Item, node: &Node) -> bool {
+        rules::allows(self.rules, item, node, self.difficulty)
+    }
+
+    fn allows_spawn(&self, item: &Item) -> bool {
+        self.rules.iter().all(|rule| rule.allows_spawn(item))
+    }
+
+    /// Relative chance of placing `item` on `node`, 0 if the rules forbid it
+    fn weight(&self, item: &Item, node: &Node) -> f64 {
+        if self.allows(item, node) {
+            weights::placement_weight(self.weights, item, node)
+        } else { 0.0 }
+    }
+}
+
 struct ReachContext<'a> {
     reachable: Vec<graph::Reached<'a>>,
     reachable_states: Vec<Vec<&'a Node>>,
@@ -94,7 +152,7 @@ fn format_identifiers(mut identifiers: Vec<&str>) -> String {
     identifiers
 }
 
-fn progression_check<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &GeneratorContext<'_, '_, R, I>) -> Result<ReachContext<'a>, String>
+fn progression_check<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &GeneratorContext<'_, '_, R, I>) -> Result<ReachContext<'a>, Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -124,7 +182,7 @@ where
     })
 }
 
-fn place_item<'a, R, I>(origin_world_index: usize, target_world_index: usize, node: &'a Node, was_placeholder: bool, item: Item, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn place_item<'a, R, I>(origin_world_index: usize, target_world_index: usize, node: &'a Node, was_placeholder: bool, item: Item, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -132,6 +190,14 @@ where
     let origin_player_name = world_contexts[origin_world_index].player_name.clone();
     let target_player_name = world_contexts[target_world_index].player_name.clone();
 
+    if item.is_progression(world_contexts[target_world_index].world.player.difficulty) && !matches!(item, Item::SpiritLight(_)) {
+        world_contexts[origin_world_index].placed_progressions.push(SphereItem {
+            node,
+            target_world_index,
+            item: item.clone(),
+        });
+    }
+
     let origin_world_context = &mut world_contexts[origin_world_index];
 
     let uber_state = node.uber_state().unwrap();
@@ -146,13 +212,13 @@ where
         if is_shop {
             let (_, _, price_uber_state) = SHOP_PRICES.iter()
                 .find(|(_, location, _)| &uber_state.identifier == location)
-                .ok_or_else(|| format!("({}): Uber State {} claims to be a shop location, but doesn't have an entry in the shop prices table!", origin_player_name, node))?;
+                .ok_or_else(|| generation_error(origin_world_index, format!("({}): Uber State {} claims to be a shop location, but doesn't have an entry in the shop prices table!", origin_player_name, node)))?;
 
             let mut price = details.and_then(|details| details.price).unwrap_or_else(|| item.shop_price());
 
             if item.random_shop_price() {
                 let modified_price = f32::from(price) * context.price_range.sample(context.rng);
-                price = util::float_to_int(modified_price).map_err(|_| format!("({}): Overflowed shop price for {} after adding a random amount to it", origin_player_name, item))?;
+                price = util::float_to_int(modified_price).map_err(|_| generation_error(origin_world_index, format!("({}): Overflowed shop price for {} after adding a random amount to it", origin_player_name, item)))?;
             }
 
             let price_setter = UberState {
@@ -232,9 +298,9 @@ where
 
         let custom_name = custom_name.unwrap_or_else(|| format!("$[{}]", code));
         let origin_message = Item::Message(format!("{}'s {}", target_player_name, custom_name));
-        let send_item = UberState::from_parts("12" , &state_index.to_string())?.to_item(UberType::Bool);
+        let send_item = UberState::from_parts("12" , &state_index.to_string()).map_err(|message| generation_error(origin_world_index, message))?.to_item(UberType::Bool);
         let target_message = Item::Message(format!("{} from {}|mute", display.unwrap_or(custom_name), origin_player_name));
-        let target_uber_state = UberState::from_parts("12", &state_index.to_string())?;
+        let target_uber_state = UberState::from_parts("12", &state_index.to_string()).map_err(|message| generation_error(origin_world_index, message))?;
 
         origin_world_context.placements.push(Placement {
             node: Some(node),
@@ -261,7 +327,7 @@ where
     Ok(())
 }
 
-fn place_relics<'a, R, I>(amount: usize, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn place_relics<'a, R, I>(amount: usize, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -301,7 +367,7 @@ where
 }
 
 #[inline]
-fn force_keystones<'a, R, I>(reachable_states: &[Vec<&Node>], reserved_slots: &mut Vec<(usize, &'a Node)>, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn force_keystones<'a, R, I>(reachable_states: &[Vec<&Node>], reserved_slots: &mut Vec<(usize, &'a Node)>, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -336,7 +402,7 @@ where
     Ok(())
 }
 
-fn forced_placement<'a, R, I>(target_world_index: usize, item: Item, reserved_slots: &mut Vec<(usize, &'a Node)>, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn forced_placement<'a, R, I>(target_world_index: usize, item: Item, reserved_slots: &mut Vec<(usize, &'a Node)>, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -348,31 +414,31 @@ where
             let mut world_indices = (0..context.world_count).collect::<Vec<_>>();
             world_indices.shuffle(context.rng);
 
-            if let Some((origin_world_index, node)) = reserved_slots.pop() {
+            if let Some(index) = reserved_slots.iter().rposition(|(_, node)| context.bias.allows(&item, node)) {
+                let (origin_world_index, node) = reserved_slots.remove(index);
                 return Ok((origin_world_index, node, false));
             }
             for origin_world_index in world_indices {
                 let placeholders = &mut world_contexts[origin_world_index].placeholders;
-                if !placeholders.is_empty() {
-                    let index = context.rng.gen_range(0..placeholders.len());
+                if let Some(index) = choose_slot(placeholders, &item, context.bias, context.rng) {
                     let node = placeholders.remove(index);
                     return Ok((origin_world_index, node, true));
                 }
             }
         } else {
-            if let Some((index, _)) = reserved_slots.iter().enumerate().find(|(_, (world_index, _))| world_index == &target_world_index) {
+            if let Some((index, _)) = reserved_slots.iter().enumerate().find(|(_, (world_index, node))| world_index == &target_world_index && context.bias.allows(&item, node)) {
                 let (_, node) = reserved_slots.remove(index);
                 return Ok((target_world_index, node, false));
             }
 
             let placeholders = &mut world_contexts[target_world_index].placeholders;
-            if !placeholders.is_empty() {
-                let index = context.rng.gen_range(0..placeholders.len());
+            if let Some(index) = choose_slot(placeholders, &item, context.bias, context.rng) {
                 let node = placeholders.remove(index);
                 return Ok((target_world_index, node, true));
             }
         }
-        return Err(format!("({}): Not enough slots to place forced progression {}", world_contexts[target_world_index].player_name, item))  // due to the slot checks in missing_items this should only ever happen for forced keystone placements
+        let rules_hint = if context.bias.rules.is_empty() { "" } else { " that the placement rules allow" };
+        return Err(generation_error(target_world_index, format!("({}): Not enough slots{} to place forced progression {}", world_contexts[target_world_index].player_name, rules_hint, item)))  // due to the slot checks in missing_items this should only ever happen for forced keystone placements
     };
 
     let mut node = choose_node()?;
@@ -400,7 +466,7 @@ where
     Ok(())
 }
 
-fn determine_progressions<'a>(world_index: usize, slots: usize, world_slots: usize, reach_context: &ReachContext, world_context: &WorldContext<'a>) -> Result<Vec<Inventory>, String> {
+fn determine_progressions<'a>(world_index: usize, slots: usize, world_slots: usize, reach_context: &ReachContext, world_context: &WorldContext<'a>) -> Result<Vec<Inventory>, Error> {
     let mut itemsets = Vec::new();
 
     let owned_states = reach_context.reachable_states[world_index].iter().map(|&node| node.index()).collect::<Vec<_>>();
@@ -421,7 +487,7 @@ fn determine_progressions<'a>(world_index: usize, slots: usize, world_slots: usi
 
                     if missing.items.is_empty() {  // sanity check
                         log::trace!("({}): Failed to determine which items were needed for progression to meet {:?} (had {})", world_context.player_name, requirement, world_context.world.player.inventory);
-                        return Err(String::from("Failed to determine which items were needed for progression"));
+                        return Err(generation_error(world_index, String::from("Failed to determine which items were needed for progression")));
                     }
                     if missing.item_count() > slots
                     || missing.world_item_count() > world_slots
@@ -436,7 +502,7 @@ fn determine_progressions<'a>(world_index: usize, slots: usize, world_slots: usi
     Ok(itemsets)
 }
 
-fn filter_itemsets(itemsets: &mut Vec<Inventory>) {
+pub(crate) fn filter_itemsets(itemsets: &mut Vec<Inventory>) {
     itemsets.sort_unstable_by_key(Inventory::item_count);
     itemsets.reverse();
     let mut index = 0;
@@ -450,14 +516,14 @@ fn filter_itemsets(itemsets: &mut Vec<Inventory>) {
     }
 }
 
-fn pick_progression<'a, 'b, R, I>(target_world_index: usize, itemsets: &'b [Inventory], slots: usize, reach_context: &ReachContext, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<&'b Inventory, String>where
+fn pick_progression<'a, 'b, R, I>(target_world_index: usize, itemsets: &'b [Inventory], slots: usize, reach_context: &ReachContext, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<&'b Inventory, Error>where
 R: Rng,
 I: Iterator<Item=usize>,
 {
     let world_context = &world_contexts[target_world_index];
     log::trace!("({}): {} options for forced progression:", world_context.player_name, itemsets.len());
 
-    let weight = |inventory: &Inventory| -> Result<f32, String> {
+    let weight = |inventory: &Inventory| -> Result<f32, Error> {
         let mut newly_reached = 0;
 
         let target_world_context = &world_contexts[target_world_index];
@@ -481,12 +547,16 @@ I: Iterator<Item=usize>,
         }
 
         let base_weight = 1.0 / inventory.cost();
+        // Once per item rather than per amount, Spirit Light and resources are stored as many single items
+        let item_weight: f64 = inventory.items.keys()
+            .map(|item| weights::item_weight(context.bias.weights, item))
+            .product();
 
-        #[allow(clippy::cast_precision_loss)]
-        Ok(base_weight * (newly_reached + 1) as f32)
+        #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+        Ok((base_weight * (newly_reached + 1) as f32 * item_weight as f32).min(f32::MAX))
     };
     let with_weights = itemsets.iter()
-        .map::<Result<(&Inventory, f32), String>, _>(|inventory| Ok((inventory, weight(inventory)?)))
+        .map::<Result<(&Inventory, f32), Error>, _>(|inventory| Ok((inventory, weight(inventory)?)))
         .collect::<Result<Vec<_>, _>>()?;
     let weight_sum: f32 = with_weights.iter().map(|(_, weight)| weight).sum();
 
@@ -498,14 +568,14 @@ I: Iterator<Item=usize>,
 
             weight
         })
-        .map_err(|err| format!("({}): Error choosing progression: {}", world_context.player_name, err))?;
+        .map_err(|err| generation_error(target_world_index, format!("({}): Error choosing progression: {}", world_context.player_name, err)))?;
 
     log::trace!("({}): Chosen progression: {}", world_context.player_name, progression);
 
     Ok(progression)
 }
 
-fn split_progression_item<'a, R, I>(world_index: usize, item: &Item, amount: &u16, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<Vec<Item>, String>
+fn split_progression_item<'a, R, I>(world_index: usize, item: &Item, amount: &u16, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<Vec<Item>, Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -527,7 +597,7 @@ where
 }
 
 #[inline]
-fn spawn_progressions<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn spawn_progressions<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -553,6 +623,12 @@ where
                 continue 'outer;
             }
 
+            itemsets.retain(|itemset| itemset.items.keys().all(|item| context.bias.allows_spawn(item)));
+            if itemsets.is_empty() {
+                log::trace!("({}): The placement rules forbid all progressions on spawn", world_context.player_name);
+                continue 'outer;
+            }
+
             filter_itemsets(&mut itemsets);
             let progression = pick_progression(world_index, &itemsets, available_spawn_slots, &reach_context, world_contexts, context)?;
 
@@ -569,8 +645,19 @@ where
                     if matches!(item, Item::Skill(Skill::Regenerate)) {
                         random_slots -= 1;
                     }
-                    world_contexts[world_index].placements.push(Placement {
-                        node: world_contexts[world_index].spawn_slots.pop(),
+                    let world_context = &mut world_contexts[world_index];
+                    let node = world_context.spawn_slots.pop();
+                    if let Some(node) = node {
+                        if !matches!(item, Item::SpiritLight(_)) {
+                            world_context.placed_progressions.push(SphereItem {
+                                node,
+                                target_world_index: world_index,
+                                item: item.clone(),
+                            });
+                        }
+                    }
+                    world_context.placements.push(Placement {
+                        node,
                         uber_state: UberState::spawn(),
                         item,
                     });
@@ -582,13 +669,18 @@ where
     Ok(())
 }
 
-fn force_progression<'a, R, I>(reserved_slots: &mut Vec<(usize, &'a Node)>, reach_context: &mut ReachContext,world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn force_progression<'a, R, I>(reserved_slots: &mut Vec<(usize, &'a Node)>, reach_context: &mut ReachContext,world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
 {
     let slots = reserved_slots.len() + world_contexts.iter().map(|world_context| world_context.placeholders.len()).sum::<usize>();
 
+    let available_slots = reserved_slots.iter()
+        .map(|&(_, node)| node)
+        .chain(world_contexts.iter().flat_map(|world_context| world_context.placeholders.iter().copied()))
+        .collect::<Vec<_>>();
+
     let mut world_indices = (0..context.world_count).collect::<Vec<_>>();
     world_indices.shuffle(context.rng);
 
@@ -597,7 +689,10 @@ where
             let world_context = &mut world_contexts[chosen_world_index];
             let world_slots = reserved_slots.iter().filter(|(world_index, _)| *world_index == chosen_world_index).count() + world_context.placeholders.len();
 
-            let itemsets = determine_progressions(chosen_world_index, slots, world_slots, reach_context, world_context)?;
+            let mut itemsets = determine_progressions(chosen_world_index, slots, world_slots, reach_context, world_context)?;
+            if !context.bias.rules.is_empty() {
+                itemsets.retain(|itemset| itemset.items.keys().all(|item| available_slots.iter().any(|node| context.bias.allows(item, node))));
+            }
 
             if itemsets.is_empty() {
                 log::trace!("({}): No progressions found", world_context.player_name);
@@ -615,7 +710,7 @@ where
                 for world_context in world_contexts {
                     log::trace!("({}): Failed to reach anything from spawn location {}", world_context.player_name, world_context.spawn);
                 }
-                return Err(String::from("Failed to reach anything from spawn location"));
+                return Err(Error::Generation { world_index: None, message: String::from("Failed to reach anything from spawn location") });
             }
 
             for world_context in world_contexts {
@@ -638,7 +733,7 @@ where
                 log::error!("({}): Couldn't reach locations {}", world_context.player_name, format_identifiers(identifiers));
             }
 
-            return Err(String::from("Failed to reach all locations"));
+            return Err(Error::Generation { world_index: None, message: String::from("Failed to reach all locations") });
         }
     };
 
@@ -657,7 +752,7 @@ where
 }
 
 #[inline]
-fn random_item_placement<'a, R, I>(origin_world_index: usize, node: &'a Node, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<bool, String>
+fn random_item_placement<'a, R, I>(origin_world_index: usize, node: &'a Node, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<bool, Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -672,7 +767,8 @@ where
         if is_purchasable || origin_world_context.shop_slots < world_contexts[target_world_index].world.pool.inventory.item_count() {
             let target_world_context = &mut world_contexts[target_world_index];
 
-            if let Some(item) = target_world_context.world.pool.choose_random(origin_world_index != target_world_index, context.rng) {
+            let bias = context.bias;
+            if let Some(item) = target_world_context.world.pool.choose_random(origin_world_index != target_world_index, |item| bias.weight(item, node), context.rng) {
                 let item = item.clone();
                 target_world_context.world.pool.remove(&item, 1);
                 target_world_context.world.grant_player(item.clone(), 1).unwrap_or_else(|err| log::error!("({}): {}", target_world_context.player_name, err));
@@ -698,7 +794,7 @@ where
 }
 
 #[inline]
-fn random_placement<'a, R, I>(origin_world_index: usize, node: &'a Node, allow_placeholder: bool, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<bool, String>
+fn random_placement<'a, R, I>(origin_world_index: usize, node: &'a Node, allow_placeholder: bool, world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<bool, Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -727,7 +823,7 @@ where
 }
 
 #[inline]
-fn one_xp<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+fn one_xp<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -786,7 +882,29 @@ impl SpiritLightAmounts {
     }
 }
 
-fn place_remaining<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+/// Chooses the index of a slot to place `item` on, respecting the placement weights and rules
+///
+/// Returns `None` if there are no slots or none of them may hold the item
+fn choose_slot<R: Rng>(slots: &[&Node], item: &Item, bias: PlacementBias, rng: &mut R) -> Option<usize> {
+    if slots.is_empty() {
+        None
+    } else if bias.is_empty() {
+        Some(rng.gen_range(0..slots.len()))
+    } else {
+        let distribution = WeightedIndex::new(slots.iter().map(|node| bias.weight(item, node))).ok()?;
+        Some(distribution.sample(rng))
+    }
+}
+
+/// Removes a slot to place `item` on from already shuffled `slots`, respecting the placement weights and rules
+fn take_slot<'a, R: Rng>(slots: &mut Vec<&'a Node>, item: &Item, bias: PlacementBias, rng: &mut R) -> Option<&'a Node> {
+    if bias.is_empty() {
+        return slots.pop();
+    }
+    choose_slot(slots, item, bias, rng).map(|index| slots.swap_remove(index))
+}
+
+fn place_remaining<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -827,13 +945,19 @@ where
                     vec![target_world_index]
                 };
 
-                for origin_world_index in origin_world_indices {
-                    if let Some(node) = shop_placeholders[origin_world_index].pop().or_else(|| world_contexts[origin_world_index].placeholders.pop()) {
+                for &origin_world_index in &origin_world_indices {
+                    let node = take_slot(&mut shop_placeholders[origin_world_index], &item, context.bias, context.rng)
+                        .or_else(|| take_slot(&mut world_contexts[origin_world_index].placeholders, &item, context.bias, context.rng));
+                    if let Some(node) = node {
                         place_item(origin_world_index, target_world_index, node, true, item, world_contexts, context)?;
                         continue 'outer;
                     }
                 }
 
+                let has_slots = origin_world_indices.iter().any(|&origin_world_index| !shop_placeholders[origin_world_index].is_empty() || !world_contexts[origin_world_index].placeholders.is_empty());
+                if has_slots {
+                    return Err(generation_error(target_world_index, format!("({}): No free location satisfies the placement rules for {}", world_contexts[target_world_index].player_name, item)));
+                }
                 out_of_space = true;
 
                 log::warn!("({}): Not enough space to place all items from the item pool!", world_contexts[target_world_index].player_name);
@@ -886,16 +1010,39 @@ where
     Ok(())
 }
 
+/// Makes sure no placement slipped past the placement rules, for instance because there was nothing else to place than Spirit Light
+fn check_rules<R, I>(world_contexts: &[WorldContext], context: &GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
+where
+    R: Rng,
+    I: Iterator<Item=usize>,
+{
+    if context.bias.rules.is_empty() {
+        return Ok(());
+    }
+
+    for (world_index, world_context) in world_contexts.iter().enumerate() {
+        for placement in &world_context.placements {
+            if let Some(node) = placement.node {
+                if placement.uber_state != UberState::spawn() && !context.bias.allows(&placement.item, node) {
+                    return Err(generation_error(world_index, format!("({}): Placing {} at {} violates the placement rules", world_context.player_name, placement.item, node)));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[inline]
-fn total_reach_check<'a>(world: &World<'a>, player_name: &str) -> Result<Vec<&'a Node>, String> {
+pub(crate) fn total_reach_check<'a>(world: &World<'a>, player_name: &str) -> Result<Vec<&'a Node>, Error> {
     log::trace!("({}): Creating a player with everything to determine reachable locations", player_name);
     let mut finished_world = world.clone();
     for (item, amount) in &world.pool.inventory.items {
         if item.is_progression(world.player.difficulty) {
-            finished_world.grant_player(item.clone(), *amount)?;
+            finished_world.grant_player(item.clone(), *amount).map_err(|message| Error::Generation { world_index: None, message })?;
         }
     }
-    finished_world.grant_player(Item::SpiritLight(1), world.pool.spirit_light)?;
+    finished_world.grant_player(Item::SpiritLight(1), world.pool.spirit_light).map_err(|message| Error::Generation { world_index: None, message })?;
 
     let mut collected_preplacements = Vec::new();
     let mut total_reachable_count = 0;
@@ -927,7 +1074,46 @@ fn total_reach_check<'a>(world: &World<'a>, player_name: &str) -> Result<Vec<&'a
     };
 }
 
-fn flush_item_pool<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), String>
+/// Records what each world newly reached, worlds that reached nothing new get an empty step so the spheres of all worlds line up
+fn record_reach_step<'a>(reach_context: &ReachContext<'a>, spawn_pickup_node: &'a Node, world_contexts: &mut [WorldContext<'a>]) {
+    let steps = world_contexts.iter_mut().zip(&reach_context.reachable).map(|(world_context, world_reachable)| {
+        let mut newly_reached = Vec::new();
+
+        if world_context.reach_steps.is_empty() && world_context.spawn.identifier() != DEFAULT_SPAWN {
+            newly_reached.push(spawn_pickup_node);
+        }
+
+        for &node in world_reachable {
+            if node.can_place() && world_context.reached_indices.insert(node.index()) {
+                newly_reached.push(node);
+            }
+        }
+
+        newly_reached
+    }).collect::<Vec<_>>();
+
+    if steps.iter().any(|newly_reached| !newly_reached.is_empty()) {
+        for (world_context, newly_reached) in world_contexts.iter_mut().zip(steps) {
+            world_context.reach_steps.push(newly_reached);
+        }
+    }
+}
+
+fn collect_spheres<'a>(world_context: &WorldContext<'a>) -> Vec<Sphere<'a>> {
+    world_context.reach_steps.iter().map(|locations| {
+        let progressions = world_context.placed_progressions.iter()
+            .filter(|progression| locations.iter().any(|&location| location.index() == progression.node.index()))
+            .cloned()
+            .collect();
+
+        Sphere {
+            locations: locations.clone(),
+            progressions,
+        }
+    }).collect()
+}
+
+fn flush_item_pool<'a, R, I>(world_contexts: &mut [WorldContext<'a>], context: &mut GeneratorContext<'_, '_, R, I>) -> Result<(), Error>
 where
     R: Rng,
     I: Iterator<Item=usize>,
@@ -968,7 +1154,7 @@ pub fn generate_placements<'a, R>(
     custom_items: &HashMap<String, ItemDetails>,
     settings: &Settings,
     rng: &mut R
-) -> Result<Vec<Vec<Placement<'a>>>, String>
+) -> Result<(Placements<'a>, Spheres<'a>), Error>
 where
     R: Rng,
 {
@@ -995,6 +1181,7 @@ where
     let mut world_contexts = worlds.into_iter().enumerate().map(|(world_index, mut world)| {
         let player_name = settings.players.get(world_index).cloned().unwrap_or_else(|| format!("Player {}", world_index + 1));
 
+        rules::check(&settings.placement_rules, &world, settings.difficulty)?;
         world.collect_preplacements(&UberState::spawn());
 
         let mut placements = Vec::with_capacity(450);
@@ -1109,8 +1296,11 @@ where
             spirit_light_rng,
             random_spirit_light,
             shop_slots,
+            reached_indices: FxHashSet::default(),
+            reach_steps: Vec::new(),
+            placed_progressions: Vec::new(),
         })
-    }).collect::<Result<Vec<_>, String>>()?;
+    }).collect::<Result<Vec<_>, Error>>()?;
 
     let total_reachable_count: usize = world_contexts.iter().map(|world_context| world_context.reachable_locations.len()).sum();
 
@@ -1118,6 +1308,11 @@ where
         world_count: settings.worlds,
         total_reachable_count,
         custom_items,
+        bias: PlacementBias {
+            weights: &settings.placement_weights,
+            rules: &settings.placement_rules,
+            difficulty: settings.difficulty,
+        },
         multiworld_state_index: 0..,
         price_range,
         random_progression: Bernoulli::new(RANDOM_PROGRESSION).unwrap(),
@@ -1136,6 +1331,8 @@ where
     loop {
         let mut reach_context = progression_check(&mut world_contexts, &context)?;
 
+        record_reach_step(&reach_context, spawn_pickup_node, &mut world_contexts);
+
         force_keystones(&reach_context.reachable_states, &mut reserved_slots, &mut world_contexts, &mut context)?;
 
         let mut needs_placement = (0..context.world_count).map(|world_index| {
@@ -1226,9 +1423,69 @@ where
             }
 
             place_remaining(&mut world_contexts, &mut context)?;
+            check_rules(&world_contexts, &context)?;
 
+            let spheres = world_contexts.iter().map(collect_spheres).collect::<Vec<_>>();
             let placements = world_contexts.into_iter().map(|world_context| world_context.placements).collect::<Vec<_>>();
-            return Ok(placements);
+            return Ok((placements, spheres));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SeedBuilder, languages};
+
+    #[test]
+    fn spheres() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut headers = SeedBuilder::new(&graph, settings).unwrap().seed(String::from("spheres")).parse_headers().unwrap();
+        let generated = headers.generate().unwrap();
+        let (placements, spheres, spawn) = (&generated.placements[0], &generated.spheres[0], generated.spawns[0]);
+
+        let mut sphere_of = HashMap::new();
+        for (index, sphere) in spheres.iter().enumerate() {
+            for location in &sphere.locations {
+                assert!(sphere_of.insert(location.index(), index).is_none(), "{} is in more than one sphere", location);
+            }
+            for progression in &sphere.progressions {
+                assert_eq!(sphere_of.get(&progression.node.index()), Some(&index), "{} is listed in the wrong sphere", progression.item);
+            }
+        }
+
+        // Collecting the spheres in order reaches every next one, items placed into a sphere late may reach further than the generator did
+        let mut world = generated.headers.world().clone();
+        world.collect_preplacements(&UberState::spawn());
+        for (index, sphere) in spheres.iter().enumerate() {
+            let (reached, _) = world.graph.reached_and_visited(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+            let reached = reached.iter().map(|node| node.index()).collect::<FxHashSet<_>>();
+            for location in &sphere.locations {
+                assert!(reached.contains(&location.index()), "{} in sphere {} can't be reached with the spheres before it", location, index);
+            }
+
+            for location in &sphere.locations {
+                if let Some(uber_state) = location.uber_state() {
+                    world.collect_preplacements(uber_state);
+                }
+                for placement in placements.iter().filter(|placement| placement.node.map_or(false, |node| node.index() == location.index())) {
+                    world.grant_player(placement.item.clone(), 1).unwrap();
+                }
+            }
+        }
+        let (reached, _) = world.graph.reached_and_visited(&world.player, spawn, &world.uber_states, &world.sets).unwrap();
+        for node in reached.iter().filter(|node| node.can_place()) {
+            assert!(sphere_of.contains_key(&node.index()), "{} is reachable but in no sphere", node);
         }
+        let listed = spheres.iter().map(|sphere| sphere.progressions.len()).sum::<usize>();
+        let placed = placements.iter().filter(|placement| placement.node.is_some() && placement.item.is_progression(Difficulty::Moki) && !matches!(placement.item, Item::SpiritLight(_))).count();
+        assert_eq!(listed, placed);
+
+        // Sphere n of every world comes from the same reach step
+        let settings = Settings { worlds: 3, ..Settings::default() };
+        let mut headers = SeedBuilder::new(&graph, settings).unwrap().seed(String::from("spheres")).parse_headers().unwrap();
+        let generated = headers.generate().unwrap();
+        assert!(generated.spheres.iter().all(|world_spheres| world_spheres.len() == generated.spheres[0].len()));
     }
 }
@@ -86,6 +86,15 @@ impl Inventory {
         cost.into()
     }
 
+    /// Names the items along with their amounts, sorted so the output stays the same between runs
+    pub fn item_names(&self) -> Vec<String> {
+        let mut names = self.items.iter().map(|(item, &amount)| {
+            if amount == 1 { item.to_string() } else { format!("{} {}", amount, item) }
+        }).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
     pub fn contains(&self, other: &Inventory) -> bool {
         for (item, amount) in &other.items {
             if !self.has(item, *amount) {
@@ -1,8 +1,9 @@
 use num_enum::TryFromPrimitive;
+use serde::{Serialize, Deserialize};
 
 use crate::{util::{Difficulty, Icon}, auto_display};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, TryFromPrimitive)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, TryFromPrimitive, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Skill {
     Bash = 0,
@@ -1,10 +1,11 @@
 use std::fmt;
 
 use num_enum::TryFromPrimitive;
+use serde::{Serialize, Deserialize};
 
 use crate::util::{auto_display, UberIdentifier, UberState};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, TryFromPrimitive)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, TryFromPrimitive, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Teleporter {
     Marsh = 16,
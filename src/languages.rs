@@ -2,6 +2,6 @@ pub mod logic;
 pub mod headers;
 
 pub use self::{
-    logic::parse_logic,
+    logic::{parse_logic, parse_logic_cached},
     headers::parser::parse_header,
 };
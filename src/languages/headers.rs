@@ -1,30 +1,45 @@
 pub mod parser;
+pub mod expression;
+pub mod template;
+pub mod dependencies;
+pub mod version;
+pub mod package;
 
 use std::{
+    collections::HashMap,
     fs,
     io::{BufRead, BufReader},
     path::{Path, PathBuf},
 };
 
 use ansi_term::{Style, Colour};
-
-use crate::util::{
-    self,
-    constants::{HEADER_INDENT, NAME_COLOUR, UBERSTATE_COLOUR}, UberState,
+use serde::Serialize;
+
+use parser::Parameter;
+use dependencies::DependencyGraph;
+use version::Requirement;
+use package::{Package, Manifest};
+use crate::{
+    error::Error,
+    util::{
+        self,
+        constants::{HEADER_INDENT, NAME_COLOUR, UBERSTATE_COLOUR}, UberState,
+    },
 };
 
-fn is_hidden(header: &Path) -> Result<bool, String> {
-    let file = fs::File::open(header).map_err(|err| format!("Failed to open header from {:?}: {}", header, err))?;
+fn is_hidden(header: &Path) -> Result<bool, Error> {
+    let read_error = |err: std::io::Error| Error::ReadFile { path: header.to_path_buf(), message: err.to_string() };
+    let file = fs::File::open(header).map_err(read_error)?;
     let mut file = BufReader::new(file);
 
     let mut line = String::new();
-    file.read_line(&mut line).map_err(|err| format!("Failed to read header from {:?}: {}", header, err))?;
+    file.read_line(&mut line).map_err(read_error)?;
 
     Ok(line.trim() == "#hide")
 }
 
-fn headers_in_directory(directory: &Path) -> Result<Vec<PathBuf>, String> {
-    Ok(fs::read_dir(directory).map_err(|err| format!("Failed to read directory {:?}: {}", directory, err))?
+fn headers_in_directory(directory: &Path) -> Result<Vec<PathBuf>, Error> {
+    Ok(fs::read_dir(directory).map_err(|err| Error::ReadFile { path: directory.to_path_buf(), message: err.to_string() })?
         .filter_map(|entry| {
             if let Ok(entry) = entry {
                 let path = entry.path();
@@ -39,11 +54,19 @@ fn headers_in_directory(directory: &Path) -> Result<Vec<PathBuf>, String> {
         .collect())
 }
 
-fn find_headers(show_hidden: bool) -> Result<Vec<PathBuf>, String> {
+fn find_headers(show_hidden: bool) -> Result<Vec<PathBuf>, Error> {
     let mut headers = headers_in_directory(Path::new("."))?;
     if let Ok(mut more) = headers_in_directory(Path::new("./headers")) {
         headers.append(&mut more);
     }
+    if let Ok(packages) = package::find_packages() {
+        for package in packages {
+            match package.bundled_headers() {
+                Ok(mut bundled) => headers.append(&mut bundled),
+                Err(err) => log::warn!("Skipping the headers of package {}: {}", package.manifest.name, err),
+            }
+        }
+    }
 
     if !show_hidden {
         headers = headers.iter()
@@ -58,38 +81,134 @@ fn find_headers(show_hidden: bool) -> Result<Vec<PathBuf>, String> {
     Ok(headers)
 }
 
-fn summarize_headers(headers: &[PathBuf]) -> Result<String, String> {
-    let mut output = String::new();
+/// A header's name and a short description
+#[derive(Debug, Clone, Serialize)]
+pub struct HeaderSummary {
+    pub name: String,
+    pub description: Option<String>,
+    /// Why this seed generator can't use the header, if it can't
+    pub incompatible: Option<String>,
+}
+/// A header's name, full description, parameters and versioning
+#[derive(Debug, Clone, Serialize)]
+pub struct HeaderDetails {
+    pub name: String,
+    pub description: Vec<String>,
+    pub parameters: Vec<ParameterDetails>,
+    /// From `!!version`
+    pub version: Option<String>,
+    /// From `!!requires`
+    pub requires: Vec<String>,
+}
+/// A parameter with the `////` comment above its declaration
+#[derive(Debug, Clone, Serialize)]
+pub struct ParameterDetails {
+    #[serde(flatten)]
+    pub parameter: Parameter,
+    pub description: Option<String>,
+}
 
-    for header in headers {
-        let mut name = header.file_stem().unwrap().to_string_lossy().into_owned();
-        let header = fs::read_to_string(header).map_err(|err| format!("Error reading header from {:?}: {}", header, err))?;
+/// The `!!version` and `!!requires` declarations of a header
+fn versioning(header: &str) -> (Option<String>, Vec<String>) {
+    let mut version = None;
+    let mut requires = Vec::new();
+
+    for line in header.lines() {
+        let line = line.trim();
+        let line = line.find("//").map_or(line, |index| line[..index].trim_end());
+        if let Some(declared) = line.strip_prefix("!!version ") {
+            version = Some(declared.trim().to_string());
+        } else if let Some(requirement) = line.strip_prefix("!!requires ") {
+            requires.push(requirement.trim().to_string());
+        }
+    }
 
-        let mut description = None;
+    (version, requires)
+}
 
-        for line in header.lines() {
-            if let Some(desc) = line.trim_start().strip_prefix("///") {
-                let desc = desc.trim();
-                if desc.is_empty() {
-                    continue;
-                }
-                let first = description.is_none();
-                description = Some(desc);
-                if !first {
-                    break;
-                }
+/// Why this seed generator can't fulfill the requirement, if it can't
+fn unfulfilled(requirement: &str) -> Option<String> {
+    requirement.parse::<Requirement>().and_then(|requirement| requirement.check()).err()
+}
+
+fn summarize(header: &Path) -> Result<HeaderSummary, Error> {
+    let name = header.file_stem().unwrap().to_string_lossy().into_owned();
+    let header = fs::read_to_string(header).map_err(|err| Error::ReadFile { path: header.to_path_buf(), message: err.to_string() })?;
+
+    let mut description = None;
+
+    for line in header.lines() {
+        if let Some(desc) = line.trim_start().strip_prefix("///") {
+            let desc = desc.trim();
+            if desc.is_empty() {
+                continue;
+            }
+            let first = description.is_none();
+            description = Some(desc);
+            if !first {
+                break;
             }
         }
+    }
+
+    let incompatible = versioning(&header).1.iter().find_map(|requirement| unfulfilled(requirement));
+
+    Ok(HeaderSummary { name, description: description.map(str::to_string), incompatible })
+}
+
+fn summarize_headers(headers: &[PathBuf]) -> Result<String, Error> {
+    let mut output = String::new();
+
+    for header in headers {
+        let HeaderSummary { mut name, description, incompatible } = summarize(header)?;
 
         util::add_trailing_spaces(&mut name, HEADER_INDENT);
 
-        output += &format!("{}  {}\n", NAME_COLOUR.paint(name), description.unwrap_or("no description"));
+        output += &format!("{}  {}", NAME_COLOUR.paint(name), description.as_deref().unwrap_or("no description"));
+        if let Some(incompatible) = incompatible {
+            output += &format!("  {}", Colour::Red.paint(format!("(incompatible: {})", incompatible)));
+        }
+        output.push('\n');
     }
 
     Ok(output)
 }
 
-pub fn list() -> Result<(), String> {
+/// Summaries of the headers that aren't hidden
+pub fn summaries() -> Result<Vec<HeaderSummary>, Error> {
+    find_headers(false)?.iter().map(|header| summarize(header)).collect()
+}
+
+/// The description of a header, `header` may leave out the folder and extension
+pub fn details(mut header: PathBuf) -> Result<HeaderDetails, Error> {
+    header.set_extension("wotwrh");
+    let name = header.file_stem().unwrap().to_string_lossy().into_owned();
+
+    let contents = util::read_file(&header, "headers")?;
+
+    let mut description = Vec::new();
+    let mut parameters = Vec::new();
+    let mut last_comment = None;
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix("////") {
+            last_comment = Some(comment.trim().to_string());
+            continue;
+        } else if let Some(desc) = line.strip_prefix("///") {
+            description.push(desc.trim().to_string());
+        } else if let Some(parameter) = line.strip_prefix("!!parameter ") {
+            let parameter = Parameter::parse(parameter.trim()).map_err(|message| Error::ParseHeader { header: header.clone(), line: index + 1, message })?;
+            parameters.push(ParameterDetails { parameter, description: last_comment.take() });
+        }
+        last_comment = None;
+    }
+
+    let (version, requires) = versioning(&contents);
+
+    Ok(HeaderDetails { name, description, parameters, version, requires })
+}
+
+pub fn list() -> Result<(), Error> {
     let mut output = String::new();
 
     let headers = find_headers(false)?;
@@ -105,13 +224,50 @@ pub fn list() -> Result<(), String> {
     output += &summarize_headers(&headers)?;
     output.push('\n');
 
-    output += "Use 'headers <name>...' for details about one or more headers";
+    let packages = package::find_packages().unwrap_or_default();
+    if !packages.is_empty() {
+        let packages_length = packages.len();
+        output += &format!("{}", Style::new().fg(Colour::Green).bold().paint(format!("{} package{} found\n\n", packages_length, if packages_length == 1 { "" } else { "s" })));
+
+        for package in packages {
+            let mut name = package.manifest.name;
+            util::add_trailing_spaces(&mut name, HEADER_INDENT);
+            output += &format!("{}  {}\n", NAME_COLOUR.paint(name), package.manifest.description.as_deref().unwrap_or("no description"));
+        }
+        output.push('\n');
+    }
+
+    output += "Use 'headers <name>...' for details about one or more headers or packages";
 
     println!("{}", output);
     Ok(())
 }
 
-pub fn inspect(headers: Vec<PathBuf>) -> Result<(), String> {
+fn describe_package(package: &Package) -> String {
+    let Manifest { name, description, version, header_args, .. } = &package.manifest;
+
+    let mut output = NAME_COLOUR.paint(format!("{} package:\n", name)).to_string();
+    if let Some(description) = description {
+        output += &format!("{}\n", description);
+    }
+    if let Some(version) = version {
+        output += &format!("\nVersion {}\n", version);
+    }
+
+    let list = |paths: Vec<PathBuf>| paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", ");
+    output += &format!("\nHeaders: {}\n", list(package.headers()));
+    if !header_args.is_empty() {
+        output += &format!("Header arguments: {}\n", header_args.join(" "));
+    }
+    if !package.manifest.presets.is_empty() {
+        output += &format!("Presets: {}\n", list(package.presets()));
+    }
+    output += &format!("\nUse 'seed -p {}' to generate with the whole package\n\n", NAME_COLOUR.paint(name));
+
+    output
+}
+
+pub fn inspect(headers: Vec<PathBuf>) -> Result<(), Error> {
     let mut output = String::new();
 
     let hint = if headers.len() == 1 {
@@ -126,35 +282,120 @@ pub fn inspect(headers: Vec<PathBuf>) -> Result<(), String> {
         format!("Use 'preset <name> -h {} ...' to add these headers to a preset", NAME_COLOUR.paint(arguments))
     };
 
-    for mut header in headers {
-        header.set_extension("wotwrh");
-        let name = header.file_stem().unwrap().to_string_lossy();
-
-        let contents = util::read_file(&header, "headers")?;
+    let mut any_headers = false;
+    for header in headers {
+        if let Some(package) = Package::find(&header)? {
+            output += &describe_package(&package);
+            continue;
+        }
+        any_headers = true;
 
-        let mut description = NAME_COLOUR.paint(format!("{} header:\n", name)).to_string();
+        let HeaderDetails { name, description, parameters, version, requires } = details(header)?;
 
-        for line in contents.lines() {
-            if let Some(desc) = line.trim_start().strip_prefix("///") {
-                description.push_str(desc.trim());
-                description.push('\n');
+        output += &NAME_COLOUR.paint(format!("{} header:\n", name)).to_string();
+        for line in description {
+            output += &line;
+            output.push('\n');
+        }
+        if let Some(version) = version {
+            output += &format!("\nVersion {}\n", version);
+        }
+        for requirement in requires {
+            match unfulfilled(&requirement) {
+                Some(err) => output += &format!("{}\n", Colour::Red.paint(err)),
+                None => output += &format!("Requires {}\n", requirement),
             }
         }
-
-        if description.is_empty() {
-            output += &Style::new().italic().paint("No description provided\n");
-        } else {
-            output += &description;
+        if !parameters.is_empty() {
+            output += "\nParameters:\n";
+        }
+        for ParameterDetails { parameter, description } in parameters {
+            let mut identifier = parameter.identifier;
+            util::add_trailing_spaces(&mut identifier, HEADER_INDENT);
+            output += &format!("  {}  {}, defaults to {}", NAME_COLOUR.paint(identifier), parameter.parameter_type, parameter.default);
+            if let Some(description) = description {
+                output += &format!(" - {}", description);
+            }
             output.push('\n');
         }
+        output.push('\n');
     }
 
-    output += &hint;
+    if any_headers {
+        output += &hint;
+    }
     println!("{}", output);
     Ok(())
 }
 
-pub fn validate(path: Option<PathBuf>) -> Result<bool, String> {
+/// Installs a package archive into the headers folder
+pub fn install(archive: PathBuf) -> Result<(), Error> {
+    let package = package::install(&archive, Path::new("headers"))?;
+    log::info!("Installed {} to {}", package.manifest.name, package.directory.display());
+    Ok(())
+}
+
+/// Bundles a package into an archive next to the current directory
+pub fn pack(name: PathBuf) -> Result<(), Error> {
+    let package = Package::find(&name)?.ok_or_else(|| Error::Package(format!("{} not found", name.display())))?;
+    let archive = package.pack()?;
+
+    let path = util::create_file(Path::new(&package.manifest.name).with_extension(package::ARCHIVE_EXTENSION), &archive, ".", false)?;
+    log::info!("Packed {} into {}", package.manifest.name, path.display());
+    Ok(())
+}
+
+fn print_dependencies(graph: &DependencyGraph, name: &str, depth: usize, printed: &mut Vec<String>, output: &mut String) {
+    let indent = "  ".repeat(depth);
+    let node = match graph.get(name) {
+        Some(node) => node,
+        None => return,
+    };
+
+    if printed.iter().any(|printed| printed == name) {
+        *output += &format!("{}{} (see above)\n", indent, NAME_COLOUR.paint(name));
+        return;
+    }
+    printed.push(name.to_string());
+
+    *output += &format!("{}{}", indent, NAME_COLOUR.paint(name));
+    if !node.excludes.is_empty() {
+        *output += &format!("  excludes {}", node.excludes.join(", "));
+    }
+    output.push('\n');
+
+    for include in &node.includes {
+        print_dependencies(graph, include, depth + 1, printed, output);
+    }
+}
+
+/// Prints which headers the given headers include and exclude and whether any of them conflict
+///
+/// Returns `false` if there were conflicts
+pub fn graph(headers: Vec<PathBuf>) -> Result<bool, Error> {
+    let graph = DependencyGraph::build(&headers, &HashMap::new())?;
+    let mut output = String::new();
+
+    let mut printed = Vec::new();
+    for root in &graph.roots {
+        print_dependencies(&graph, root, 0, &mut printed, &mut output);
+    }
+
+    let conflicts = graph.conflicts();
+    if conflicts.is_empty() {
+        output += &format!("{}", Colour::Green.paint(format!("\nNo conflicts between {} header{}", graph.nodes.len(), if graph.nodes.len() == 1 { "" } else { "s" })));
+    } else {
+        output += &format!("{}", Colour::Red.paint(format!("\n{} conflict{}\n", conflicts.len(), if conflicts.len() == 1 { "" } else { "s" })));
+        for conflict in &conflicts {
+            output += &format!("{}\n", conflict);
+        }
+    }
+
+    println!("{}", output);
+    Ok(conflicts.is_empty())
+}
+
+pub fn validate(path: Option<PathBuf>) -> Result<bool, Error> {
     let mut output = String::new();
 
     let headers = match path {
@@ -174,9 +415,9 @@ pub fn validate(path: Option<PathBuf>) -> Result<bool, String> {
         let contents = util::read_file(&header, "headers")?;
         let mut name = header.file_stem().unwrap().to_string_lossy().into_owned();
 
-        match parser::validate_header(&header, &contents) {
-            Ok((occupied, excludes)) => {
-                occupation_map.push((name, occupied, excludes));
+        match parser::validate_header(&header, &contents, &HashMap::new()) {
+            Ok((occupied, context)) => {
+                occupation_map.push((name, occupied, context.excludes));
             },
             Err(err) => {
                 util::add_trailing_spaces(&mut name, HEADER_INDENT);
@@ -189,31 +430,17 @@ pub fn validate(path: Option<PathBuf>) -> Result<bool, String> {
         let (header, occupied, excludes) = &occupation_map[index];
         let mut collision_message = String::new();
 
-        'outer: for uber_state in occupied {
-            // special cases because this system is not holding up to modern header logic
-            if uber_state.identifier.uber_group == 9 && (
-                uber_state.identifier.uber_id == 0 && ["250", "251", "999"].contains(&&uber_state.value[..])
-                || uber_state.identifier.uber_id == 999 && uber_state.value == "200"
-                || uber_state.identifier.uber_id == 150
-            ) {
+        for (other_header, other_occupied, _) in &occupation_map {
+            if header == other_header || excludes.contains_key(other_header) {
                 continue;
             }
-
-            for (other_header, other_occupied, _) in &occupation_map {
-                if header == other_header || excludes.contains_key(other_header) {
-                    continue;
-                }
-                if let Some(collision) = other_occupied.iter().find(|&other| {
-                    let generic = uber_state.value.is_empty() || other.value.is_empty();
-                    uber_state == other || (generic && uber_state.identifier == other.identifier)
-                }) {
-                    collision_message = format!("Collision between used state {} and {} using {}",
-                        UBERSTATE_COLOUR.paint(format!("{}", uber_state)),
-                        NAME_COLOUR.paint(other_header),
-                        UBERSTATE_COLOUR.paint(format!("{}", collision))
-                    );
-                    break 'outer;
-                }
+            if let Some((uber_state, collision)) = dependencies::collision(occupied, other_occupied) {
+                collision_message = format!("Collision between used state {} and {} using {}",
+                    UBERSTATE_COLOUR.paint(format!("{}", uber_state)),
+                    NAME_COLOUR.paint(other_header),
+                    UBERSTATE_COLOUR.paint(format!("{}", collision))
+                );
+                break;
             }
         }
 
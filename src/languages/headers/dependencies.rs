@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use super::parser;
+use crate::{
+    error::Error,
+    util::{self, UberState},
+};
+
+/// A header along with what it includes, excludes and which states in uber group 9 it writes to
+#[derive(Debug, Clone)]
+pub struct HeaderNode {
+    pub name: String,
+    pub includes: Vec<String>,
+    pub excludes: Vec<String>,
+    pub occupied: Vec<UberState>,
+}
+
+/// Why two headers can't be active at the same time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    /// `header` contains `!!exclude <excluded>`
+    Excluded { header: String, excluded: String },
+    /// Both headers write to the same uber state
+    Collision { header: String, uber_state: UberState, other: String, other_uber_state: UberState },
+}
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Conflict::Excluded { header, excluded } => write!(f, "{} excludes {}", header, excluded),
+            Conflict::Collision { header, uber_state, other, other_uber_state } =>
+                write!(f, "{} uses {} which collides with {} using {}", header, uber_state, other, other_uber_state),
+        }
+    }
+}
+
+/// Whether writing `uber_state` is fine even if other headers write it too
+fn is_shared(uber_state: &UberState) -> bool {
+    // special cases because this system is not holding up to modern header logic
+    uber_state.identifier.uber_group == 9 && (
+        uber_state.identifier.uber_id == 0 && ["250", "251", "999"].contains(&&uber_state.value[..])
+        || uber_state.identifier.uber_id == 999 && uber_state.value == "200"
+        || uber_state.identifier.uber_id == 150
+    )
+}
+
+/// The first state in `occupied` that is also written to in `other_occupied`, along with the state it collides with
+///
+/// A state without a value collides with any value of the same state
+pub fn collision<'a>(occupied: &'a [UberState], other_occupied: &'a [UberState]) -> Option<(&'a UberState, &'a UberState)> {
+    occupied.iter().filter(|uber_state| !is_shared(uber_state)).find_map(|uber_state| {
+        other_occupied.iter().find(|&other| {
+            let generic = uber_state.value.is_empty() || other.value.is_empty();
+            uber_state == other || (generic && uber_state.identifier == other.identifier)
+        }).map(|other| (uber_state, other))
+    })
+}
+
+fn header_name(path: &Path) -> String {
+    path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// The headers reachable from a list of headers through `!!include`s
+#[derive(Debug, Clone)]
+pub struct DependencyGraph {
+    /// The names of the headers the graph was built from
+    pub roots: Vec<String>,
+    /// Every reachable header once, in the order they were discovered
+    pub nodes: Vec<HeaderNode>,
+}
+impl DependencyGraph {
+    /// Reads the headers and everything they include, using the header arguments in `param_values`
+    pub fn build(headers: &[PathBuf], param_values: &HashMap<&str, HashMap<&str, &str>>) -> Result<DependencyGraph, Error> {
+        let mut pending = headers.iter().rev().map(|header| {
+            let mut header = header.clone();
+            header.set_extension("wotwrh");
+            header
+        }).collect::<Vec<_>>();
+        let roots = pending.iter().rev().map(|header| header_name(header)).collect();
+
+        let mut nodes: Vec<HeaderNode> = Vec::new();
+        while let Some(path) = pending.pop() {
+            let name = header_name(&path);
+            if nodes.iter().any(|node| node.name == name) { continue }
+
+            let contents = util::read_file(&path, "headers")?;
+            let (occupied, context) = parser::validate_header(&path, &contents, param_values)?;
+
+            let mut excludes = context.excludes.into_keys().collect::<Vec<_>>();
+            excludes.sort_unstable();
+            let includes = context.dependencies.iter().map(|include| header_name(include)).collect();
+            pending.extend(context.dependencies.into_iter().rev());
+
+            nodes.push(HeaderNode { name, includes, excludes, occupied });
+        }
+
+        Ok(DependencyGraph { roots, nodes })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HeaderNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    /// Every pair of headers that can't be active together
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = Vec::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            for other in &self.nodes[index + 1..] {
+                if node.excludes.contains(&other.name) {
+                    conflicts.push(Conflict::Excluded { header: node.name.clone(), excluded: other.name.clone() });
+                } else if other.excludes.contains(&node.name) {
+                    conflicts.push(Conflict::Excluded { header: other.name.clone(), excluded: node.name.clone() });
+                } else if let Some((uber_state, other_uber_state)) = collision(&node.occupied, &other.occupied) {
+                    conflicts.push(Conflict::Collision {
+                        header: node.name.clone(),
+                        uber_state: uber_state.clone(),
+                        other: other.name.clone(),
+                        other_uber_state: other_uber_state.clone(),
+                    });
+                }
+            }
+        }
+
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dependency_graph() {
+        let graph = DependencyGraph::build(&[PathBuf::from("bingo"), PathBuf::from("black_market")], &HashMap::new()).unwrap();
+        assert_eq!(graph.roots, ["bingo", "black_market"]);
+        assert_eq!(graph.nodes[0].name, "bingo");
+        assert!(graph.conflicts().is_empty());
+
+        let graph = DependencyGraph::build(&[PathBuf::from("bonus+"), PathBuf::from("bonus_items")], &HashMap::new()).unwrap();
+        assert_eq!(graph.get("bonus+").unwrap().includes, ["skill_velocity", "jumpgrades", "rapid_smash_core", "rapid_sword_core", "rapid_spear_core"]);
+        assert_eq!(graph.conflicts(), [Conflict::Excluded { header: String::from("bonus+"), excluded: String::from("bonus_items") }]);
+
+        let folder = std::env::temp_dir().join(format!("seedgen_dependencies_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(folder.join("strict.wotwrh"), "!!parameter strict bool:false\n!!if strict\n!!exclude lenient\n!!endif\n").unwrap();
+        std::fs::write(folder.join("lenient.wotwrh"), "3|0|6|lenient\n").unwrap();
+        let headers = [folder.join("strict"), folder.join("lenient")];
+        assert!(DependencyGraph::build(&headers, &HashMap::new()).unwrap().conflicts().is_empty());
+        let param_values = vec![("strict", vec![("strict", "true")].into_iter().collect())].into_iter().collect();
+        assert_eq!(DependencyGraph::build(&headers, &param_values).unwrap().conflicts().len(), 1);
+
+        // States written in a skipped branch don't collide
+        std::fs::write(folder.join("optional.wotwrh"), "!!parameter on bool:false\n!!if on\n3|0|8|9|10|int|1\n!!endif\n").unwrap();
+        std::fs::write(folder.join("fixed.wotwrh"), "3|0|8|9|10|int|1\n").unwrap();
+        let headers = [folder.join("optional"), folder.join("fixed")];
+        assert!(DependencyGraph::build(&headers, &HashMap::new()).unwrap().conflicts().is_empty());
+        let param_values = vec![("optional", vec![("on", "true")].into_iter().collect())].into_iter().collect();
+        assert!(matches!(DependencyGraph::build(&headers, &param_values).unwrap().conflicts()[..], [Conflict::Collision { .. }]));
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        let uber_state = |value: &str| UberState::from_parts("9", &format!("10={}", value)).unwrap();
+        assert!(collision(&[uber_state("1")], &[uber_state("2")]).is_none());
+        assert_eq!(collision(&[uber_state("1")], &[uber_state("")]), Some((&uber_state("1"), &uber_state(""))));
+    }
+}
@@ -0,0 +1,421 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    iter::Peekable,
+    str::CharIndices,
+};
+
+use crate::error::Error;
+
+/// The result of evaluating an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+impl Value {
+    /// Reads a parameter value, which is stored as text
+    fn infer(value: &str) -> Value {
+        match value {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => value.parse().map(Value::Int)
+                .or_else(|_| value.parse().map(Value::Float))
+                .unwrap_or_else(|_| Value::String(value.to_string())),
+        }
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        #[allow(clippy::cast_precision_loss)]
+        match self {
+            Value::Int(int) => Some(*int as f64),
+            Value::Float(float) => Some(*float),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "boolean",
+            Value::Int(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+        }
+    }
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(bool) => write!(f, "{}", bool),
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Float(float) => write!(f, "{}", float),
+            Value::String(string) => write!(f, "{}", string),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Identifier(&'a str),
+    Number(&'a str),
+    String(&'a str),
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+/// Operators with two characters have to come first so they win over their prefixes
+const OPERATORS: [&str; 15] = ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "!", "+", "-", "*", "/", "%", "="];
+/// The operators that can follow a parameter in a condition, unlike `-1` which is a value in the older `<parameter> <value>` form
+const CONDITION_OPERATORS: [&str; 9] = ["&&", "||", "==", "!=", "<=", ">=", "<", ">", "="];
+
+fn tokenize(expression: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<CharIndices> = expression.char_indices().peekable();
+
+    while let Some(&(start, char)) = chars.peek() {
+        let rest = &expression[start..];
+        let mut take_while = |condition: fn(char) -> bool| {
+            let mut end = expression.len();
+            while let Some(&(index, char)) = chars.peek() {
+                if !condition(char) { end = index; break }
+                chars.next();
+            }
+            &expression[start..end]
+        };
+
+        if char.is_whitespace() {
+            chars.next();
+        } else if char.is_ascii_digit() {
+            tokens.push(Token::Number(take_while(|char| char.is_ascii_digit() || char == '.')));
+        } else if char.is_alphanumeric() || char == '_' {
+            tokens.push(Token::Identifier(take_while(|char| char.is_alphanumeric() || char == '_')));
+        } else if char == '"' {
+            let end = rest[1..].find('"').ok_or_else(|| format!("Unterminated string in {}", expression))?;
+            tokens.push(Token::String(&rest[1..=end]));
+            for _ in 0..rest[..end + 2].chars().count() { chars.next(); }
+        } else if char == '(' {
+            tokens.push(Token::Open);
+            chars.next();
+        } else if char == ')' {
+            tokens.push(Token::Close);
+            chars.next();
+        } else {
+            let operator = OPERATORS.iter().find(|&&operator| rest.starts_with(operator)).ok_or_else(|| format!("Unexpected {} in {}", char, expression))?;
+            // a single = means the same as ==
+            tokens.push(Token::Operator(if *operator == "=" { "==" } else { operator }));
+            for _ in 0..operator.len() { chars.next(); }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a, 'b> {
+    tokens: Vec<Token<'a>>,
+    index: usize,
+    parameters: &'b HashMap<String, String>,
+}
+impl Parser<'_, '_> {
+    fn peek_operator(&self, operators: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.index) {
+            Some(Token::Operator(operator)) if operators.contains(operator) => Some(operator),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, operators: &[&str], next: fn(&mut Self) -> Result<Value, String>) -> Result<Value, String> {
+        let mut left = next(self)?;
+        while let Some(operator) = self.peek_operator(operators) {
+            self.index += 1;
+            let right = next(self)?;
+            left = apply(operator, left, right)?;
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Value, String> { self.binary(&["||"], Self::and) }
+    fn and(&mut self) -> Result<Value, String> { self.binary(&["&&"], Self::comparison) }
+    fn comparison(&mut self) -> Result<Value, String> { self.binary(&["==", "!=", "<", "<=", ">", ">="], Self::sum) }
+    fn sum(&mut self) -> Result<Value, String> { self.binary(&["+", "-"], Self::product) }
+    fn product(&mut self) -> Result<Value, String> { self.binary(&["*", "/", "%"], Self::unary) }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        match self.peek_operator(&["!", "-"]) {
+            Some("!") => {
+                self.index += 1;
+                match self.unary()? {
+                    Value::Bool(bool) => Ok(Value::Bool(!bool)),
+                    other => Err(format!("Can't negate {} {}", other.type_name(), other)),
+                }
+            },
+            Some(_) => {
+                self.index += 1;
+                apply("-", Value::Int(0), self.unary()?)
+            },
+            None => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<Value, String> {
+        let token = self.tokens.get(self.index).cloned().ok_or_else(|| String::from("Unexpected end of expression"))?;
+        self.index += 1;
+
+        match token {
+            Token::Identifier("true") => Ok(Value::Bool(true)),
+            Token::Identifier("false") => Ok(Value::Bool(false)),
+            Token::Identifier(identifier) => self.parameters.get(identifier)
+                .map(|value| Value::infer(value))
+                .ok_or_else(|| format!("Unknown parameter {}", identifier)),
+            Token::Number(number) => number.parse().map(Value::Int)
+                .or_else(|_| number.parse().map(Value::Float))
+                .map_err(|_| format!("Invalid number {}", number)),
+            Token::String(string) => Ok(Value::String(string.to_string())),
+            Token::Open => {
+                let value = self.or()?;
+                match self.tokens.get(self.index) {
+                    Some(Token::Close) => { self.index += 1; Ok(value) },
+                    _ => Err(String::from("Missing closing parenthesis")),
+                }
+            },
+            Token::Operator(operator) => Err(format!("Unexpected {}", operator)),
+            Token::Close => Err(String::from("Unexpected )")),
+        }
+    }
+}
+
+fn apply(operator: &str, left: Value, right: Value) -> Result<Value, String> {
+    let mismatch = |left: &Value, right: &Value| format!("Can't apply {} to {} {} and {} {}", operator, left.type_name(), left, right.type_name(), right);
+
+    match operator {
+        "&&" | "||" => match (&left, &right) {
+            (Value::Bool(left), Value::Bool(right)) => Ok(Value::Bool(if operator == "&&" { *left && *right } else { *left || *right })),
+            _ => Err(mismatch(&left, &right)),
+        },
+        // values of different types are compared as text, so `strict == true` works however strict was declared
+        "==" | "!=" => {
+            let equal = match (left.as_float(), right.as_float()) {
+                (Some(left), Some(right)) => (left - right).abs() < f64::EPSILON,
+                _ => left.to_string() == right.to_string(),
+            };
+            Ok(Value::Bool(equal == (operator == "==")))
+        },
+        "<" | "<=" | ">" | ">=" => {
+            let (left_float, right_float) = left.as_float().zip(right.as_float()).ok_or_else(|| mismatch(&left, &right))?;
+            Ok(Value::Bool(match operator {
+                "<" => left_float < right_float,
+                "<=" => left_float <= right_float,
+                ">" => left_float > right_float,
+                _ => left_float >= right_float,
+            }))
+        },
+        _ => match (&left, &right) {
+            (Value::Int(left), Value::Int(right)) => {
+                let result = match operator {
+                    "+" => left.checked_add(*right),
+                    "-" => left.checked_sub(*right),
+                    "*" => left.checked_mul(*right),
+                    "/" => left.checked_div(*right),
+                    _ => left.checked_rem(*right),
+                };
+                result.map(Value::Int).ok_or_else(|| format!("Overflow or division by zero in {} {} {}", left, operator, right))
+            },
+            (Value::String(left), Value::String(right)) if operator == "+" => Ok(Value::String(format!("{}{}", left, right))),
+            _ => {
+                let (left_float, right_float) = left.as_float().zip(right.as_float()).ok_or_else(|| mismatch(&left, &right))?;
+                Ok(Value::Float(match operator {
+                    "+" => left_float + right_float,
+                    "-" => left_float - right_float,
+                    "*" => left_float * right_float,
+                    "/" => left_float / right_float,
+                    _ => left_float % right_float,
+                }))
+            },
+        },
+    }
+}
+
+/// Evaluates an expression over the values of `parameters`
+///
+/// Expressions may use `&&`, `||`, `!`, the comparisons `==`, `!=`, `<`, `<=`, `>`, `>=`, the arithmetic operators `+`, `-`, `*`, `/`, `%` and parentheses.
+/// Bare words name parameters, text in other places has to be quoted
+pub fn evaluate(expression: &str, parameters: &HashMap<String, String>) -> Result<Value, Error> {
+    let error = |message| Error::Expression { expression: expression.to_string(), message };
+
+    let mut parser = Parser { tokens: tokenize(expression).map_err(error)?, index: 0, parameters };
+    if parser.tokens.is_empty() {
+        return Err(error(String::from("Empty expression")));
+    }
+
+    let value = parser.or().map_err(error)?;
+    if parser.index < parser.tokens.len() {
+        return Err(error(format!("Expected an operator after {} tokens in {}", parser.index, expression)));
+    }
+    Ok(value)
+}
+
+/// Evaluates a condition for `!!if` and `!!elif`
+///
+/// Besides expressions this accepts the older `<parameter> <value>`, which compares the parameter to the value as text.
+/// A single word after a parameter is read that way, so `offset -1` compares instead of subtracting
+pub fn evaluate_condition(condition: &str, parameters: &HashMap<String, String>) -> Result<bool, Error> {
+    if let Some((identifier, value)) = condition.split_once(' ') {
+        let value = value.trim();
+        let is_expression = CONDITION_OPERATORS.iter().any(|operator| value.starts_with(operator)) || value.contains(char::is_whitespace);
+        if parameters.contains_key(identifier) && !is_expression {
+            return Ok(parameters[identifier] == value);
+        }
+    }
+
+    match evaluate(condition, parameters)? {
+        Value::Bool(bool) => Ok(bool),
+        other => Err(Error::Expression {
+            expression: condition.to_string(),
+            message: format!("Expected a condition, but {} is the {} {}", condition, other.type_name(), other),
+        }),
+    }
+}
+
+/// Whether `command`, without the leading `!!`, is one of `if`, `elif`, `else` and `endif`
+pub fn is_condition(command: &str) -> bool {
+    let name = command.split_whitespace().next().unwrap_or("");
+    matches!(name, "if" | "elif" | "else" | "endif")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Branch {
+    /// The lines in this branch are used
+    Taken,
+    /// No branch has been taken yet, a later `!!elif` or `!!else` might be
+    Pending,
+    /// A branch has been taken already, or the surrounding branch isn't
+    Done,
+}
+
+/// Keeps track of which lines `!!if`, `!!elif`, `!!else` and `!!endif` let through
+#[derive(Debug, Default)]
+pub struct Conditions {
+    branches: Vec<(Branch, bool)>,
+}
+impl Conditions {
+    /// Whether the current line is in a taken branch
+    pub fn active(&self) -> bool {
+        self.branches.iter().all(|(branch, _)| *branch == Branch::Taken)
+    }
+
+    /// How many conditions are open
+    pub fn depth(&self) -> usize {
+        self.branches.len()
+    }
+
+    fn outer_active(&self) -> bool {
+        self.branches[..self.branches.len() - 1].iter().all(|(branch, _)| *branch == Branch::Taken)
+    }
+
+    /// Applies `command` if it is one of the conditional commands, returns whether it was
+    ///
+    /// Conditions are only evaluated if their branch could be taken
+    pub fn command(&mut self, command: &str, parameters: &HashMap<String, String>) -> Result<bool, Error> {
+        let (name, condition) = command.split_once(' ').unwrap_or((command, ""));
+        let condition = condition.trim();
+
+        match name.trim_end() {
+            "if" => {
+                let branch = if !self.active() {
+                    Branch::Done
+                } else if evaluate_condition(condition, parameters)? {
+                    Branch::Taken
+                } else { Branch::Pending };
+                self.branches.push((branch, false));
+            },
+            "elif" => {
+                let outer_active = !self.branches.is_empty() && self.outer_active();
+                let (branch, seen_else) = self.branches.last_mut().ok_or_else(|| Error::Condition(String::from("!!elif without !!if")))?;
+                if *seen_else { return Err(Error::Condition(String::from("!!elif after !!else"))) }
+                *branch = match branch {
+                    Branch::Pending if outer_active && evaluate_condition(condition, parameters)? => Branch::Taken,
+                    Branch::Pending => Branch::Pending,
+                    _ => Branch::Done,
+                };
+            },
+            "else" => {
+                let (branch, seen_else) = self.branches.last_mut().ok_or_else(|| Error::Condition(String::from("!!else without !!if")))?;
+                if *seen_else { return Err(Error::Condition(String::from("!!else after !!else"))) }
+                *seen_else = true;
+                *branch = if *branch == Branch::Pending { Branch::Taken } else { Branch::Done };
+            },
+            "endif" => { self.branches.pop().ok_or_else(|| Error::Condition(String::from("!!endif without !!if")))?; },
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions() {
+        let parameters = [("lines", "3"), ("strict", "true"), ("speed", "1.5"), ("mode", "hard")].iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        let evaluate = |expression| evaluate(expression, &parameters);
+
+        assert_eq!(evaluate("lines * 2 + 1").unwrap(), Value::Int(7));
+        assert_eq!(evaluate("(lines + 1) * 2").unwrap(), Value::Int(8));
+        assert_eq!(evaluate("lines / 2").unwrap(), Value::Int(1));
+        assert_eq!(evaluate("speed * 2").unwrap(), Value::Float(3.0));
+        assert_eq!(evaluate("-lines").unwrap(), Value::Int(-3));
+        assert_eq!(evaluate("lines >= 3 && !(mode == \"easy\")").unwrap(), Value::Bool(true));
+        assert_eq!(evaluate("strict == false || speed < 1").unwrap(), Value::Bool(false));
+        assert_eq!(evaluate("mode = \"hard\"").unwrap(), Value::Bool(true));
+        assert!(evaluate("lines / 0").is_err());
+        assert!(evaluate("missing > 1").is_err());
+        assert!(evaluate("strict + 1").is_err());
+        assert!(evaluate("(lines").is_err());
+        assert!(evaluate("lines lines").is_err());
+        assert_eq!(evaluate(""), Err(Error::Expression { expression: String::new(), message: String::from("Empty expression") }));
+
+        assert!(evaluate_condition("strict true", &parameters).unwrap());
+        assert!(!evaluate_condition("mode easy", &parameters).unwrap());
+        assert!(evaluate_condition("strict", &parameters).unwrap());
+        assert!(evaluate_condition("lines", &parameters).is_err());
+        assert!(!evaluate_condition("lines -1", &parameters).unwrap());
+        assert!(evaluate_condition("lines 3", &parameters).unwrap());
+        assert!(evaluate_condition("lines - 1 == 2", &parameters).unwrap());
+        assert!(evaluate_condition("lines >= 3", &parameters).unwrap());
+    }
+
+    #[test]
+    fn conditions() {
+        let parameters = [("a", "1"), ("b", "2")].iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+        let header = [
+            "if a == 2", "one", "if missing", "skipped", "endif", "elif b == 2", "two", "else", "three", "endif",
+            "if a == 1", "four", "elif missing", "five", "endif",
+            "if a == 2", "elif a == 3", "else", "six", "endif",
+        ];
+
+        let mut conditions = Conditions::default();
+        let mut used = Vec::new();
+        for line in header.iter() {
+            if !conditions.command(line, &parameters).unwrap() && conditions.active() {
+                used.push(*line);
+            }
+        }
+        assert_eq!(used, ["two", "four", "six"]);
+        assert_eq!(conditions.depth(), 0);
+
+        assert_eq!(conditions.command("endif", &parameters), Err(Error::Condition(String::from("!!endif without !!if"))));
+        assert!(conditions.command("else", &parameters).is_err());
+        conditions.command("if a == 1", &parameters).unwrap();
+        conditions.command("else", &parameters).unwrap();
+        assert!(conditions.command("elif b == 2", &parameters).is_err());
+        assert!(matches!(Conditions::default().command("if missing", &parameters), Err(Error::Expression { .. })));
+    }
+}
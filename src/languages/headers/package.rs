@@ -0,0 +1,247 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    error::Error,
+    settings::Settings,
+};
+
+/// The file in a package directory describing the package
+pub const MANIFEST: &str = "package.json";
+/// The extension of single-file package archives
+pub const ARCHIVE_EXTENSION: &str = "wotwrp";
+
+/// What a package consists of
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Headers in the package directory or the headers folder
+    pub headers: Vec<PathBuf>,
+    /// Default header arguments in the form `<header>.<parameter>=<value>`
+    #[serde(default)]
+    pub header_args: Vec<String>,
+    /// Presets in the package directory or the presets folder, applied along with the package
+    #[serde(default)]
+    pub presets: Vec<PathBuf>,
+}
+
+/// A package directory bundling headers, their arguments and presets into a single mode
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub directory: PathBuf,
+    pub manifest: Manifest,
+}
+
+/// A package with all its files in one file, to be installed into the headers folder
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    #[serde(flatten)]
+    manifest: Manifest,
+    /// The base64 encoded contents of every file, so that binary files survive as well
+    files: BTreeMap<String, String>,
+}
+
+fn package_error(message: impl Into<String>) -> Error {
+    Error::Package(message.into())
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|err| Error::ReadFile { path: path.to_path_buf(), message: err.to_string() })
+}
+
+fn read_bytes(path: &Path) -> Result<Vec<u8>, Error> {
+    fs::read(path).map_err(|err| Error::ReadFile { path: path.to_path_buf(), message: err.to_string() })
+}
+
+/// Names have to be a plain file name, so that they can't point anywhere outside the package directory
+fn check_name(name: &str, what: &str) -> Result<(), Error> {
+    let mut components = Path::new(name).components();
+    let plain = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !plain || name == MANIFEST || name.contains(&['/', '\\'][..]) || name.starts_with('.') {
+        return Err(package_error(format!("Invalid {} name {}", what, name)));
+    }
+    Ok(())
+}
+
+impl Package {
+    /// Reads the package in `directory`
+    pub fn read(directory: &Path) -> Result<Package, Error> {
+        let manifest = read(&directory.join(MANIFEST))?;
+        let manifest: Manifest = serde_json::from_str(&manifest).map_err(|err| package_error(format!("Invalid manifest in {}: {}", directory.display(), err)))?;
+        check_name(&manifest.name, "package")?;
+
+        Ok(Package { directory: directory.to_path_buf(), manifest })
+    }
+
+    /// Finds the package called `name`, either at that path or in the headers folder
+    pub fn find(name: &Path) -> Result<Option<Package>, Error> {
+        [name.to_path_buf(), Path::new("headers").join(name)].iter()
+            .find(|directory| directory.join(MANIFEST).is_file())
+            .map(|directory| Package::read(directory))
+            .transpose()
+    }
+
+    fn resolve(&self, file: &Path, extension: &str) -> PathBuf {
+        let mut bundled = self.directory.join(file);
+        bundled.set_extension(extension);
+        if bundled.is_file() { bundled } else { file.to_path_buf() }
+    }
+
+    /// The paths of the package's headers, preferring the ones bundled in the package
+    pub fn headers(&self) -> Vec<PathBuf> {
+        self.manifest.headers.iter().map(|header| self.resolve(header, "wotwrh")).collect()
+    }
+
+    /// The paths of the package's presets, preferring the ones bundled in the package
+    pub fn presets(&self) -> Vec<PathBuf> {
+        self.manifest.presets.iter().map(|preset| self.resolve(preset, "json")).collect()
+    }
+
+    /// Settings that select the package
+    pub fn settings(&self) -> Settings {
+        Settings {
+            presets: self.presets(),
+            header_list: self.headers(),
+            header_args: self.manifest.header_args.clone(),
+            ..Settings::default()
+        }
+    }
+
+    /// The headers bundled in the package directory
+    pub fn bundled_headers(&self) -> Result<Vec<PathBuf>, Error> {
+        let read_error = |err: std::io::Error| Error::ReadFile { path: self.directory.clone(), message: err.to_string() };
+
+        let mut headers = Vec::new();
+        for entry in fs::read_dir(&self.directory).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            if path.extension().map_or(false, |extension| extension == "wotwrh") {
+                headers.push(path);
+            }
+        }
+        headers.sort_unstable();
+
+        Ok(headers)
+    }
+
+    /// Bundles the manifest and all files next to it into an archive
+    pub fn pack(&self) -> Result<String, Error> {
+        let read_error = |err: std::io::Error| Error::ReadFile { path: self.directory.clone(), message: err.to_string() };
+
+        let mut files = BTreeMap::new();
+        for entry in fs::read_dir(&self.directory).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if !path.is_file() || name == MANIFEST { continue }
+
+            files.insert(name, BASE64.encode(read_bytes(&path)?));
+        }
+
+        let archive = Archive { manifest: self.manifest.clone(), files };
+        serde_json::to_string_pretty(&archive).map_err(|err| Error::Serialize { what: "package", message: err.to_string() })
+    }
+}
+
+/// Unpacks an archive created by [`Package::pack`] into `folder`, returning the package directory
+///
+/// Refuses to overwrite an installed package of the same name
+pub fn install(archive: &Path, folder: &Path) -> Result<Package, Error> {
+    let contents = read(archive)?;
+    let Archive { manifest, files } = serde_json::from_str(&contents).map_err(|err| package_error(format!("Invalid package archive {}: {}", archive.display(), err)))?;
+
+    check_name(&manifest.name, "package")?;
+    let files = files.into_iter().map(|(name, contents)| {
+        check_name(&name, "file")?;
+        let contents = BASE64.decode(contents).map_err(|err| package_error(format!("Invalid contents of {} in {}: {}", name, archive.display(), err)))?;
+        Ok((name, contents))
+    }).collect::<Result<Vec<_>, Error>>()?;
+
+    let directory = folder.join(&manifest.name);
+    if directory.exists() {
+        return Err(package_error(format!("{} is already installed at {}", manifest.name, directory.display())));
+    }
+
+    let create_error = |path: &Path, err: std::io::Error| Error::CreateFile { path: path.to_path_buf(), message: err.to_string() };
+    fs::create_dir_all(&directory).map_err(|err| create_error(&directory, err))?;
+    for (name, contents) in files {
+        let path = directory.join(name);
+        fs::write(&path, contents).map_err(|err| create_error(&path, err))?;
+    }
+    let path = directory.join(MANIFEST);
+    let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|err| Error::Serialize { what: "package manifest", message: err.to_string() })?;
+    fs::write(&path, manifest_json).map_err(|err| create_error(&path, err))?;
+
+    Ok(Package { directory, manifest })
+}
+
+/// The packages in the headers folder, packages that can't be read are logged and skipped
+pub fn find_packages() -> Result<Vec<Package>, Error> {
+    let folder = Path::new("headers");
+    let read_error = |err: std::io::Error| Error::ReadFile { path: folder.to_path_buf(), message: err.to_string() };
+
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(folder).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if path.join(MANIFEST).is_file() {
+            match Package::read(&path) {
+                Ok(package) => packages.push(package),
+                Err(err) => log::warn!("Skipping the package in {}: {}", path.display(), err),
+            }
+        }
+    }
+    packages.sort_unstable_by(|a, b| a.manifest.name.cmp(&b.manifest.name));
+
+    Ok(packages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packages() {
+        let package = Package::find(Path::new("bingo_launch")).unwrap().unwrap();
+        assert_eq!(package.headers(), [PathBuf::from("bingo"), PathBuf::from("launch_from_bingo")]);
+        let settings = Settings::from_preset(PathBuf::from("bingo_launch")).unwrap().apply_presets().unwrap();
+        assert!(settings.header_list.ends_with(&package.headers()));
+        assert!(settings.header_list.contains(&PathBuf::from("progress_helper")), "should apply the qol preset");
+        assert!(settings.header_args.contains(&String::from("bingo.lines=3")));
+        assert!(find_packages().unwrap().iter().any(|package| package.manifest.name == "bingo_launch"));
+        assert!(Package::find(Path::new("bingo")).unwrap().is_none());
+
+        let folder = std::env::temp_dir().join(format!("seedgen_packages_{}", std::process::id()));
+        let archive_path = folder.join("test.wotwrp");
+        let mut archive = serde_json::from_str::<serde_json::Value>(&package.pack().unwrap()).unwrap();
+        archive["name"] = serde_json::json!("test");
+        archive["files"]["extra.wotwrh"] = serde_json::json!(BASE64.encode("3|0|6|extra"));
+        archive["files"]["icon.bin"] = serde_json::json!(BASE64.encode([0, 159, 146, 150]));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(&archive_path, archive.to_string()).unwrap();
+
+        let installed = install(&archive_path, &folder).unwrap();
+        assert_eq!(installed.bundled_headers().unwrap(), [folder.join("test").join("extra.wotwrh")]);
+        assert_eq!(fs::read(folder.join("test").join("icon.bin")).unwrap(), [0, 159, 146, 150]);
+        assert!(install(&archive_path, &folder).is_err());
+
+        archive["name"] = serde_json::json!("other");
+        for name in ["../escape.wotwrh", "..", "/absolute.wotwrh"] {
+            let mut escaping = archive.clone();
+            escaping["files"][name] = serde_json::json!("");
+            fs::write(&archive_path, escaping.to_string()).unwrap();
+            assert!(install(&archive_path, &folder).is_err(), "{} was accepted", name);
+        }
+        assert!(!folder.join("other").exists());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
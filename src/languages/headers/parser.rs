@@ -1,5 +1,6 @@
 use std::{
     collections::HashMap,
+    fmt,
     path::{Path, PathBuf},
     str::FromStr, convert::TryFrom,
 };
@@ -7,9 +8,17 @@ use std::{
 use decorum::R32;
 use rand::Rng;
 use regex::Regex;
+use serde::Serialize;
+
+use super::{
+    expression::{self, Conditions},
+    template,
+    version::{Version, Requirement},
+};
 
 use crate::{
     ItemDetails,
+    error::Error,
     world::{
         World,
         graph::Graph,
@@ -27,7 +36,7 @@ where
     if parts.next().is_some() { return Err(String::from("too many parts")); }
     Ok(())
 }
-fn parse_uber_state<'a, I>(parts: &mut I) -> Result<UberState, String>
+pub(crate) fn parse_uber_state<'a, I>(parts: &mut I) -> Result<UberState, String>
 where
     I: Iterator<Item = &'a str>,
 {
@@ -897,13 +906,22 @@ where P: Iterator<Item=&'a str>
         _ => Err(String::from("invalid item type")),
     }
 }
-pub fn parse_item(item: &str) -> Result<Item, String> {
+pub fn parse_item(item: &str) -> Result<Item, Error> {
     let parts = item.trim().split('|');
 
-    parse_item_parts(parts).map_err(|err| format!("{} in item {}", err, item))
+    parse_item_parts(parts).map_err(|message| Error::ParseItem { item: item.to_string(), message })
 }
+pub fn parse_pickup(pickup: &str) -> Result<(UberState, Item), String> {
+    let mut parts = pickup.trim().splitn(3, '|');
+    let uber_state = parse_uber_state(&mut parts).map_err(|err| format!("malformed pickup {}: {}", pickup, err))?;
+
+    let item = parts.next().ok_or_else(|| format!("malformed pickup {}", pickup))?;
+    let item = parse_item(item).map_err(|err| err.to_string())?;
 
-fn parse_count(item: &mut &str) -> u16 {
+    Ok((uber_state, item))
+}
+
+pub(crate) fn parse_count(item: &mut &str) -> u16 {
     if let Some(index) = item.find('x') {
         let amount = item[..index].trim();
         if let Ok(amount) = amount.parse::<u16>() {
@@ -948,8 +966,11 @@ where R: Rng + ?Sized
 
     Ok(processed)
 }
+/// Replaces every `$PARAM(...)` in `line`
+///
+/// A parameter name is replaced with its value, anything else is evaluated as an expression, e.g. `$PARAM(lines * 2)`
 #[inline]
-fn apply_parameters(line: &mut String, parameters: &HashMap<String, String>) -> Result<(), String> {
+pub(crate) fn apply_parameters(line: &mut String, parameters: &HashMap<String, String>) -> Result<(), String> {
     let mut last_index = 0;
     loop {
         if let Some(mut start_index) = line[last_index..].find("$PARAM(") {
@@ -961,11 +982,12 @@ fn apply_parameters(line: &mut String, parameters: &HashMap<String, String>) ->
             if let Some(end_index) = read_args(line, after_bracket) {
                 let identifier = line[after_bracket..end_index].trim();
 
-                let value = parameters
-                    .get(identifier)
-                    .ok_or_else(|| format!("Unknown parameter {}", identifier))?;
+                let value = match parameters.get(identifier) {
+                    Some(value) => value.clone(),
+                    None => expression::evaluate(identifier, parameters).map_err(|err| err.to_string())?.to_string(),
+                };
 
-                line.replace_range(start_index..=end_index, value);
+                line.replace_range(start_index..=end_index, &value);
 
                 continue;
             }
@@ -988,9 +1010,9 @@ fn exclude_command(name: &Path, exclude: &str, excludes: &mut HashMap<String, St
     excludes.insert(exclude.to_string(), name);
 }
 #[inline]
-fn add_command(mut item: &str, world: &mut World) -> Result<(), String> {
+pub(crate) fn add_command(mut item: &str, world: &mut World) -> Result<(), String> {
     let count = parse_count(&mut item);
-    let item = parse_item(item)?;
+    let item = parse_item(item).map_err(|err| err.to_string())?;
 
     log::trace!("adding {}{} to the item pool", if count == 1 { String::new() } else { format!("{}x ", count) }, item);
 
@@ -1010,9 +1032,9 @@ fn remove_from_pool(mut item: &Item, amount: u16, world: &mut World, negative_in
     }
 }
 #[inline]
-fn remove_command(mut item: &str, world: &mut World, negative_inventory: &mut Inventory) -> Result<(), String> {
+pub(crate) fn remove_command(mut item: &str, world: &mut World, negative_inventory: &mut Inventory) -> Result<(), String> {
     let count = parse_count(&mut item);
-    let item = parse_item(item)?;
+    let item = parse_item(item).map_err(|err| err.to_string())?;
 
     log::trace!("removing {}{} from the item pool", if count == 1 { String::new() } else { format!("{}x ", count) }, item);
     remove_from_pool(&item, count, world, negative_inventory);
@@ -1020,10 +1042,10 @@ fn remove_command(mut item: &str, world: &mut World, negative_inventory: &mut In
     Ok(())
 }
 #[inline]
-fn name_command(naming: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
+pub(crate) fn name_command(naming: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
     let mut parts = naming.splitn(2, ' ');
     let item = parts.next().unwrap();
-    parse_item(item)?;
+    parse_item(item).map_err(|err| err.to_string())?;
     let name = parts.next().ok_or_else(|| String::from("Missing name"))?;
 
     let entry = custom_items.entry(item.to_owned()).or_default();
@@ -1032,10 +1054,10 @@ fn name_command(naming: &str, custom_items: &mut HashMap<String, ItemDetails>) -
     Ok(())
 }
 #[inline]
-fn display_command(display: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
+pub(crate) fn display_command(display: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
     let mut parts = display.splitn(2, ' ');
     let item = parts.next().unwrap();
-    parse_item(item)?;
+    parse_item(item).map_err(|err| err.to_string())?;
     let display = parts.next().ok_or_else(|| String::from("Missing display name"))?;
 
     let entry = custom_items.entry(item.to_owned()).or_default();
@@ -1044,10 +1066,10 @@ fn display_command(display: &str, custom_items: &mut HashMap<String, ItemDetails
     Ok(())
 }
 #[inline]
-fn description_command(description: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
+pub(crate) fn description_command(description: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
     let mut parts = description.splitn(2, ' ');
     let item = parts.next().unwrap();
-    parse_item(item)?;
+    parse_item(item).map_err(|err| err.to_string())?;
     let description = parts.next().ok_or_else(|| String::from("Missing description"))?;
 
     let entry = custom_items.entry(item.to_owned()).or_default();
@@ -1056,10 +1078,10 @@ fn description_command(description: &str, custom_items: &mut HashMap<String, Ite
     Ok(())
 }
 #[inline]
-fn price_command(price: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
+pub(crate) fn price_command(price: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
     let mut parts = price.splitn(2, ' ');
     let item = parts.next().unwrap();
-    parse_item(item)?;
+    parse_item(item).map_err(|err| err.to_string())?;
     let price = parts.next().ok_or_else(|| String::from("Missing price"))?;
     let price: u16 = price.parse().map_err(|_| format!("invalid price {}", price))?;
 
@@ -1069,10 +1091,10 @@ fn price_command(price: &str, custom_items: &mut HashMap<String, ItemDetails>) -
     Ok(())
 }
 #[inline]
-fn icon_command(icon: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
+pub(crate) fn icon_command(icon: &str, custom_items: &mut HashMap<String, ItemDetails>) -> Result<(), String> {
     let mut parts = icon.splitn(2, ' ');
     let item = parts.next().unwrap();
-    parse_item(item)?;
+    parse_item(item).map_err(|err| err.to_string())?;
     let icon = parts.next().ok_or_else(|| String::from("Missing icon"))?;
     let icon = parse_icon(icon)?;
 
@@ -1081,31 +1103,101 @@ fn icon_command(icon: &str, custom_items: &mut HashMap<String, ItemDetails>) ->
 
     Ok(())
 }
-#[inline]
-fn parameter_command(parameter: &str, parameters: &mut HashMap<String, String>, param_values: &HashMap<&str, &str>) -> Result<(), String> {
-    let mut parts = parameter.splitn(2, ' ');
-    let identifier = parts.next().unwrap();
-    let default = parts.next().ok_or_else(|| String::from("Missing default value"))?;
-
-    let mut default_parts = default.splitn(2, ':');
-    let first_part = default_parts.next().unwrap();
-    let (parameter_type, default) = if let Some(default) = default_parts.next() {
-        (first_part, default)
-    } else {
-        ("string", first_part)
-    };
-    let value = param_values.get(identifier).map_or(default, |value| &value[..]);
-
-    match parameter_type {
-        "bool" => { value.parse::<bool>().map_err(|_| format!("Invalid value {} for boolean {}", value, identifier))?; },
-        "int" => { value.parse::<i64>().map_err(|_| format!("Invalid value {} for integer {}", value, identifier))?; },
-        "float" => { value.parse::<R32>().map_err(|_| format!("Invalid value {} for float {}", value, identifier))?; },
-        "string" => {},
-        _ => return Err(format!("Invalid parameter type {}", parameter_type)),
+/// The type a `!!parameter` is declared with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ParameterType {
+    Bool,
+    Int,
+    Float,
+    String,
+    /// One of a list of allowed values, declared as `enum(<value>,<value>...)`
+    Enum(Vec<String>),
+}
+impl fmt::Display for ParameterType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterType::Bool => write!(f, "bool"),
+            ParameterType::Int => write!(f, "int"),
+            ParameterType::Float => write!(f, "float"),
+            ParameterType::String => write!(f, "string"),
+            ParameterType::Enum(values) => write!(f, "one of {}", values.join(", ")),
+        }
+    }
+}
+impl FromStr for ParameterType {
+    type Err = String;
+
+    fn from_str(parameter_type: &str) -> Result<ParameterType, String> {
+        match parameter_type {
+            "bool" => Ok(ParameterType::Bool),
+            "int" => Ok(ParameterType::Int),
+            "float" => Ok(ParameterType::Float),
+            "string" => Ok(ParameterType::String),
+            _ => {
+                let values = parameter_type.strip_prefix("enum(").and_then(|values| values.strip_suffix(')'))
+                    .ok_or_else(|| format!("Invalid parameter type {}", parameter_type))?;
+                let values = values.split(',').map(|value| value.trim().to_string()).collect::<Vec<_>>();
+                if values.iter().any(String::is_empty) {
+                    return Err(format!("Empty value in parameter type {}", parameter_type));
+                }
+                Ok(ParameterType::Enum(values))
+            },
+        }
+    }
+}
+
+/// A `!!parameter <identifier> <type>:<default>` declaration, the type defaults to string
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Parameter {
+    pub identifier: String,
+    pub parameter_type: ParameterType,
+    pub default: String,
+}
+impl Parameter {
+    pub fn parse(parameter: &str) -> Result<Parameter, String> {
+        let mut parts = parameter.splitn(2, ' ');
+        let identifier = parts.next().unwrap();
+        let default = parts.next().ok_or_else(|| String::from("Missing default value"))?;
+
+        let mut default_parts = default.splitn(2, ':');
+        let first_part = default_parts.next().unwrap();
+        let (parameter_type, default) = if let Some(default) = default_parts.next() {
+            (first_part.parse()?, default)
+        } else {
+            (ParameterType::String, first_part)
+        };
+
+        let parameter = Parameter { identifier: identifier.to_string(), parameter_type, default: default.to_string() };
+        parameter.check(default)?;
+        Ok(parameter)
     }
 
-    if parameters.insert(identifier.to_string(), value.to_string()).is_some() {
-        log::warn!("Parameter {} already declared", identifier);
+    /// Checks whether `value` fits the parameter's type
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let identifier = &self.identifier;
+        match &self.parameter_type {
+            ParameterType::Bool => { value.parse::<bool>().map_err(|_| format!("Invalid value {} for boolean {}", value, identifier))?; },
+            ParameterType::Int => { value.parse::<i64>().map_err(|_| format!("Invalid value {} for integer {}", value, identifier))?; },
+            ParameterType::Float => { value.parse::<R32>().map_err(|_| format!("Invalid value {} for float {}", value, identifier))?; },
+            ParameterType::String => {},
+            ParameterType::Enum(values) => if !values.iter().any(|allowed| allowed == value) {
+                return Err(format!("Invalid value {} for {}, expected one of {}", value, identifier, values.join(", ")));
+            },
+        }
+        Ok(())
+    }
+}
+
+#[inline]
+pub(crate) fn parameter_command(parameter: &str, parameters: &mut HashMap<String, String>, param_values: &HashMap<&str, &str>) -> Result<(), String> {
+    let parameter = Parameter::parse(parameter)?;
+    let value = param_values.get(&parameter.identifier[..]).map_or(&parameter.default[..], |value| &value[..]);
+    parameter.check(value)?;
+
+    if parameters.insert(parameter.identifier.clone(), value.to_string()).is_some() {
+        log::warn!("Parameter {} already declared", parameter.identifier);
     }
 
     Ok(())
@@ -1198,18 +1290,13 @@ fn set_command(identifier: &str, world: &mut World, sets: &mut Vec<String>) -> R
     Ok(())
 }
 #[inline]
-fn if_command(comparison: &str, parameters: &HashMap<String, String>) -> Result<bool, String> {
-    let mut parts = comparison.splitn(2, ' ');
-    let identifier = parts.next().unwrap();
-    let compare_value = parts.next().ok_or_else(|| String::from("Missing comparison value"))?;
-
-    let parameter_value = parameters
-        .get(identifier)
-        .ok_or_else(|| format!("Unknown parameter {}", identifier))?;
-
-    Ok(compare_value == parameter_value)
+pub(crate) fn version_command(version: &str) -> Result<(), String> {
+    version.parse::<Version>().map(|_| ())
+}
+#[inline]
+pub(crate) fn requires_command(requirement: &str) -> Result<(), String> {
+    requirement.parse::<Requirement>()?.check()
 }
-
 #[derive(Debug, Default)]
 pub struct HeaderContext {
     pub dependencies: Vec<PathBuf>,
@@ -1220,98 +1307,117 @@ pub struct HeaderContext {
     pub negative_inventory: Inventory,
 }
 
-pub fn parse_header<R>(name: &Path, header: &str, world: &mut World, context: &mut HeaderContext, param_values: &HashMap<&str, HashMap<&str, &str>>, rng: &mut R) -> Result<String, String>
+/// The commands a header may use after `!!`
+///
+/// Conditions are handled by [`Conditions`], macros and loops by [`template::expand`] and everything else by `parse_header`
+pub const COMMANDS: &[&str] = &[
+    "include", "exclude", "add", "remove", "name", "display", "description", "price",
+    "icon", "parameter", "pool", "addpool", "flush", "set", "version", "requires",
+    "if", "elif", "else", "endif", "macro", "endmacro", "for", "endfor",
+];
+
+/// Applies `!!if`, `!!elif`, `!!else` and `!!endif` lines, returns whether the line should be skipped
+///
+/// This comes first, so lines in skipped branches neither evaluate parameters nor draw from the pool
+fn skip_conditional(line: &str, conditions: &mut Conditions, parameters: &HashMap<String, String>) -> Result<bool, String> {
+    let code = line.trim();
+    let code = code.find("//").map_or(code, |index| &code[..index]);
+    if let Some(command) = code.strip_prefix("!!") {
+        if expression::is_condition(command) {
+            let mut command = command.trim_end().to_string();
+            apply_parameters(&mut command, parameters)?;
+            conditions.command(&command, parameters).map_err(|err| format!("{} in {}", err, line.trim()))?;
+            return Ok(true);
+        }
+    }
+
+    Ok(!conditions.active())
+}
+
+/// A line in a header that couldn't be parsed, along with its 0-based index
+pub type LineError = (usize, String);
+
+fn header_error(name: &Path) -> impl Fn(usize, String) -> Result<(), Error> + '_ {
+    move |index, message| Err(Error::ParseHeader { header: name.to_path_buf(), line: index + 1, message })
+}
+
+pub fn parse_header<R>(name: &Path, header: &str, world: &mut World, context: &mut HeaderContext, param_values: &HashMap<&str, HashMap<&str, &str>>, rng: &mut R) -> Result<String, Error>
 where R: Rng + ?Sized
+{
+    parse_header_with(name, header, world, context, param_values, rng, &mut header_error(name))
+}
+
+/// Parses a header, passing every line that fails to `on_error`, which decides whether to stop
+fn parse_header_with<R, E>(name: &Path, header: &str, world: &mut World, context: &mut HeaderContext, param_values: &HashMap<&str, HashMap<&str, &str>>, rng: &mut R, on_error: &mut E) -> Result<String, Error>
+where
+    R: Rng + ?Sized,
+    E: FnMut(usize, String) -> Result<(), Error>,
 {
     let mut processed = String::with_capacity(header.len());
     let mut pool = Vec::new();
     let mut parameters = HashMap::new();
-    let mut skip_until = -1;
-    let mut depth: i8 = 0;
+    let mut conditions = Conditions::default();
     let mut first_line = true;
 
     let default = HashMap::default();
     let header_param_values = param_values.get(&name.file_stem().unwrap().to_string_lossy().to_string()[..]).unwrap_or(&default);
 
-    for line in header.lines() {
+    let mut parse_line = |line: &str| -> Result<(), String> {
+        if first_line {
+            first_line = false;
+            if line.starts_with('#') { return Ok(()); }
+        }
+
+        if skip_conditional(line, &mut conditions, &parameters)? {
+            return Ok(());
+        }
+
         let mut line = apply_take_commands(line, &mut pool, rng)?;
         apply_parameters(&mut line, &parameters)?;
 
         let mut trimmed = line.trim();
 
-        if first_line {
-            first_line = false;
-            if line.starts_with('#') { continue; }
-        }
-
         if trimmed.starts_with("////") {
-            continue;
+            return Ok(());
         }
 
         if let Some(index) = trimmed.find("//") {
             if trimmed[index..].contains("skip-validate") {
-                continue;
+                return Ok(());
             }
             trimmed = &trimmed[..index];
         }
 
-        if skip_until > -1 {
-            if trimmed.trim_end() == "!!endif" {
-                depth -= 1;
-            } else if trimmed.starts_with("!!if ") {
-                depth += 1;
-            }
-            if depth == skip_until {
-                skip_until = -1;
-            }
-            continue;
-        }
-
         if let Some(flagline) = trimmed.strip_prefix("Flags:") {
             for flag in flagline.split(',') {
                 context.flags.push(flag.trim().to_string());
             }
         } else if let Some(command) = trimmed.strip_prefix("!!") {
-            if let Some(include) = command.strip_prefix("include ") {
-                include_command(include.trim(), &mut context.dependencies);
-            } else if let Some(exclude) = command.strip_prefix("exclude ") {
-                exclude_command(name, exclude.trim(), &mut context.excludes);
-            } else if let Some(item) = command.strip_prefix("add ") {
-                add_command(item.trim(), world).map_err(|err| format!("{} in add command {}", err, line))?;
-            } else if let Some(item) = command.strip_prefix("remove ") {
-                remove_command(item.trim(), world, &mut context.negative_inventory).map_err(|err| format!("{} in remove command {}", err, line))?;
-            } else if let Some(naming) = command.strip_prefix("name ") {
-                name_command(naming.trim(), &mut context.custom_items).map_err(|err| format!("{} in name command {}", err, line))?;
-            } else if let Some(display) = command.strip_prefix("display ") {
-                display_command(display.trim(), &mut context.custom_items).map_err(|err| format!("{} in display command {}", err, line))?;
-            } else if let Some(description) = command.strip_prefix("description ") {
-                description_command(description.trim(), &mut context.custom_items).map_err(|err| format!("{} in description command {}", err, line))?;
-            } else if let Some(price) = command.strip_prefix("price ") {
-                price_command(price.trim(), &mut context.custom_items).map_err(|err| format!("{} in price command {}", err, line))?;
-            } else if let Some(icon) = command.strip_prefix("icon ") {
-                icon_command(icon.trim(), &mut context.custom_items).map_err(|err| format!("{} in icon command {}", err, line))?;
-            } else if let Some(parameter) = command.strip_prefix("parameter ") {
-                parameter_command(parameter.trim(), &mut parameters, header_param_values).map_err(|err| format!("{} in parameter command {}", err, line))?;
-            } else if let Some(string) = command.strip_prefix("pool ") {
-                pool_command(string.trim(), &mut pool).map_err(|err| format!("{} in pool command {}", err, line))?;
-            } else if let Some(amount) = command.strip_prefix("addpool ") {
-                addpool_command(amount.trim(), world, &mut pool, rng).map_err(|err| format!("{} in addpool command {}", err, line))?;
-            } else if command.trim_end() == "flush" {
-                flush_command(&mut pool);
-            } else if let Some(identifier) = command.strip_prefix("set ") {
-                set_command(identifier.trim(), world, &mut context.sets).map_err(|err| format!("{} in set command {}", err, line))?;
-            } else if let Some(comparison) = command.strip_prefix("if ") {
-                if !if_command(comparison.trim(), &parameters).map_err(|err| format!("{} in if command {}", err, line))? {
-                    skip_until = depth;
-                }
-                depth += 1;
-            } else if command.trim_end() == "endif" {
-                if depth == 0 {
-                    return Err(String::from("!!endif without !!if"));
-                }
-                depth -= 1;
-            } else {
-                return Err(format!("Unknown command {}", command));
+            let (command_name, arguments) = command.split_once(' ').unwrap_or((command, ""));
+            let arguments = arguments.trim();
+            let in_command = |err: String| format!("{} in {} command {}", err, command_name, line);
+            if arguments.is_empty() && command_name != "flush" && COMMANDS.contains(&command_name) {
+                return Err(format!("Missing arguments in {} command {}", command_name, line));
+            }
+
+            match command_name {
+                "include" => include_command(arguments, &mut context.dependencies),
+                "exclude" => exclude_command(name, arguments, &mut context.excludes),
+                "add" => add_command(arguments, world).map_err(in_command)?,
+                "remove" => remove_command(arguments, world, &mut context.negative_inventory).map_err(in_command)?,
+                "name" => name_command(arguments, &mut context.custom_items).map_err(in_command)?,
+                "display" => display_command(arguments, &mut context.custom_items).map_err(in_command)?,
+                "description" => description_command(arguments, &mut context.custom_items).map_err(in_command)?,
+                "price" => price_command(arguments, &mut context.custom_items).map_err(in_command)?,
+                "icon" => icon_command(arguments, &mut context.custom_items).map_err(in_command)?,
+                "parameter" => parameter_command(arguments, &mut parameters, header_param_values).map_err(in_command)?,
+                "pool" => pool_command(arguments, &mut pool).map_err(in_command)?,
+                "addpool" => addpool_command(arguments, world, &mut pool, rng).map_err(in_command)?,
+                "flush" => flush_command(&mut pool),
+                "set" => set_command(arguments, world, &mut context.sets).map_err(in_command)?,
+                "version" => version_command(arguments).map_err(in_command)?,
+                "requires" => requires_command(arguments).map_err(in_command)?,
+                _ => return Err(format!("Unknown command {}", command.trim_end())),
             }
         } else if let Some(ignored) = line.strip_prefix('!') {
             processed += ignored;
@@ -1329,11 +1435,7 @@ where R: Rng + ?Sized
             processed.push('\n');
         } else {
             if !trimmed.is_empty() {
-                let mut parts = trimmed.splitn(3, '|');
-                let uber_state = parse_uber_state(&mut parts).map_err(|err| format!("malformed pickup {}: {}", trimmed, err))?;
-
-                let item = parts.next().ok_or_else(|| format!("malformed pickup {}", trimmed))?;
-                let item = parse_item(item)?;
+                let (uber_state, item) = parse_pickup(trimmed)?;
 
                 // if someone sets an uberstate on spawn, they probably don't want an item placed on it
                 if let Item::UberState(command) = &item {
@@ -1366,6 +1468,24 @@ where R: Rng + ?Sized
             processed += &line;
             processed.push('\n');
         }
+
+        Ok(())
+    };
+
+    let lines = match template::expand(header) {
+        Ok(lines) => lines,
+        Err((index, message)) => {
+            on_error(index, message)?;
+            return Ok(String::new());
+        },
+    };
+    for (index, line) in lines {
+        if let Err(message) = parse_line(&line) {
+            on_error(index, message)?;
+        }
+    }
+    if conditions.depth() > 0 {
+        on_error(header.lines().count().saturating_sub(1), String::from("!!if without !!endif"))?;
     }
 
     processed.push('\n');
@@ -1373,38 +1493,69 @@ where R: Rng + ?Sized
     Ok(processed)
 }
 
-pub fn validate_header(name: &Path, contents: &str) -> Result<(Vec<UberState>, HashMap<String, String>), String> {
-    let mut context = HeaderContext::default();
-    parse_header(name, contents, &mut World::new(&Graph::default()), &mut context, &HashMap::default(), &mut rand::thread_rng())?;
+/// Checks a header for errors with the header arguments in `param_values`, returning the states it writes to in uber group 9 and the context it left behind
+pub fn validate_header(name: &Path, contents: &str, param_values: &HashMap<&str, HashMap<&str, &str>>) -> Result<(Vec<UberState>, HeaderContext), Error> {
+    let validated = validate_header_with(name, contents, param_values, &mut header_error(name))?;
 
-    for dependency in context.dependencies {
-        util::read_file(&dependency, "headers")?;
+    for dependency in &validated.1.dependencies {
+        util::read_file(dependency, "headers")?;
     }
 
+    Ok(validated)
+}
+
+/// Checks a header like [`validate_header`], but reports every line that fails instead of stopping at the first one
+///
+/// Doesn't check whether included headers exist, since that depends on where the header is
+pub fn header_errors(name: &Path, contents: &str) -> Vec<LineError> {
+    let mut errors = Vec::new();
+    validate_header_with(name, contents, &HashMap::new(), &mut |index, message| {
+        errors.push((index, message));
+        Ok(())
+    }).unwrap_or_default();
+
+    errors.sort_by_key(|(index, _)| *index);
+    // Lines inside loops and macros may fail once per repetition, and both passes check most lines, the first error is enough
+    errors.dedup_by_key(|(index, _)| *index);
+    errors
+}
+
+fn validate_header_with<E>(name: &Path, contents: &str, param_values: &HashMap<&str, HashMap<&str, &str>>, on_error: &mut E) -> Result<(Vec<UberState>, HeaderContext), Error>
+where E: FnMut(usize, String) -> Result<(), Error>
+{
+    let mut context = HeaderContext::default();
+    parse_header_with(name, contents, &mut World::new(&Graph::default()), &mut context, param_values, &mut rand::thread_rng(), on_error)?;
+
     let mut occupied_states = Vec::new();
     let mut pool = Vec::new();
     let mut parameters = HashMap::new();
-    let param_values = HashMap::new();
+    let default = HashMap::default();
+    let header_param_values = param_values.get(&name.file_stem().unwrap_or_default().to_string_lossy()[..]).unwrap_or(&default);
     let mut rng = rand::thread_rng();
     let graph = Graph::default();
     let mut world = World::new(&graph);
 
+    let mut conditions = Conditions::default();
     let mut first_line = true;
     let mut skip_line = false;
 
-    for line in contents.lines() {
+    let mut parse_line = |line: &str| -> Result<(), String> {
+        if first_line {
+            first_line = false;
+            if line.trim().starts_with('#') { return Ok(()); }
+        }
+
+        if skip_conditional(line, &mut conditions, &parameters)? {
+            return Ok(());
+        }
+
         let mut line = apply_take_commands(line, &mut pool, &mut rng)?;
         apply_parameters(&mut line, &parameters)?;
 
         let mut trimmed = line.trim();
 
-        if first_line {
-            first_line = false;
-            if trimmed.starts_with('#') { continue; }
-        }
-
         if line.starts_with("Flags:") || line.starts_with("timer:") {
-            continue;
+            return Ok(());
         }
 
         let comment = trimmed.find("//");
@@ -1416,16 +1567,16 @@ pub fn validate_header(name: &Path, contents: &str) -> Result<(Vec<UberState>, H
         }
 
         if trimmed.is_empty() {
-            continue;
+            return Ok(());
         }
         if skip_line {
             skip_line = false;
-            continue;
+            return Ok(());
         }
 
         if let Some(command) = trimmed.strip_prefix("!!") {
             if let Some(parameter) = command.strip_prefix("parameter ") {
-                parameter_command(parameter.trim(), &mut parameters, &param_values).map_err(|err| format!("{} in parameter command {}", err, line))?;
+                parameter_command(parameter.trim(), &mut parameters, header_param_values).map_err(|err| format!("{} in parameter command {}", err, line))?;
             } else if let Some(string) = command.strip_prefix("pool ") {
                 // TODO determinate validation would be nice?
                 pool_command(string, &mut pool)?;
@@ -1447,16 +1598,16 @@ pub fn validate_header(name: &Path, contents: &str) -> Result<(Vec<UberState>, H
             }
 
             let item = parts.next().ok_or_else(|| format!("malformed pickup {}", trimmed))?;
-            let item = parse_item(item)?;
+            let item = parse_item(item).map_err(|err| err.to_string())?;
 
             match item {
                 Item::UberState(command) => {
-                    if command.uber_identifier.uber_group != 9 { continue; }
+                    if command.uber_identifier.uber_group != 9 { return Ok(()); }
 
                     match command.operator {
                         UberStateOperator::Value(mut value) => {
                             if value == "false" || value == "0" {
-                                continue;
+                                return Ok(());
                             }
                             if value == "true" {
                                 value = String::from("1");
@@ -1498,6 +1649,15 @@ pub fn validate_header(name: &Path, contents: &str) -> Result<(Vec<UberState>, H
                 _ => {},
             }
         }
+
+        Ok(())
+    };
+
+    // Expansion errors were reported while parsing already
+    for (index, line) in template::expand(contents).unwrap_or_default() {
+        if let Err(message) = parse_line(&line) {
+            on_error(index, message)?;
+        }
     }
 
     occupied_states.sort_unstable();
@@ -1517,7 +1677,7 @@ pub fn validate_header(name: &Path, contents: &str) -> Result<(Vec<UberState>, H
 
     occupied_states.dedup();
 
-    Ok((occupied_states, context.excludes))
+    Ok((occupied_states, context))
 }
 
 fn where_is(pattern: &str, world_index: usize, seeds: &[String], graph: &Graph, settings: &Settings) -> Result<String, String> {
@@ -1626,58 +1786,64 @@ fn how_many(pattern: &str, zone: Zone, world_index: usize, seeds: &[String], gra
     Ok(locations)
 }
 
-pub fn postprocess(seeds: &mut Vec<String>, graph: &Graph, settings: &Settings) -> Result<(), String> {
-    let clone = seeds.clone();
-
-    for (world_index, seed) in seeds.iter_mut().enumerate() {
-        let mut last_index = 0;
-        loop {
-            if let Some(mut start_index) = seed[last_index..].find("$WHEREIS(") {
-                start_index += last_index;
-                last_index = start_index;
+fn postprocess_seed(seed: &mut String, world_index: usize, seeds: &[String], graph: &Graph, settings: &Settings) -> Result<(), String> {
+    let mut last_index = 0;
+    loop {
+        if let Some(mut start_index) = seed[last_index..].find("$WHEREIS(") {
+            start_index += last_index;
+            last_index = start_index;
 
-                let after_bracket = start_index + 9;
+            let after_bracket = start_index + 9;
 
-                if let Some(end_index) = read_args(seed, after_bracket) {
-                    let pattern = seed[after_bracket..end_index].trim();
+            if let Some(end_index) = read_args(seed, after_bracket) {
+                let pattern = seed[after_bracket..end_index].trim();
 
-                    let zone = where_is(pattern, world_index, &clone, graph, settings)?;
-                    seed.replace_range(start_index..=end_index, &zone);
+                let zone = where_is(pattern, world_index, seeds, graph, settings)?;
+                seed.replace_range(start_index..=end_index, &zone);
 
-                    continue;
-                }
+                continue;
             }
-            break;
         }
+        break;
+    }
 
-        last_index = 0;
-        loop {
-            if let Some(mut start_index) = seed[last_index..].find("$HOWMANY(") {
-                start_index += last_index;
-                last_index = start_index;
+    last_index = 0;
+    loop {
+        if let Some(mut start_index) = seed[last_index..].find("$HOWMANY(") {
+            start_index += last_index;
+            last_index = start_index;
 
-                let after_bracket = start_index + 9;
+            let after_bracket = start_index + 9;
 
-                if let Some(end_index) = read_args(seed, after_bracket) {
-                    let mut args = seed[after_bracket..end_index].splitn(2, ',');
-                    let zone = args.next().unwrap().trim();
-                    let zone: u8 = zone.parse().map_err(|_| format!("expected numeric zone, got {}", zone))?;
-                    let zone = Zone::try_from(zone).map_err(|_| format!("invalid zone {}", zone))?;
-                    let pattern = args.next().unwrap_or("").trim();
+            if let Some(end_index) = read_args(seed, after_bracket) {
+                let mut args = seed[after_bracket..end_index].splitn(2, ',');
+                let zone = args.next().unwrap().trim();
+                let zone: u8 = zone.parse().map_err(|_| format!("expected numeric zone, got {}", zone))?;
+                let zone = Zone::try_from(zone).map_err(|_| format!("invalid zone {}", zone))?;
+                let pattern = args.next().unwrap_or("").trim();
 
-                    let locations = how_many(pattern, zone, world_index, &clone, graph)?;
-                    let locations = locations.into_iter().map(|uber_state| uber_state.to_string()).collect::<Vec<_>>();
-                    let locations = locations.join(",").replace('|', ",");
+                let locations = how_many(pattern, zone, world_index, seeds, graph)?;
+                let locations = locations.into_iter().map(|uber_state| uber_state.to_string()).collect::<Vec<_>>();
+                let locations = locations.join(",").replace('|', ",");
 
-                    let sysmessage = format!("$[15|4|{}]", locations);
+                let sysmessage = format!("$[15|4|{}]", locations);
 
-                    seed.replace_range(start_index..=end_index, &sysmessage);
+                seed.replace_range(start_index..=end_index, &sysmessage);
 
-                    continue;
-                }
+                continue;
             }
-            break;
         }
+        break;
+    }
+
+    Ok(())
+}
+
+pub fn postprocess(seeds: &mut Vec<String>, graph: &Graph, settings: &Settings) -> Result<(), Error> {
+    let clone = seeds.clone();
+
+    for (world_index, seed) in seeds.iter_mut().enumerate() {
+        postprocess_seed(seed, world_index, &clone, graph, settings).map_err(|message| Error::Generation { world_index: Some(world_index), message })?;
     }
 
     Ok(())
@@ -1721,4 +1887,55 @@ mod tests {
         assert!(parse_item("7|3").is_err());
         assert!(parse_item("-0|65").is_err());
     }
+
+    #[test]
+    fn conditions_and_parameters() {
+        let header = "\
+!!parameter lines int:3
+!!parameter goal enum(lines, blackout):lines
+!!if goal == \"blackout\"
+3|0|6|blackout
+!!elif lines > 2 && lines < 5
+3|0|6|$PARAM(lines * 2) lines
+!!else
+3|0|6|few lines
+!!endif
+";
+        let graph = Graph::default();
+        let parse = |values: &[(&'static str, &'static str)]| {
+            let param_values = vec![("test", values.iter().cloned().collect())].into_iter().collect();
+            parse_header(Path::new("test.wotwrh"), header, &mut World::new(&graph), &mut HeaderContext::default(), &param_values, &mut rand::thread_rng())
+        };
+
+        assert_eq!(parse(&[]).unwrap().trim(), "3|0|6|6 lines");
+        assert_eq!(parse(&[("lines", "1")]).unwrap().trim(), "3|0|6|few lines");
+        assert_eq!(parse(&[("goal", "blackout")]).unwrap().trim(), "3|0|6|blackout");
+        assert!(parse(&[("goal", "bingo")]).is_err());
+
+        let guarded = "!!parameter count int:0\n!!if count > 0\n3|0|6|$PARAM(100 / count)\n!!endif\n";
+        let guarded = parse_header(Path::new("test.wotwrh"), guarded, &mut World::new(&graph), &mut HeaderContext::default(), &HashMap::new(), &mut rand::thread_rng());
+        assert_eq!(guarded.unwrap().trim(), "");
+
+        let unclosed = parse_header(Path::new("test.wotwrh"), "!!if true\n3|0|6|text\n", &mut World::new(&graph), &mut HeaderContext::default(), &HashMap::new(), &mut rand::thread_rng());
+        assert_eq!(unclosed.unwrap_err(), Error::ParseHeader { header: PathBuf::from("test.wotwrh"), line: 2, message: String::from("!!if without !!endif") });
+
+        let parameter = Parameter::parse("goal enum(lines, blackout):lines").unwrap();
+        assert_eq!(parameter.parameter_type, ParameterType::Enum(vec![String::from("lines"), String::from("blackout")]));
+        assert_eq!(parameter.parameter_type.to_string(), "one of lines, blackout");
+        assert!(Parameter::parse("goal enum(lines, blackout):bingo").is_err());
+        assert!(Parameter::parse("goal list:lines").is_err());
+    }
+
+    #[test]
+    fn header_errors_per_line() {
+        for command in COMMANDS {
+            let recognized = [format!("!!{}", command), format!("!!{} x", command)].iter()
+                .any(|line| header_errors(Path::new("test.wotwrh"), line).iter().all(|(_, message)| !message.starts_with("Unknown command")));
+            assert!(recognized, "{} is not recognized", command);
+        }
+
+        let errors = header_errors(Path::new("test.wotwrh"), "!!frobnicate\n3|0|6|fine\n3|0|99|nothing\n!!if true\n");
+        assert_eq!(errors.iter().map(|(index, _)| *index).collect::<Vec<_>>(), [0, 2, 3]);
+        assert_eq!(errors[0].1, "Unknown command frobnicate");
+    }
 }
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+/// How deeply macro calls and loops may nest, mostly to stop macros that call themselves
+const MAX_DEPTH: usize = 32;
+/// How many times a single loop may repeat
+const MAX_ITERATIONS: i64 = 10000;
+/// How many lines and loop repetitions a whole header may expand to, since nested loops multiply
+const MAX_EXPANSION: usize = 100_000;
+
+/// A line of the expanded header along with the 0-based index of the line it came from
+pub type ExpandedLine = (usize, String);
+
+struct Macro {
+    arguments: Vec<String>,
+    body: Vec<ExpandedLine>,
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|char| char.is_alphanumeric() || char == '_') && !name.starts_with(|char: char| char.is_ascii_digit())
+}
+
+/// The command on a `!!` line without its comment
+fn directive(line: &str) -> Option<&str> {
+    let line = line.trim();
+    let line = line.find("//").map_or(line, |index| &line[..index]);
+    line.strip_prefix("!!").map(str::trim)
+}
+
+/// Replaces `$<name>` with the value bound to `name`
+fn substitute(line: &str, bindings: &[(&str, String)]) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(index) = rest.find('$') {
+        output += &rest[..index];
+        let after = &rest[index + 1..];
+        let length = after.find(|char: char| !(char.is_alphanumeric() || char == '_')).unwrap_or(after.len());
+        match bindings.iter().find(|(name, _)| *name == &after[..length]) {
+            Some((_, value)) => output += value,
+            None => output += &rest[index..=index + length],
+        }
+        rest = &after[length..];
+    }
+    output += rest;
+    output
+}
+
+/// Splits `name(a, b)` into the name and the arguments, respecting nested parentheses
+fn parse_call(call: &str) -> Option<(&str, Vec<String>)> {
+    let (name, arguments) = call.split_once('(')?;
+    let arguments = arguments.strip_suffix(')')?;
+    if !is_identifier(name) { return None }
+
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, char) in arguments.char_indices() {
+        match char {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(arguments[start..index].trim().to_string());
+                start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    let last = arguments[start..].trim();
+    if !last.is_empty() || !parts.is_empty() {
+        parts.push(last.to_string());
+    }
+
+    Some((name, parts))
+}
+
+/// Finds the line closing the block opened at `start`
+fn block_end(lines: &[ExpandedLine], start: usize, open: &str, close: &str) -> Result<usize, (usize, String)> {
+    let mut depth = 0;
+    for (index, (_, line)) in lines.iter().enumerate().skip(start) {
+        match directive(line) {
+            Some(command) if command.split(' ').next() == Some(open) => depth += 1,
+            Some(command) if command == close => {
+                depth -= 1;
+                if depth == 0 { return Ok(index) }
+            },
+            _ => {},
+        }
+    }
+    Err((lines[start].0, format!("!!{} without !!{}", open, close)))
+}
+
+/// Reads `<variable> in <start>..<end>` or `<variable> in <start>..=<end>`
+fn parse_loop(header: &str) -> Result<(&str, Vec<i64>), String> {
+    let (variable, range) = header.split_once(" in ").ok_or_else(|| String::from("Expected !!for <variable> in <start>..<end>"))?;
+    let variable = variable.trim();
+    if !is_identifier(variable) {
+        return Err(format!("Invalid loop variable {}", variable));
+    }
+
+    let (start, end, inclusive) = match range.split_once("..=") {
+        Some((start, end)) => (start, end, true),
+        None => {
+            let (start, end) = range.split_once("..").ok_or_else(|| format!("Invalid range {}", range.trim()))?;
+            (start, end, false)
+        },
+    };
+    let parse = |bound: &str| bound.trim().parse::<i64>().map_err(|_| format!("Invalid range bound {}", bound.trim()));
+    let (start, end) = (parse(start)?, parse(end)?);
+    let end = if inclusive { end.saturating_add(1) } else { end };
+
+    if end.saturating_sub(start) > MAX_ITERATIONS {
+        return Err(format!("Loops may repeat at most {} times", MAX_ITERATIONS));
+    }
+    Ok((variable, (start..end).collect()))
+}
+
+struct Expansion {
+    macros: HashMap<String, Macro>,
+    output: Vec<ExpandedLine>,
+    /// How many more lines and loop repetitions may be processed
+    budget: usize,
+}
+
+fn spend(expansion: &mut Expansion, line_index: usize) -> Result<(), (usize, String)> {
+    expansion.budget = expansion.budget.checked_sub(1)
+        .ok_or_else(|| (line_index, format!("The header expands to more than {} lines", MAX_EXPANSION)))?;
+    Ok(())
+}
+
+fn expand_lines(lines: &[ExpandedLine], expansion: &mut Expansion, depth: usize) -> Result<(), (usize, String)> {
+    let mut index = 0;
+    while let Some((line_index, line)) = lines.get(index) {
+        let error = |message: String| (*line_index, message);
+        if depth > MAX_DEPTH {
+            return Err(error(format!("Macros and loops may nest at most {} levels deep", MAX_DEPTH)));
+        }
+        spend(expansion, *line_index)?;
+
+        match directive(line) {
+            Some(command) if command.starts_with("macro ") => {
+                let end = block_end(lines, index, "macro", "endmacro")?;
+                let (name, arguments) = parse_call(command["macro ".len()..].trim()).ok_or_else(|| error(String::from("Expected !!macro <name>(<arguments>)")))?;
+                if let Some(argument) = arguments.iter().find(|argument| !is_identifier(argument)) {
+                    return Err(error(format!("Invalid macro argument {}", argument)));
+                }
+                expansion.macros.insert(name.to_string(), Macro { arguments, body: lines[index + 1..end].to_vec() });
+                index = end;
+            },
+            Some(command) if command.starts_with("for ") => {
+                let end = block_end(lines, index, "for", "endfor")?;
+                let (variable, values) = parse_loop(&command["for ".len()..]).map_err(error)?;
+                for value in values {
+                    spend(expansion, *line_index)?;
+                    let bindings = [(variable, value.to_string())];
+                    let body = lines[index + 1..end].iter().map(|(line_index, line)| (*line_index, substitute(line, &bindings))).collect::<Vec<_>>();
+                    expand_lines(&body, expansion, depth + 1)?;
+                }
+                index = end;
+            },
+            Some("endmacro") => return Err(error(String::from("!!endmacro without !!macro"))),
+            Some("endfor") => return Err(error(String::from("!!endfor without !!for"))),
+            Some(command) if command.find('(').map_or(false, |index| is_identifier(&command[..index])) => {
+                let (name, arguments) = parse_call(command).ok_or_else(|| error(format!("Invalid macro call {}", command)))?;
+                let called = expansion.macros.get(name).ok_or_else(|| error(format!("Unknown macro {}", name)))?;
+                if called.arguments.len() != arguments.len() {
+                    return Err(error(format!("Macro {} takes {} arguments, but {} were given", name, called.arguments.len(), arguments.len())));
+                }
+
+                let bindings = called.arguments.iter().map(String::as_str).zip(arguments).collect::<Vec<_>>();
+                let body = called.body.iter().map(|(line_index, line)| (*line_index, substitute(line, &bindings))).collect::<Vec<_>>();
+                expand_lines(&body, expansion, depth + 1)?;
+            },
+            _ => expansion.output.push((*line_index, line.clone())),
+        }
+
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Expands the `!!macro`s and `!!for` loops of a header
+///
+/// `!!macro <name>(<arguments>)` up to `!!endmacro` defines a macro that `!!<name>(<values>)` inserts later on,
+/// `!!for <variable> in <start>..<end>` up to `!!endfor` repeats its lines for every value in the range, `..=` includes the end.
+/// Inside, `$<argument>` and `$<variable>` are replaced with their values before anything else happens,
+/// so they also work in expressions like `$PARAM($i + 1)`
+///
+/// Errors come with the 0-based index of the line that caused them
+pub fn expand(header: &str) -> Result<Vec<ExpandedLine>, (usize, String)> {
+    let lines = header.lines().map(str::to_string).enumerate().collect::<Vec<_>>();
+    let mut expansion = Expansion { macros: HashMap::new(), output: Vec::with_capacity(lines.len()), budget: MAX_EXPANSION };
+    expand_lines(&lines, &mut expansion, 0)?;
+    Ok(expansion.output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expanded(header: &str) -> Vec<String> {
+        expand(header).unwrap().into_iter().map(|(_, line)| line).collect()
+    }
+
+    #[test]
+    fn templates() {
+        let header = "\
+!!macro shop(id, price)
+1|$id|6|Item $id  // costs $price
+!!for level in 1..=2
+!!add 8|1|$id|int|$PARAM($price * $level)
+!!endfor
+!!endmacro
+!!for tp in 0..3
+!!add 5|$tp
+!!endfor
+!!shop(12, 200)
+!!shop(13, (1 + 2))
+";
+        assert_eq!(expanded(header), [
+            "!!add 5|0", "!!add 5|1", "!!add 5|2",
+            "1|12|6|Item 12  // costs 200", "!!add 8|1|12|int|$PARAM(200 * 1)", "!!add 8|1|12|int|$PARAM(200 * 2)",
+            "1|13|6|Item 13  // costs (1 + 2)", "!!add 8|1|13|int|$PARAM((1 + 2) * 1)", "!!add 8|1|13|int|$PARAM((1 + 2) * 2)",
+        ]);
+        assert_eq!(expand(header).unwrap()[4].0, 3);
+        assert_eq!(expanded("$PARAM(x) $[(9|999=1)] $(4|50)"), ["$PARAM(x) $[(9|999=1)] $(4|50)"]);
+
+        assert_eq!(expand("a\n!!for i in 0..2\n!!add 5|$i\n").unwrap_err(), (1, String::from("!!for without !!endfor")));
+        assert_eq!(expand("!!missing(1)").unwrap_err().1, "Unknown macro missing");
+        assert_eq!(expand("!!macro m(a)\n!!endmacro\n!!m(1, 2)").unwrap_err().1, "Macro m takes 1 arguments, but 2 were given");
+        assert!(expand("!!macro m()\n!!m()\n!!endmacro\n!!m()").is_err());
+        assert!(expand("!!endfor").is_err());
+        assert!(expand("!!for i in 0..100000\n!!endfor").is_err());
+        let nested = "!!for a in 0..10000\n!!for b in 0..10000\n!!for c in 0..10000\n!!add 5|1\n!!endfor\n!!endfor\n!!endfor";
+        assert_eq!(expand(nested).unwrap_err().1, format!("The header expands to more than {} lines", MAX_EXPANSION));
+        assert!(expand("!!for a in 0..10000\n!!for b in 0..10000\n!!endfor\n!!endfor").is_err());
+    }
+}
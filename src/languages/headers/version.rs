@@ -0,0 +1,134 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    str::FromStr,
+};
+
+/// A dotted version number like `0.1` or `1.2.3`, missing parts count as 0
+///
+/// Pre-release and build suffixes like `-beta` or `+build.1` are ignored
+#[derive(Debug, Clone, Eq)]
+pub struct Version(Vec<u32>);
+impl Version {
+    /// The version of this seed generator
+    pub fn current() -> Version {
+        env!("CARGO_PKG_VERSION").parse().unwrap()
+    }
+
+    fn part(&self, index: usize) -> u32 {
+        self.0.get(index).copied().unwrap_or(0)
+    }
+}
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(version: &str) -> Result<Version, String> {
+        let version = version.trim();
+        let numbers = version.find(['-', '+']).map_or(version, |index| &version[..index]);
+        let parts = numbers.split('.')
+            .map(|part| part.parse().map_err(|_| format!("Invalid version {}", version)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Version(parts))
+    }
+}
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", parts.join("."))
+    }
+}
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        let length = self.0.len().max(other.0.len());
+        (0..length).map(|index| self.part(index).cmp(&other.part(index)))
+            .find(|ordering| ordering != &Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+/// A `!!requires seedgen <operator> <version>` declaration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub operator: &'static str,
+    pub version: Version,
+}
+impl Requirement {
+    /// Whether `version` fulfills the requirement
+    pub fn matches(&self, version: &Version) -> bool {
+        match self.operator {
+            ">=" => version >= &self.version,
+            ">" => version > &self.version,
+            "<=" => version <= &self.version,
+            "<" => version < &self.version,
+            _ => version == &self.version,
+        }
+    }
+
+    /// Returns an error if this seed generator doesn't fulfill the requirement
+    pub fn check(&self) -> Result<(), String> {
+        let current = Version::current();
+        if self.matches(&current) {
+            Ok(())
+        } else {
+            Err(format!("Requires {}, but this is seedgen {}", self, current))
+        }
+    }
+}
+impl FromStr for Requirement {
+    type Err = String;
+
+    fn from_str(requirement: &str) -> Result<Requirement, String> {
+        let requirement = requirement.trim();
+        let constraint = requirement.strip_prefix("seedgen").ok_or_else(|| format!("Unknown requirement {}, expected seedgen <operator> <version>", requirement))?.trim_start();
+
+        let operator = [">=", "<=", "==", ">", "<", "="].iter()
+            .find(|operator| constraint.starts_with(*operator))
+            .ok_or_else(|| format!("Expected one of >=, >, <=, <, == before the version in {}", requirement))?;
+        let version = constraint[operator.len()..].parse()?;
+        let operator = if *operator == "=" { "==" } else { operator };
+
+        Ok(Requirement { operator, version })
+    }
+}
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "seedgen {} {}", self.operator, self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requirements() {
+        let version = |version: &str| version.parse::<Version>().unwrap();
+        assert!(version("0.1") == version("0.1.0"));
+        assert!(version("0.0.10") > version("0.0.2"));
+        assert!("1.x".parse::<Version>().is_err());
+        assert!(version("0.1.0-beta.2") == version("0.1"));
+        assert!(version("1.2.3+build.5") == version("1.2.3"));
+        assert!("-beta".parse::<Version>().is_err());
+
+        let requirement = "seedgen >= 0.1".parse::<Requirement>().unwrap();
+        assert!(requirement.matches(&version("0.1.0")));
+        assert!(requirement.matches(&version("1.0")));
+        assert!(!requirement.matches(&version("0.0.9")));
+        assert_eq!("seedgen=0.0.2".parse::<Requirement>().unwrap().to_string(), "seedgen == 0.0.2");
+        assert!("seedgen 0.1".parse::<Requirement>().is_err());
+        assert!("rando >= 1".parse::<Requirement>().is_err());
+
+        assert!(format!("seedgen <= {}", Version::current()).parse::<Requirement>().unwrap().check().is_ok());
+        assert_eq!("seedgen > 999".parse::<Requirement>().unwrap().check().unwrap_err(), format!("Requires seedgen > 999, but this is seedgen {}", Version::current()));
+    }
+}
@@ -1,42 +1,140 @@
 pub mod tokenizer;
 pub mod parser;
 pub mod emitter;
+pub mod formatter;
+pub mod lint;
 
-use std::{path::Path, convert::TryFrom};
+use std::{
+    convert::TryFrom,
+    fs,
+    hash::{Hash, Hasher},
+    path::Path,
+    process,
+};
 
 use decorum::R32;
 use parser::ParseError;
+use rustc_hash::FxHasher;
 use serde::Deserialize;
+use crate::error::Error;
 use crate::world::graph::Graph;
 use crate::settings::Settings;
 use crate::util::{self, UberState, Position};
 
-pub fn parse_logic<P1, P2, P3>(areas: P1, locations: P2, states: P3, settings: &Settings, validate: bool) -> Result<Graph, String>
+pub fn parse_logic<P1, P2, P3>(areas: P1, locations: P2, states: P3, settings: &Settings, validate: bool) -> Result<Graph, Error>
 where
     P1: AsRef<Path>,
     P2: AsRef<Path>,
     P3: AsRef<Path>,
 {
-    let input = util::read_file(&areas, "logic")?;
-    let (tokens, metadata) = tokenizer::tokenize(&input).map_err(|err| format!("Error parsing areas from {}: {}", areas.as_ref().display(), err))?;
-
-    let areas = parser::parse_areas(tokens, &metadata).map_err(|err| {
-        let ParseError { description, position } = err;
-        let line = if position == usize::MAX {
-            input.lines().last()
-        } else {
-            input[position..].lines().next()
-        }.unwrap_or("");
-        format!("Error parsing areas.wotw: {}: {}", description, line)
-    })?;
-
-    let input = util::read_file(&locations, "logic")?;
-    let locations = parse_locations(&input).map_err(|err| format!("Error parsing locations from {}: {}", locations.as_ref().display(), err))?;
-
-    let input = util::read_file(&states, "logic")?;
-    let state_map = parse_states(&input).map_err(|err| format!("Error parsing states from {}: {}", states.as_ref().display(), err))?;
-
-    emitter::emit(&areas, &metadata, &locations, &state_map, settings, validate).map_err(|err| format!("Error building the logic: {}", err))
+    let inputs = LogicInputs::read(areas.as_ref(), locations.as_ref(), states.as_ref())?;
+    inputs.compile(settings, validate)
+}
+
+/// Like `parse_logic`, but stores the compiled graph in `cache_folder` and loads it from there as long as the input files, difficulty and glitches stay the same
+///
+/// A cache that can't be read or written is logged and otherwise ignored
+pub fn parse_logic_cached<P1, P2, P3, P4>(areas: P1, locations: P2, states: P3, settings: &Settings, validate: bool, cache_folder: P4) -> Result<Graph, Error>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    P3: AsRef<Path>,
+    P4: AsRef<Path>,
+{
+    let inputs = LogicInputs::read(areas.as_ref(), locations.as_ref(), states.as_ref())?;
+    let cache_file = cache_folder.as_ref().join(format!("{:016x}.json", inputs.cache_key(settings, validate)));
+
+    if cache_file.is_file() {
+        match fs::read_to_string(&cache_file).map_err(|err| err.to_string()).and_then(|cache| serde_json::from_str(&cache).map_err(|err| err.to_string())) {
+            Ok(graph) => return Ok(graph),
+            Err(err) => log::warn!("Failed to read logic cache {}: {}", cache_file.display(), err),
+        }
+    }
+
+    let graph = inputs.compile(settings, validate)?;
+    if let Err(err) = write_cache(&graph, cache_folder.as_ref(), &cache_file) {
+        log::warn!("Failed to write logic cache {}: {}", cache_file.display(), err);
+    }
+    Ok(graph)
+}
+
+fn write_cache(graph: &Graph, cache_folder: &Path, cache_file: &Path) -> Result<(), String> {
+    let cache = serde_json::to_string(graph).map_err(|err| err.to_string())?;
+    fs::create_dir_all(cache_folder).map_err(|err| err.to_string())?;
+    // write somewhere else first so nobody reads a half written cache
+    let temporary = cache_file.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&temporary, cache).map_err(|err| err.to_string())?;
+    fs::rename(&temporary, cache_file).map_err(|err| err.to_string())
+}
+
+/// Bump when the compiled graph changes in a way that makes old caches invalid
+const CACHE_VERSION: u32 = 1;
+
+struct LogicInputs<'a> {
+    areas: &'a Path,
+    areas_input: String,
+    locations: &'a Path,
+    locations_input: String,
+    states: &'a Path,
+    states_input: String,
+}
+impl<'a> LogicInputs<'a> {
+    fn read(areas: &'a Path, locations: &'a Path, states: &'a Path) -> Result<LogicInputs<'a>, Error> {
+        Ok(LogicInputs {
+            areas,
+            areas_input: util::read_file(areas, "logic")?,
+            locations,
+            locations_input: util::read_file(locations, "logic")?,
+            states,
+            states_input: util::read_file(states, "logic")?,
+        })
+    }
+
+    fn compile(&self, settings: &Settings, validate: bool) -> Result<Graph, Error> {
+        let (tokens, metadata) = tokenizer::tokenize(&self.areas_input).map_err(|err| areas_error(self.areas, &self.areas_input, err))?;
+        let areas = parser::parse_areas(tokens, &metadata).map_err(|err| areas_error(self.areas, &self.areas_input, err))?;
+
+        let locations = parse_locations(&self.locations_input).map_err(|message| Error::ParseLogic { file: self.locations.to_path_buf(), line: None, column: None, message })?;
+        let state_map = parse_states(&self.states_input).map_err(|message| Error::ParseLogic { file: self.states.to_path_buf(), line: None, column: None, message })?;
+
+        emitter::emit(&areas, &metadata, &locations, &state_map, settings, validate)
+    }
+
+    /// Identifies everything the compiled graph depends on
+    fn cache_key(&self, settings: &Settings, validate: bool) -> u64 {
+        let mut hasher = FxHasher::default();
+        CACHE_VERSION.hash(&mut hasher);
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        self.areas_input.hash(&mut hasher);
+        self.locations_input.hash(&mut hasher);
+        self.states_input.hash(&mut hasher);
+        settings.difficulty.hash(&mut hasher);
+        let mut glitches = settings.glitches.iter().map(|glitch| format!("{:?}", glitch)).collect::<Vec<_>>();
+        glitches.sort_unstable();
+        glitches.dedup();
+        glitches.hash(&mut hasher);
+        validate.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+fn areas_error(file: &Path, input: &str, err: ParseError) -> Error {
+    let ParseError { description, position } = err;
+
+    // position usize::MAX means the input ended unexpectedly
+    let (line, column, text) = if position == usize::MAX {
+        (input.lines().count(), None, input.lines().last())
+    } else {
+        let line_start = input[..position].rfind('\n').map_or(0, |index| index + 1);
+        (input[..position].matches('\n').count() + 1, Some(position - line_start + 1), input[position..].lines().next())
+    };
+
+    Error::ParseLogic {
+        file: file.to_path_buf(),
+        line: Some(line),
+        column,
+        message: format!("{}: {}", description, text.unwrap_or("")),
+    }
 }
 
 #[derive(Debug)]
@@ -116,3 +214,37 @@ pub fn parse_states(input: &str) -> Result<Vec<NamedState>, String> {
 
     Ok(states)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::Difficulty;
+
+    #[test]
+    fn logic_cache() {
+        let cache_folder = std::env::temp_dir().join(format!("seedgen_logic_cache_{}", process::id()));
+        let _ = fs::remove_dir_all(&cache_folder);
+        let settings = Settings::default();
+        let cache_files = || fs::read_dir(&cache_folder).unwrap().map(|entry| entry.unwrap().path()).collect::<Vec<_>>();
+
+        let graph = parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let expected = serde_json::to_string(&graph).unwrap();
+
+        let compiled = parse_logic_cached("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false, &cache_folder).unwrap();
+        assert_eq!(serde_json::to_string(&compiled).unwrap(), expected);
+        let cache_file = cache_files().pop().unwrap();
+        let cached = parse_logic_cached("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false, &cache_folder).unwrap();
+        assert_eq!(serde_json::to_string(&cached).unwrap(), expected);
+
+        let settings = Settings { difficulty: Difficulty::Unsafe, ..Settings::default() };
+        parse_logic_cached("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false, &cache_folder).unwrap();
+        assert_eq!(cache_files().len(), 2);
+
+        fs::write(&cache_file, "not a graph").unwrap();
+        let recompiled = parse_logic_cached("areas.wotw", "loc_data.csv", "state_data.csv", &Settings::default(), false, &cache_folder).unwrap();
+        assert_eq!(serde_json::to_string(&recompiled).unwrap(), expected);
+        assert_eq!(fs::read_to_string(&cache_file).unwrap(), expected);
+
+        fs::remove_dir_all(&cache_folder).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 use rustc_hash::{FxHashSet, FxHashMap};
 
 use super::{parser::{self, AreaTree}, tokenizer::Metadata, Location, NamedState};
+use crate::error::Error;
 use crate::world::{
     graph::{self, Graph, Node},
     requirements::Requirement,
@@ -173,14 +174,14 @@ fn build_requirement_group<'a>(group: &parser::Group<'a>, region: bool, context:
     build_or(lines)
 }
 
-fn add_entry<'a>(graph: &mut FxHashMap<&'a str, usize>, key: &'a str, index: usize) -> Result<(), String> {
+fn add_entry<'a>(graph: &mut FxHashMap<&'a str, usize>, key: &'a str, index: usize) -> Result<(), Error> {
     if graph.insert(key, index).is_some() {
-        return Err(format!("Name {} was used multiple times ambiguously", key));
+        return Err(Error::InvalidLogic(format!("Name {} was used multiple times ambiguously", key)));
     }
     Ok(())
 }
 
-pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state_map: &[NamedState], settings: &Settings, validate: bool) -> Result<Graph, String> {
+pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state_map: &[NamedState], settings: &Settings, validate: bool) -> Result<Graph, Error> {
     let node_count = areas.anchors.len() + locations.len() + metadata.states.len();
     let mut graph = Vec::with_capacity(node_count);
     let mut used_states = FxHashSet::default();
@@ -205,7 +206,7 @@ pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state
             "Willows End" => Zone::Willow,
             "Shop" => Zone::Shop,
             "Void" => Zone::Void,
-            _ => return Err(format!("invalid zone {} in loc_data", location.zone)),
+            _ => return Err(Error::InvalidLogic(format!("invalid zone {} in loc_data", location.zone))),
         };
 
         let index = graph.len();
@@ -286,7 +287,7 @@ pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state
                 requirement = build_and(vec![region_requirement.clone(), requirement]);
             }
 
-            let to = *context.node_map.get(connection.identifier).ok_or_else(|| format!("Anchor {} connects to {:?} {} which doesn't actually exist", anchor.identifier, connection.name, connection.identifier))?;
+            let to = *context.node_map.get(connection.identifier).ok_or_else(|| Error::InvalidLogic(format!("Anchor {} connects to {:?} {} which doesn't actually exist", anchor.identifier, connection.name, connection.identifier)))?;
 
             connections.push(graph::Connection {
                 to,
@@ -309,7 +310,7 @@ pub fn emit(areas: &AreaTree, metadata: &Metadata, locations: &[Location], state
             for connection in &anchor.connections {
                 let expected_type = graph[context.node_map[connection.identifier]].node_type();
                 if connection.name != expected_type {
-                    return Err(format!("Anchor {} connects to {:?} {} which is actually a {:?}", anchor.identifier, connection.name, connection.identifier, expected_type));
+                    return Err(Error::InvalidLogic(format!("Anchor {} connects to {:?} {} which is actually a {:?}", anchor.identifier, connection.name, connection.identifier, expected_type)));
                 }
             }
         }
@@ -0,0 +1,323 @@
+use std::{
+    mem,
+    path::Path,
+};
+
+use super::{
+    parser,
+    tokenizer::{self, Token, TokenType},
+};
+use crate::{
+    error::Error,
+    util::Difficulty,
+};
+
+const INDENT: &str = "  ";
+
+/// A comment on its own line
+struct Comment<'a> {
+    depth: usize,
+    text: &'a str,
+    /// Whether a blank line came before
+    blank: bool,
+}
+
+#[derive(Default)]
+struct Line<'a> {
+    /// The comments on their own lines right above
+    comments: Vec<Comment<'a>>,
+    /// Whether a blank line came before
+    blank: bool,
+    text: String,
+    /// A comment at the end of the line
+    comment: Option<&'a str>,
+    /// The comments on their own lines after the children that are indented further than the next line
+    after: Vec<Comment<'a>>,
+    /// The difficulty the line starts with
+    difficulty: Option<Difficulty>,
+    children: Vec<Line<'a>>,
+}
+
+/// How far the line containing `position` is indented
+fn indentation(input: &str, position: usize) -> usize {
+    let line = &input[input[..position].rfind('\n').map_or(0, |index| index + 1)..];
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Which depth a comment on its own line belongs to, going by the indentation of the code above it
+///
+/// `widths` are the columns the code above was indented to on each depth
+fn comment_depth(widths: &[usize], column: usize) -> usize {
+    if column == 0 {
+        return 0;
+    }
+    widths.iter().position(|&width| column <= width).map_or(widths.len() + 1, |index| index + 1)
+}
+
+/// The line that was finished last
+fn last_finished<'a, 'b>(levels: &'b mut [Vec<Line<'a>>]) -> Option<&'b mut Line<'a>> {
+    let mut line = levels.iter_mut().rev().find_map(|level| level.last_mut())?;
+    while !line.children.is_empty() {
+        line = line.children.last_mut().unwrap();
+    }
+    Some(line)
+}
+
+fn difficulty(token: &Token) -> Option<Difficulty> {
+    match token.value {
+        "moki" => Some(Difficulty::Moki),
+        "gorlek" => Some(Difficulty::Gorlek),
+        "kii" => Some(Difficulty::Kii),
+        "unsafe" => Some(Difficulty::Unsafe),
+        _ => None,
+    }
+}
+
+/// Appends `token` to a line of code with the usual spacing
+fn push_token(text: &mut String, token: &Token) {
+    if text.ends_with(':') || text.ends_with(',') {
+        text.push(' ');
+    }
+    match token.name {
+        TokenType::Definition => *text += &format!("requirement {}", token.value),
+        TokenType::Region => *text += &format!("region {}", token.value),
+        TokenType::Anchor => *text += &format!("anchor {}", token.value),
+        TokenType::Position => *text += &format!(" at {}", token.value.split(',').map(str::trim).collect::<Vec<_>>().join(", ")),
+        TokenType::Refill => *text += &format!("refill {}", token.value),
+        TokenType::State => *text += &format!("state {}", token.value),
+        TokenType::Quest => *text += &format!("quest {}", token.value),
+        TokenType::Pickup => *text += &format!("pickup {}", token.value),
+        TokenType::Connection => *text += &format!("conn {}", token.value),
+        TokenType::Free => *text += "free",
+        TokenType::NoSpawn => *text += "nospawn",
+        TokenType::Group => text.push(':'),
+        TokenType::And => text.push(','),
+        TokenType::Or => *text += " OR ",
+        _ => *text += token.value,
+    }
+}
+
+/// Rebuilds the lines of the file and their nesting from the tokens
+fn build_lines<'a>(input: &str, tokens: &[Token<'a>]) -> (Vec<Line<'a>>, Vec<Comment<'a>>) {
+    let mut levels = vec![Vec::<Line>::new()];
+    let mut widths = Vec::new();
+    let mut current: Option<(usize, Line)> = None;
+    let mut comments = Vec::new();
+    let mut last_line = 0;
+
+    for token in tokens {
+        match token.name {
+            TokenType::Comment => match &mut current {
+                Some((line, current)) if *line == token.line => current.comment = Some(token.value),
+                _ => {
+                    let depth = comment_depth(&widths, indentation(input, token.position));
+                    comments.push(Comment { depth, text: token.value, blank: last_line > 0 && token.line > last_line + 1 });
+                    last_line = token.line;
+                },
+            },
+            TokenType::Newline | TokenType::Indent | TokenType::Dedent => {
+                if let Some((_, line)) = current.take() {
+                    levels.last_mut().unwrap().push(line);
+                }
+                match token.name {
+                    TokenType::Indent => levels.push(Vec::new()),
+                    TokenType::Dedent if levels.len() > 1 => {
+                        let children = levels.pop().unwrap();
+                        if let Some(parent) = levels.last_mut().unwrap().last_mut() {
+                            parent.children = children;
+                        }
+                    },
+                    _ => {},
+                }
+            },
+            _ => {
+                if current.is_none() {
+                    let depth = levels.len() - 1;
+                    let indentation = indentation(input, token.position);
+                    if widths.len() >= depth {
+                        widths.truncate(depth);
+                        if depth > 0 { widths[depth - 1] = indentation }
+                    } else {
+                        widths.push(indentation);
+                    }
+
+                    // comments indented further than this line stay with the lines above
+                    let mut before = mem::take(&mut comments);
+                    if let Some(previous) = last_finished(&mut levels) {
+                        let (after, rest) = before.into_iter().partition(|comment| comment.depth > depth);
+                        previous.after.extend(after);
+                        before = rest;
+                    }
+                    let line = Line {
+                        comments: before,
+                        blank: last_line > 0 && token.line > last_line + 1,
+                        difficulty: difficulty(token),
+                        ..Line::default()
+                    };
+                    current = Some((token.line, line));
+                }
+                let (_, line) = current.as_mut().unwrap();
+                push_token(&mut line.text, token);
+                last_line = token.line;
+            },
+        }
+    }
+
+    if let Some((_, line)) = current {
+        levels.last_mut().unwrap().push(line);
+    }
+    while levels.len() > 1 {
+        let children = levels.pop().unwrap();
+        if let Some(parent) = levels.last_mut().unwrap().last_mut() {
+            parent.children = children;
+        }
+    }
+
+    (levels.pop().unwrap(), comments)
+}
+
+/// Orders lines nested under the same line by their difficulty, lines without one count as moki
+fn sort_difficulties(lines: &mut [Line]) {
+    for line in lines {
+        line.children.sort_by_key(|child| child.difficulty.unwrap_or(Difficulty::Moki));
+        sort_difficulties(&mut line.children);
+    }
+}
+
+fn write_comment(comment: &Comment, first: bool, output: &mut String) {
+    if comment.blank && !first && !output.is_empty() {
+        output.push('\n');
+    }
+    *output += &INDENT.repeat(comment.depth);
+    *output += comment.text;
+    output.push('\n');
+}
+
+fn write_lines(lines: &[Line], depth: usize, output: &mut String) {
+    for (index, line) in lines.iter().enumerate() {
+        // no blank lines directly below the line opening a group
+        let mut first = index == 0 && depth > 0;
+        for comment in &line.comments {
+            write_comment(comment, first, output);
+            first = false;
+        }
+
+        if line.blank && !first && !output.is_empty() {
+            output.push('\n');
+        }
+        *output += &INDENT.repeat(depth);
+        *output += &line.text;
+        if let Some(comment) = line.comment {
+            *output += "  ";
+            *output += comment;
+        }
+        output.push('\n');
+
+        write_lines(&line.children, depth + 1, output);
+        for comment in &line.after {
+            write_comment(comment, false, output);
+        }
+    }
+}
+
+/// Formats the contents of an areas file, `file` is only used in error messages
+///
+/// Indentation becomes two spaces per level, requirements are separated by `, ` and ` OR `,
+/// lines in a group are ordered by difficulty and runs of blank lines are shortened to one.
+/// Comments are kept, but their spacing is normalized
+pub fn format<P: AsRef<Path>>(input: &str, file: P) -> Result<String, Error> {
+    // only format files that parse, so the result has the same meaning
+    let (tokens, metadata) = tokenizer::tokenize(input).map_err(|err| super::areas_error(file.as_ref(), input, err))?;
+    parser::parse_areas(tokens, &metadata).map_err(|err| super::areas_error(file.as_ref(), input, err))?;
+
+    let (tokens, _) = tokenizer::tokenize_with_comments(input).map_err(|err| super::areas_error(file.as_ref(), input, err))?;
+    let (mut lines, comments) = build_lines(input, &tokens);
+    sort_difficulties(&mut lines);
+
+    let mut output = String::with_capacity(input.len());
+    write_lines(&lines, 0, &mut output);
+    for comment in &comments {
+        write_comment(comment, false, &mut output);
+    }
+
+    Ok(output)
+}
+
+/// The first line that differs between `input` and its formatted version, if any
+pub fn first_difference(input: &str, formatted: &str) -> Option<usize> {
+    let mut input_lines = input.lines();
+    let mut formatted_lines = formatted.lines();
+    let mut line = 1;
+    loop {
+        match (input_lines.next(), formatted_lines.next()) {
+            (None, None) => return if input.ends_with('\n') || input.is_empty() { None } else { Some(line - 1) },
+            (input_line, formatted_line) if input_line != formatted_line => return Some(line),
+            _ => line += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_areas() {
+        let input = "\
+# logic
+
+requirement Used:
+    Bash
+anchor A   at 0,0:  # the start
+      nospawn
+      pickup A.Pickup:
+          # only unsafe
+          unsafe: Launch
+          gorlek:
+              Bash,Grapple OR    Dash
+              # commented out
+          moki:   DoubleJump
+  # between
+      conn B: free
+
+
+
+anchor B:
+  conn A: free
+";
+        let formatted = format(input, "areas.wotw").unwrap();
+        assert_eq!(formatted, "\
+# logic
+
+requirement Used:
+  Bash
+anchor A at 0, 0:  # the start
+  nospawn
+  pickup A.Pickup:
+    moki: DoubleJump
+    gorlek:
+      Bash, Grapple OR Dash
+      # commented out
+    # only unsafe
+    unsafe: Launch
+  # between
+  conn B: free
+
+anchor B:
+  conn A: free
+");
+        assert_eq!(format(&formatted, "areas.wotw").unwrap(), formatted);
+        assert_eq!(first_difference(input, &formatted), Some(4));
+        assert_eq!(first_difference(&formatted, &formatted), None);
+
+        let areas = std::fs::read_to_string("areas.wotw").unwrap();
+        let formatted = format(&areas, "areas.wotw").unwrap();
+        assert_eq!(format(&formatted, "areas.wotw").unwrap(), formatted);
+        let (tokens, metadata) = tokenizer::tokenize(&formatted).unwrap();
+        let tree = parser::parse_areas(tokens, &metadata).unwrap();
+        let (tokens, metadata) = tokenizer::tokenize(&areas).unwrap();
+        let original = parser::parse_areas(tokens, &metadata).unwrap();
+        assert_eq!(tree.anchors.len(), original.anchors.len());
+        assert_eq!(areas.matches('#').count(), formatted.matches('#').count());
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{
+    parser::{self, AreaTree, Group, Requirement},
+    tokenizer::{self, Metadata},
+    Location,
+};
+use crate::{
+    error::Error,
+    util::{self, Difficulty, NodeType, constants::TP_ANCHOR},
+};
+
+/// Something in the logic files that is valid, but probably not intended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub file: PathBuf,
+    /// 1-based
+    pub line: usize,
+    pub message: String,
+}
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+/// Reads the logic files and reports anything that looks like a mistake
+pub fn lint<P1: AsRef<Path>, P2: AsRef<Path>>(areas: P1, locations: P2) -> Result<Vec<Lint>, Error> {
+    let input = util::read_file(&areas, "logic")?;
+    let (tokens, metadata) = tokenizer::tokenize(&input).map_err(|err| super::areas_error(areas.as_ref(), &input, err))?;
+    let tree = parser::parse_areas(tokens, &metadata).map_err(|err| super::areas_error(areas.as_ref(), &input, err))?;
+
+    let location_input = util::read_file(&locations, "logic")?;
+    let location_list = super::parse_locations(&location_input).map_err(|message| Error::ParseLogic { file: locations.as_ref().to_path_buf(), line: None, column: None, message })?;
+
+    Ok(check(&tree, &metadata, &location_list, areas.as_ref(), locations.as_ref()))
+}
+
+/// Collects the requirement macros and states a group refers to
+fn collect_references<'a>(group: &Group<'a>, definitions: &mut FxHashSet<&'a str>, states: &mut FxHashSet<&'a str>) {
+    for line in &group.lines {
+        for requirement in line.ands.iter().chain(&line.ors) {
+            match requirement {
+                Requirement::Definition(definition) => { definitions.insert(definition); },
+                Requirement::State(state) => { states.insert(state); },
+                _ => {},
+            }
+        }
+        if let Some(group) = &line.group {
+            collect_references(group, definitions, states);
+        }
+    }
+}
+
+/// Whether each of `requirements` can be matched to a different one of `others`
+///
+/// Repeated requirements like damage add up, so they have to appear as often in `others`
+fn is_sub_multiset(requirements: &[&Requirement], others: &[&Requirement]) -> bool {
+    let mut matched = vec![false; others.len()];
+    requirements.iter().all(|requirement| {
+        let position = others.iter().zip(&matched).position(|(other, &matched)| !matched && other == requirement);
+        position.map(|index| matched[index] = true).is_some()
+    })
+}
+
+/// A requirement line with the requirements of the lines it is nested in
+struct FlatLine<'a, 'b> {
+    line: usize,
+    difficulty: Difficulty,
+    ands: Vec<&'b Requirement<'a>>,
+    ors: Vec<&'b Requirement<'a>>,
+}
+impl FlatLine<'_, '_> {
+    /// Whether every way to meet `other` also meets this line, on every difficulty that allows `other`
+    fn covers(&self, other: &FlatLine) -> bool {
+        self.difficulty <= other.difficulty &&
+        is_sub_multiset(&self.ands, &other.ands) &&
+        (self.ors.is_empty() || (!other.ors.is_empty() && other.ors.iter().all(|or| self.ors.contains(or))) || self.ors.iter().any(|or| other.ands.contains(or)))
+    }
+}
+
+/// Flattens the lines of `group` below the difficulty and requirements of the line that opened it
+///
+/// Groups opened by a line with alternatives can't be flattened and are left out
+fn flatten<'a, 'b>(group: &'b Group<'a>, difficulty: Difficulty, ands: &[&'b Requirement<'a>], lines: &mut Vec<FlatLine<'a, 'b>>) {
+    for line in &group.lines {
+        let mut line_ands = line.ands.iter().collect::<Vec<_>>();
+        let mut ors = line.ors.iter().collect::<Vec<_>>();
+        // a single requirement at the end of the line is just another and
+        if ors.len() == 1 {
+            line_ands.push(ors.remove(0));
+        }
+
+        let mut flat = FlatLine { line: line.line, difficulty, ands: ands.to_vec(), ors };
+        for requirement in line_ands {
+            match requirement {
+                Requirement::Difficulty(line_difficulty) => flat.difficulty = *line_difficulty,
+                _ => flat.ands.push(requirement),
+            }
+        }
+
+        match &line.group {
+            Some(group) => if flat.ors.is_empty() {
+                flatten(group, flat.difficulty, &flat.ands, lines);
+            },
+            None => lines.push(flat),
+        }
+    }
+}
+
+/// Reports requirement lines of a connection that an easier or equal line already allows
+fn check_shadowed(requirements: &Group, file: &Path, lints: &mut Vec<Lint>) {
+    let mut lines = Vec::new();
+    flatten(requirements, Difficulty::Moki, &[], &mut lines);
+
+    for (index, line) in lines.iter().enumerate() {
+        let shadowing = lines.iter().enumerate().find(|&(other_index, other)| {
+            // identical lines cover each other, only report the later one
+            other_index != index && other.covers(line) && (other_index < index || !line.covers(other))
+        });
+        if let Some((_, other)) = shadowing {
+            lints.push(Lint {
+                file: file.to_path_buf(),
+                line: line.line,
+                message: format!("This {:?} requirement line is already allowed by the {:?} line at {}", line.difficulty, other.difficulty, other.line),
+            });
+        }
+    }
+}
+
+/// Finds unreachable anchors, unused states and macros, pickups missing from either file, duplicate connections and shadowed requirement lines
+pub fn check(tree: &AreaTree, metadata: &Metadata, locations: &[Location], areas_file: &Path, locations_file: &Path) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut lint = |file: &Path, line: usize, message: String| lints.push(Lint { file: file.to_path_buf(), line, message });
+
+    let mut used_definitions = FxHashSet::default();
+    let mut used_states = FxHashSet::default();
+    for group in tree.definitions.values().chain(tree.regions.values()) {
+        collect_references(group, &mut used_definitions, &mut used_states);
+    }
+    let mut entered = FxHashSet::default();
+    let mut declared_states = FxHashMap::default();
+    let mut pickups = FxHashMap::default();
+    for anchor in &tree.anchors {
+        for refill in &anchor.refills {
+            if let Some(group) = &refill.requirements {
+                collect_references(group, &mut used_definitions, &mut used_states);
+            }
+        }
+        for connection in &anchor.connections {
+            collect_references(&connection.requirements, &mut used_definitions, &mut used_states);
+            match connection.name {
+                NodeType::Anchor => { entered.insert(connection.identifier); },
+                NodeType::State => { declared_states.entry(connection.identifier).or_insert(connection.requirements.line); },
+                NodeType::Pickup | NodeType::Quest => { pickups.entry(connection.identifier).or_insert(connection.requirements.line); },
+            }
+        }
+    }
+
+    for anchor in &tree.anchors {
+        if !entered.contains(anchor.identifier) && anchor.identifier != TP_ANCHOR {
+            lint(areas_file, anchor.line, format!("No connection leads to anchor {}", anchor.identifier));
+        }
+
+        let mut targets = FxHashMap::default();
+        for connection in &anchor.connections {
+            if let Some(line) = targets.insert((connection.name, connection.identifier), connection.requirements.line) {
+                lint(areas_file, connection.requirements.line, format!("Anchor {} already connects to {:?} {} at line {}", anchor.identifier, connection.name, connection.identifier, line));
+            }
+        }
+    }
+
+    let mut states = declared_states.into_iter().filter(|(state, _)| metadata.states.contains(state) && !used_states.contains(state)).collect::<Vec<_>>();
+    states.sort_unstable_by_key(|&(_, line)| line);
+    for (state, line) in states {
+        lint(areas_file, line, format!("State {} is never used as a requirement", state));
+    }
+
+    let mut definitions = tree.definitions.iter().filter(|(definition, _)| !used_definitions.contains(*definition)).collect::<Vec<_>>();
+    definitions.sort_unstable_by_key(|(_, group)| group.line);
+    for (definition, group) in definitions {
+        lint(areas_file, group.line, format!("Requirement macro {} is never used", definition));
+    }
+
+    let location_names = locations.iter().map(|location| &location.name[..]).collect::<FxHashSet<_>>();
+    let mut missing = pickups.iter().filter(|(pickup, _)| !location_names.contains(*pickup)).collect::<Vec<_>>();
+    missing.sort_unstable_by_key(|&(_, line)| line);
+    for (pickup, &line) in missing {
+        lint(areas_file, line, format!("Pickup {} is missing from {}", pickup, locations_file.display()));
+    }
+    for (index, location) in locations.iter().enumerate() {
+        if !pickups.contains_key(&location.name[..]) {
+            lint(locations_file, index + 1, format!("Pickup {} is never connected to in {}", location.name, areas_file.display()));
+        }
+    }
+
+    for anchor in &tree.anchors {
+        for connection in &anchor.connections {
+            check_shadowed(&connection.requirements, areas_file, &mut lints);
+        }
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lints() {
+        let areas = "\
+requirement Used:
+  Bash
+requirement Unused:
+  Grapple
+anchor A at 0, 0:
+  state A.Used: free
+  state A.Unused: free
+  pickup A.Pickup:
+    moki: Used, DoubleJump
+    gorlek: Used, DoubleJump, Dash
+    unsafe: Glide, Dash
+    kii: Glide
+  pickup A.Missing: free
+  conn B:
+    A.Used
+  conn B: free
+anchor B:
+  conn A: free
+anchor C:
+  conn A: free
+anchor D:
+  conn A:
+    moki: Bash, Damage=20, Damage=20
+    gorlek: Bash, Damage=20
+    kii: Damage=20, Bash, Damage=20, Damage=20
+";
+        let (tokens, metadata) = tokenizer::tokenize(areas).unwrap();
+        let tree = parser::parse_areas(tokens, &metadata).unwrap();
+        let locations = super::super::parse_locations("A.Pickup, Marsh, , , , 1, , 2, 0, 0\nA.Unknown, Marsh, , , , 1, , 3, 0, 0\n").unwrap();
+
+        let lints = check(&tree, &metadata, &locations, Path::new("areas.wotw"), Path::new("loc_data.csv"));
+        let lints = lints.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(lints, [
+            "areas.wotw:16: Anchor A already connects to Anchor B at line 14",
+            "areas.wotw:19: No connection leads to anchor C",
+            "areas.wotw:21: No connection leads to anchor D",
+            "areas.wotw:7: State A.Unused is never used as a requirement",
+            "areas.wotw:3: Requirement macro Unused is never used",
+            "areas.wotw:13: Pickup A.Missing is missing from loc_data.csv",
+            "loc_data.csv:2: Pickup A.Unknown is never connected to in areas.wotw",
+            "areas.wotw:10: This Gorlek requirement line is already allowed by the Moki line at 9",
+            "areas.wotw:11: This Unsafe requirement line is already allowed by the Kii line at 12",
+            "areas.wotw:25: This Kii requirement line is already allowed by the Moki line at 23",
+        ]);
+    }
+}
@@ -6,6 +6,36 @@ use super::tokenizer::{Token, TokenType, Metadata};
 use crate::item::{Resource, Skill, Shard, Teleporter};
 use crate::util::{Difficulty, Glitch, RefillType, NodeType, Enemy, Position};
 
+/// Skill names as the logic spells them
+pub const SKILLS: &[(&str, Skill)] = &[
+    ("Bash", Skill::Bash), ("Blaze", Skill::Blaze), ("Bow", Skill::Bow), ("Burrow", Skill::Burrow), ("Dash", Skill::Dash),
+    ("DoubleJump", Skill::DoubleJump), ("Flap", Skill::Flap), ("Flash", Skill::Flash), ("Glide", Skill::Glide), ("Grapple", Skill::Grapple),
+    ("Grenade", Skill::Grenade), ("Hammer", Skill::Hammer), ("Launch", Skill::Launch), ("Regenerate", Skill::Regenerate), ("Seir", Skill::Seir),
+    ("Sentry", Skill::Sentry), ("Shuriken", Skill::Shuriken), ("Spear", Skill::Spear), ("Sword", Skill::Sword), ("WallJump", Skill::WallJump),
+    ("WaterBreath", Skill::WaterBreath), ("WaterDash", Skill::WaterDash),
+];
+/// Shard names as the logic spells them
+pub const SHARDS: &[(&str, Shard)] = &[
+    ("Arcing", Shard::Arcing), ("Catalyst", Shard::Catalyst), ("Deflector", Shard::Deflector), ("EnergyHarvest", Shard::EnergyHarvest),
+    ("Fracture", Shard::Fracture), ("LifeHarvest", Shard::LifeHarvest), ("Magnet", Shard::Magnet), ("Overflow", Shard::Overflow),
+    ("Sticky", Shard::Sticky), ("Thorn", Shard::Thorn), ("TripleJump", Shard::TripleJump), ("UltraBash", Shard::UltraBash),
+    ("UltraGrapple", Shard::UltraGrapple),
+];
+/// Enemy names as the logic spells them in `Combat=` requirements
+pub const ENEMIES: &[(&str, Enemy)] = &[
+    ("Mantis", Enemy::Mantis), ("Slug", Enemy::Slug), ("WeakSlug", Enemy::WeakSlug), ("BombSlug", Enemy::BombSlug),
+    ("CorruptSlug", Enemy::CorruptSlug), ("SneezeSlug", Enemy::SneezeSlug), ("ShieldSlug", Enemy::ShieldSlug), ("Lizard", Enemy::Lizard),
+    ("Bat", Enemy::Bat), ("Hornbug", Enemy::Hornbug), ("Skeeto", Enemy::Skeeto), ("SmallSkeeto", Enemy::SmallSkeeto), ("Bee", Enemy::Bee),
+    ("Nest", Enemy::Nest), ("Crab", Enemy::Crab), ("SpinCrab", Enemy::SpinCrab), ("Tentacle", Enemy::Tentacle), ("Balloon", Enemy::Balloon),
+    ("Miner", Enemy::Miner), ("MaceMiner", Enemy::MaceMiner), ("ShieldMiner", Enemy::ShieldMiner), ("CrystalMiner", Enemy::CrystalMiner),
+    ("ShieldCrystalMiner", Enemy::ShieldCrystalMiner), ("Sandworm", Enemy::Sandworm), ("Spiderling", Enemy::Spiderling),
+    ("EnergyRefill", Enemy::EnergyRefill),
+];
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table.iter().find(|(other, _)| *other == name).map(|&(_, value)| value)
+}
+
 #[derive(Debug)]
 pub struct ParseError {
     pub description: String,
@@ -20,7 +50,7 @@ impl ParseError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Requirement<'a> {
     Free,
     Impossible,
@@ -69,10 +99,14 @@ pub struct Line<'a> {
     pub ands: Vec<Requirement<'a>>,
     pub ors: Vec<Requirement<'a>>,
     pub group: Option<Group<'a>>,
+    /// The line in the file this starts on
+    pub line: usize,
 }
 #[derive(Debug)]
 pub struct Group<'a> {
-    pub lines: Vec<Line<'a>>
+    pub lines: Vec<Line<'a>>,
+    /// The line in the file with the colon that opened the group
+    pub line: usize,
 }
 #[derive(Debug)]
 pub struct Refill<'a> {
@@ -88,6 +122,8 @@ pub struct Connection<'a> {
 #[derive(Debug)]
 pub struct Anchor<'a> {
     pub identifier: &'a str,
+    /// The line in the file the anchor is declared on
+    pub line: usize,
     pub position: Option<Position>,
     pub can_spawn: bool,
     pub refills: Vec<Refill<'a>>,
@@ -184,35 +220,7 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
                         Ok(result) => result,
                         Err(_) => return Err(not_int(token)),
                     };
-                    let enemy = match enemy {
-                        "Mantis" => Ok(Enemy::Mantis),
-                        "Slug" => Ok(Enemy::Slug),
-                        "WeakSlug" => Ok(Enemy::WeakSlug),
-                        "BombSlug" => Ok(Enemy::BombSlug),
-                        "CorruptSlug" => Ok(Enemy::CorruptSlug),
-                        "SneezeSlug" => Ok(Enemy::SneezeSlug),
-                        "ShieldSlug" => Ok(Enemy::ShieldSlug),
-                        "Lizard" => Ok(Enemy::Lizard),
-                        "Bat" => Ok(Enemy::Bat),
-                        "Hornbug" => Ok(Enemy::Hornbug),
-                        "Skeeto" => Ok(Enemy::Skeeto),
-                        "SmallSkeeto" => Ok(Enemy::SmallSkeeto),
-                        "Bee" => Ok(Enemy::Bee),
-                        "Nest" => Ok(Enemy::Nest),
-                        "Crab" => Ok(Enemy::Crab),
-                        "SpinCrab" => Ok(Enemy::SpinCrab),
-                        "Tentacle" => Ok(Enemy::Tentacle),
-                        "Balloon" => Ok(Enemy::Balloon),
-                        "Miner" => Ok(Enemy::Miner),
-                        "MaceMiner" => Ok(Enemy::MaceMiner),
-                        "ShieldMiner" => Ok(Enemy::ShieldMiner),
-                        "CrystalMiner" => Ok(Enemy::CrystalMiner),
-                        "ShieldCrystalMiner" => Ok(Enemy::ShieldCrystalMiner),
-                        "Sandworm" => Ok(Enemy::Sandworm),
-                        "Spiderling" => Ok(Enemy::Spiderling),
-                        "EnergyRefill" => Ok(Enemy::EnergyRefill),
-                        _ => Err(wrong_requirement(token)),
-                    }?;
+                    let enemy = lookup(ENEMIES, enemy).ok_or_else(|| wrong_requirement(token))?;
                     enemies.push((enemy, amount));
                 }
                 return Ok(Requirement::Combat(enemies));
@@ -254,73 +262,38 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
             }
         }
         None => match keyword {
-            "Arcing" => Ok(Requirement::Shard(Shard::Arcing)),
-            "Bash" => Ok(Requirement::Skill(Skill::Bash)),
-            "Blaze" => Ok(Requirement::Skill(Skill::Blaze)),
-            "Bow" => Ok(Requirement::Skill(Skill::Bow)),
             "BreakCrystal" => Ok(Requirement::BreakCrystal),
-            "Burrow" => Ok(Requirement::Skill(Skill::Burrow)),
             "BurrowsTP" => Ok(Requirement::Teleporter(Teleporter::Burrows)),
-            "Catalyst" => Ok(Requirement::Shard(Shard::Catalyst)),
-            "Dash" => Ok(Requirement::Skill(Skill::Dash)),
-            "Deflector" => Ok(Requirement::Shard(Shard::Deflector)),
             "DenTP" => Ok(Requirement::Teleporter(Teleporter::Den)),
             "DepthsTP" => Ok(Requirement::Teleporter(Teleporter::Depths)),
-            "DoubleJump" => Ok(Requirement::Skill(Skill::DoubleJump)),
             "EastPoolsTP" => Ok(Requirement::Teleporter(Teleporter::EastLuma)),
             "EastWastesTP" => Ok(Requirement::Teleporter(Teleporter::EastWastes)),
             "EastWoodsTP" => Ok(Requirement::Teleporter(Teleporter::EastWoods)),
-            "EnergyHarvest" => Ok(Requirement::Shard(Shard::EnergyHarvest)),
-            "Flap" => Ok(Requirement::Skill(Skill::Flap)),
-            "Flash" => Ok(Requirement::Skill(Skill::Flash)),
             "FlashSwap" => Ok(Requirement::FlashSwap),
-            "Fracture" => Ok(Requirement::Shard(Shard::Fracture)),
             "free" => Ok(Requirement::Free),
             "GladesTP" => Ok(Requirement::Teleporter(Teleporter::Glades)),
-            "Glide" => Ok(Requirement::Skill(Skill::Glide)),
             "GlideHammerJump" => Ok(Requirement::GlideHammerJump),
             "GlideJump" => Ok(Requirement::GlideJump),
             "gorlek" => Ok(Requirement::Difficulty(Difficulty::Gorlek)),
-            "Grapple" => Ok(Requirement::Skill(Skill::Grapple)),
-            "Grenade" => Ok(Requirement::Skill(Skill::Grenade)),
             "GrenadeCancel" => Ok(Requirement::GrenadeCancel),
             "GrenadeJump" => Ok(Requirement::GrenadeJump),
-            "Hammer" => Ok(Requirement::Skill(Skill::Hammer)),
             "HammerBreak" => Ok(Requirement::HammerBreak),
             "HammerJump" => Ok(Requirement::HammerJump),
             "HollowTP" => Ok(Requirement::Teleporter(Teleporter::Hollow)),
             "Impossible" => Ok(Requirement::Impossible),
             "InnerRuinsTP" => Ok(Requirement::Teleporter(Teleporter::InnerRuins)),
             "kii" => Ok(Requirement::Difficulty(Difficulty::Kii)),
-            "Launch" => Ok(Requirement::Skill(Skill::Launch)),
             "LaunchSwap" => Ok(Requirement::LaunchSwap),
-            "LifeHarvest" => Ok(Requirement::Shard(Shard::LifeHarvest)),
-            "Magnet" => Ok(Requirement::Shard(Shard::Magnet)),
             "MarshTP" => Ok(Requirement::Teleporter(Teleporter::Marsh)),
             "moki" => Ok(Requirement::Difficulty(Difficulty::Moki)),
             "OuterRuinsTP" => Ok(Requirement::Teleporter(Teleporter::OuterRuins)),
-            "Overflow" => Ok(Requirement::Shard(Shard::Overflow)),
             "PauseHover" => Ok(Requirement::Glitch(Glitch::PauseHover)),
             "ReachTP" => Ok(Requirement::Teleporter(Teleporter::Reach)),
             "RemoveKillPlane" => Ok(Requirement::Glitch(Glitch::RemoveKillPlane)),
-            "Regenerate" => Ok(Requirement::Skill(Skill::Regenerate)),
-            "Seir" => Ok(Requirement::Skill(Skill::Seir)),
-            "Sentry" => Ok(Requirement::Skill(Skill::Sentry)),
             "ShriekTP" => Ok(Requirement::Teleporter(Teleporter::Shriek)),
-            "Shuriken" => Ok(Requirement::Skill(Skill::Shuriken)),
-            "Spear" => Ok(Requirement::Skill(Skill::Spear)),
             "SpearBreak" => Ok(Requirement::SpearBreak),
-            "Sticky" => Ok(Requirement::Shard(Shard::Sticky)),
-            "Sword" => Ok(Requirement::Skill(Skill::Sword)),
             "SwordJump" => Ok(Requirement::SwordJump),
-            "TripleJump" => Ok(Requirement::Shard(Shard::TripleJump)),
-            "Thorn" => Ok(Requirement::Shard(Shard::Thorn)),
-            "UltraBash" => Ok(Requirement::Shard(Shard::UltraBash)),
-            "UltraGrapple" => Ok(Requirement::Shard(Shard::UltraGrapple)),
             "unsafe" => Ok(Requirement::Difficulty(Difficulty::Unsafe)),
-            "WallJump" => Ok(Requirement::Skill(Skill::WallJump)),
-            "WaterBreath" => Ok(Requirement::Skill(Skill::WaterBreath)),
-            "WaterDash" => Ok(Requirement::Skill(Skill::WaterDash)),
             "Water" => Ok(Requirement::Water),
             "WaveDash" => Ok(Requirement::WaveDash),
             "WellspringTP" => Ok(Requirement::Teleporter(Teleporter::Wellspring)),
@@ -332,7 +305,9 @@ fn parse_requirement<'a>(token: &Token<'a>, metadata: &Metadata) -> Result<Requi
             _ if metadata.states.contains(keyword) || metadata.quests.contains(keyword) => Ok(Requirement::State(keyword)),
             "BlazeSwap" | "Boss" | "BreakWall" | "Combat" | "Damage" | "Danger" | "Energy" | "GrenadeRedirect" | "Health" | "Keystone" | "Ore" | "SentryBreak" | "SentryBurn" | "SentryJump"| "SentryRedirect" | "SentrySwap" | "SwordSJump" | "HammerSJump" | "ShardSlot" | "ShurikenBreak" | "SpiritLight"
                 => Err(wrong_amount(token)),
-            _ => Err(wrong_requirement(token))
+            _ => lookup(SKILLS, keyword).map(Requirement::Skill)
+                .or_else(|| lookup(SHARDS, keyword).map(Requirement::Shard))
+                .ok_or_else(|| wrong_requirement(token)),
         }
     }
 }
@@ -341,6 +316,7 @@ fn parse_line<'a>(tokens: &[Token<'a>], position: &mut usize, metadata: &Metadat
     let mut ands = Vec::new();
     let mut ors = Vec::new();
     let mut group = None;
+    let line = tokens.get(*position).map_or(0, |token| token.line);
     loop {
         let token = next_token!(tokens, *position, TokenType::Requirement, TokenType::Free);
         let requirement = match token.name {
@@ -370,11 +346,12 @@ fn parse_line<'a>(tokens: &[Token<'a>], position: &mut usize, metadata: &Metadat
         }
     }
 
-    Ok(Line { ands, ors, group })
+    Ok(Line { ands, ors, group, line })
 }
 
 fn parse_group<'a>(tokens: &[Token<'a>], position: &mut usize, metadata: &Metadata) -> Result<Group<'a>, ParseError> {
     let mut lines = Vec::new();
+    let line = tokens[*position - 1].line;
 
     let token = next_token!(tokens, *position, TokenType::Free, TokenType::Indent);
     match token.name {
@@ -382,6 +359,7 @@ fn parse_group<'a>(tokens: &[Token<'a>], position: &mut usize, metadata: &Metada
             eat(tokens, position, TokenType::Newline)?;
             lines.push(Line {
                 ands: vec![Requirement::Free],
+                line,
                 ..Line::default()
             });
         },
@@ -397,7 +375,7 @@ fn parse_group<'a>(tokens: &[Token<'a>], position: &mut usize, metadata: &Metada
         _ => wrong_token!(token, TokenType::Free, TokenType::Indent),
     }
 
-    Ok(Group { lines })
+    Ok(Group { lines, line })
 }
 
 fn parse_refill<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &str, metadata: &Metadata) -> Result<Refill<'a>, ParseError> {
@@ -458,6 +436,7 @@ fn parse_anchor_connection<'a>(tokens: &[Token<'a>], position: &mut usize, ident
 }
 
 fn parse_anchor<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a str, metadata: &Metadata) -> Result<Anchor<'a>, ParseError> {
+    let line = tokens[*position - 1].line;
     let mut token = next_token!(tokens, *position, TokenType::Position, TokenType::Group);
 
     let mut anchor_position = None;
@@ -501,7 +480,7 @@ fn parse_anchor<'a>(tokens: &[Token<'a>], position: &mut usize, identifier: &'a
             TokenType::Quest => connections.push(parse_quest(tokens, position, token.value, metadata)?),
             TokenType::Pickup => connections.push(parse_pickup(tokens, position, token.value, metadata)?),
             TokenType::Connection => connections.push(parse_anchor_connection(tokens, position, token.value, metadata)?),
-            TokenType::Dedent => return Ok(Anchor { identifier, position: anchor_position, can_spawn, refills, connections }),
+            TokenType::Dedent => return Ok(Anchor { identifier, line, position: anchor_position, can_spawn, refills, connections }),
             _ => wrong_token!(token, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Dedent),
         }
         token = next_token!(tokens, *position, TokenType::Refill, TokenType::State, TokenType::Quest, TokenType::Pickup, TokenType::Connection, TokenType::Dedent);
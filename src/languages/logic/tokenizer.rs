@@ -3,6 +3,7 @@ use std::cmp::Ordering;
 use rustc_hash::FxHashSet;
 use smallvec::SmallVec;
 
+use super::parser::ParseError;
 use crate::auto_display;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -26,6 +27,8 @@ pub enum TokenType {
     And,
     Or,
     NoSpawn,
+    /// Only emitted by `tokenize_with_comments`
+    Comment,
 }
 auto_display!(TokenType);
 
@@ -58,16 +61,18 @@ struct TokenContext<'a> {
     line: usize,
     position: usize,
     indent_stack: SmallVec<[usize; 8]>,
+    keep_comments: bool,
 }
 impl<'a> TokenContext<'a> {
     #[inline]
-    fn new(input: &'a str) -> Self {
+    fn new(input: &'a str, keep_comments: bool) -> Self {
         TokenContext {
             input,
             metadata: Metadata::default(),
             line: 1,
             position: 0,
             indent_stack: SmallVec::new(),
+            keep_comments,
         }
     }
 
@@ -92,6 +97,8 @@ fn tokenize_whitespace<'a>(tokens: &mut Vec<Token<'a>>, context: &mut TokenConte
     let mut lines = 0;
     let mut next = "";
     let mut offset = 0;
+    // comments on their own lines belong after the indentation tokens of the line they precede
+    let mut own_line_comments = Vec::new();
 
     for (index, char) in context.input.char_indices() {
         if char == '\n' {
@@ -101,6 +108,16 @@ fn tokenize_whitespace<'a>(tokens: &mut Vec<Token<'a>>, context: &mut TokenConte
             // pass
         } else if char == '#' {
             comment = true;
+            if context.keep_comments {
+                let end = context.input[index..].find('\n').map_or(context.input.len(), |end| index + end);
+                let token = Token {
+                    name: TokenType::Comment,
+                    value: context.input[index..end].trim_end(),
+                    line: context.line + lines,
+                    position: context.position + index,
+                };
+                if lines == 0 { tokens.push(token) } else { own_line_comments.push(token) }
+            }
         } else if char == ' ' {
             indent += 1;
         } else if !char.is_whitespace() {
@@ -132,6 +149,7 @@ fn tokenize_whitespace<'a>(tokens: &mut Vec<Token<'a>>, context: &mut TokenConte
             },
         };
     }
+    tokens.append(&mut own_line_comments);
 
     context.progress(offset, next);
     context.line += lines;
@@ -226,8 +244,16 @@ const TOKENIZERS: [for<'a> fn(&mut TokenContext<'a>) -> Option<Token<'a>>; 15] =
     tokenize_requirement,
 ];
 
-pub fn tokenize(input: &str) -> Result<(Vec<Token>, Metadata), String> {
-    let mut context = TokenContext::new(input);
+pub fn tokenize(input: &str) -> Result<(Vec<Token>, Metadata), ParseError> {
+    tokenize_input(input, false)
+}
+/// Like `tokenize`, but keeps comments as `Comment` tokens, which the parser doesn't accept
+pub fn tokenize_with_comments(input: &str) -> Result<(Vec<Token<'_>>, Metadata<'_>), ParseError> {
+    tokenize_input(input, true)
+}
+
+fn tokenize_input(input: &str, keep_comments: bool) -> Result<(Vec<Token<'_>>, Metadata<'_>), ParseError> {
+    let mut context = TokenContext::new(input, keep_comments);
     let mut tokens = Vec::with_capacity(input.len() / 9);
 
     'outer: loop {
@@ -246,7 +272,10 @@ pub fn tokenize(input: &str) -> Result<(Vec<Token>, Metadata), String> {
                 continue 'outer;
             }
         }
-        return Err(format!("Failed to read line {}: {}", context.line, context.input.lines().next().unwrap_or("")));
+        return Err(ParseError {
+            description: format!("Failed to read line {}", context.line),
+            position: context.position,
+        });
     }
 
     Ok((tokens, context.metadata))
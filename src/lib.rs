@@ -1,16 +1,24 @@
+pub mod error;
 pub mod languages;
 pub mod world;
 pub mod inventory;
 pub mod item;
+pub mod lsp;
+pub mod server;
 pub mod settings;
 pub mod generator;
+pub mod playthrough;
+pub mod rpc;
+pub mod spoiler;
+pub mod stats;
 pub mod util;
+pub mod verify;
 
 use std::collections::HashMap;
 
 use rand_seeder::Seeder;
 use rand::{
-    Rng,
+    Rng, SeedableRng,
     rngs::StdRng,
     distributions::{Distribution, Uniform},
     seq::IteratorRandom
@@ -31,14 +39,16 @@ use log4rs::{
     filter::threshold::ThresholdFilter,
 };
 
+use error::Error;
 use world::{
     World,
     graph::{Graph, Node, Pickup},
     pool::Pool
 };
-use generator::Placement;
-use languages::headers::parser::HeaderContext;
+use generator::{Placement, Placements, Sphere, SphereItem, Spheres};
+use languages::headers::{parser::HeaderContext, dependencies::{DependencyGraph, Conflict}};
 use settings::{Settings, Spawn};
+use spoiler::{SeedSpoiler, WorldSpoiler, SpoilerSpawn, SpoilerPlacement, SpoilerSphere, SpoilerSphereItem};
 use util::{
     Difficulty, Position, Zone, UberState, Icon,
     constants::{DEFAULT_SPAWN, MOKI_SPAWNS, GORLEK_SPAWNS, SPAWN_GRANTS, RETRIES},
@@ -46,7 +56,7 @@ use util::{
 
 use crate::languages::headers;
 
-fn pick_spawn<'a, R>(graph: &'a Graph, settings: &Settings, rng: &mut R) -> Result<&'a Node, String>
+fn pick_spawn<'a, R>(graph: &'a Graph, settings: &Settings, rng: &mut R) -> Result<&'a Node, Error>
 where
     R: Rng
 {
@@ -62,18 +72,18 @@ where
                 }
             })
             .choose(rng)
-            .ok_or_else(|| String::from("No valid spawn locations available"))?,
+            .ok_or(Error::NoSpawnAvailable)?,
         Spawn::FullyRandom => valid
             .choose(rng)
-            .ok_or_else(|| String::from("No valid spawn locations available"))?,
+            .ok_or(Error::NoSpawnAvailable)?,
         Spawn::Set(spawn_loc) => valid
             .find(|&node| node.identifier() == spawn_loc)
-            .ok_or_else(|| format!("Spawn {} not found", spawn_loc))?
+            .ok_or_else(|| Error::UnknownSpawn { identifier: spawn_loc.clone() })?
     };
     Ok(spawn)
 }
 
-pub fn write_flags(settings: &Settings, mut flags: Vec<String>) -> String {
+fn collect_flags(settings: &Settings, mut flags: Vec<String>) -> Vec<String> {
     let mut settings_flags = Vec::new();
 
     for flag in settings.goalmodes.iter().map(|goal| format!("{}", goal)) {
@@ -84,6 +94,12 @@ pub fn write_flags(settings: &Settings, mut flags: Vec<String>) -> String {
 
     settings_flags.append(&mut flags);
 
+    settings_flags
+}
+
+pub fn write_flags(settings: &Settings, flags: Vec<String>) -> String {
+    let settings_flags = collect_flags(settings, flags);
+
     if settings_flags.is_empty() {
         String::default()
     } else {
@@ -140,16 +156,16 @@ pub fn initialize_log(use_file: Option<&str>, stderr_log_level: LevelFilter, jso
 
 #[derive(Debug, Default)]
 pub struct ItemDetails {
-    name: Option<String>,
-    display: Option<String>,
-    description: Option<String>,
-    price: Option<u16>,
-    icon: Option<Icon>,
+    pub name: Option<String>,
+    pub display: Option<String>,
+    pub description: Option<String>,
+    pub price: Option<u16>,
+    pub icon: Option<Icon>,
 }
 
 type Flags = Vec<String>;
 type Sets = Vec<String>;
-fn parse_headers<R>(world: &mut World, inline_headers: &[String], settings: &Settings, rng: &mut R) -> Result<(String, Flags, HashMap<String, ItemDetails>, Sets), String>
+fn parse_headers<R>(world: &mut World, inline_headers: &[String], settings: &Settings, rng: &mut R) -> Result<(String, Flags, HashMap<String, ItemDetails>, Sets), Error>
 where R: Rng + ?Sized
 {
     let mut header_block = String::new();
@@ -159,10 +175,6 @@ where R: Rng + ?Sized
     for dependency in &mut dependencies {
         dependency.set_extension("wotwrh");
     }
-    let mut context = HeaderContext {
-        dependencies,
-        ..HeaderContext::default()
-    };
 
     let mut param_values = HashMap::new();
 
@@ -171,7 +183,7 @@ where R: Rng + ?Sized
         let identifier = parts.next().unwrap();
         let mut identifier_parts = identifier.splitn(2, '.');
         let header = identifier_parts.next().unwrap();
-        let identifier = identifier_parts.next().ok_or_else(|| format!("Expected <header>.<parameter> in header arg {}", header_arg))?;
+        let identifier = identifier_parts.next().ok_or_else(|| Error::HeaderArgument { argument: header_arg.clone() })?;
         let value = parts.next().unwrap_or("true");
 
         let prior = param_values.entry(header).or_insert_with(HashMap::new);
@@ -186,10 +198,20 @@ where R: Rng + ?Sized
         }
     }
 
+    let conflicts = DependencyGraph::build(&dependencies, &param_values)?.conflicts();
+    if !conflicts.is_empty() {
+        return Err(Error::HeaderConflicts(conflicts));
+    }
+
+    let mut context = HeaderContext {
+        dependencies,
+        ..HeaderContext::default()
+    };
+
     for header in inline_headers {
         log::trace!("Parsing inline header");
 
-        let header = headers::parser::parse_header("inline header".as_ref(), header, world, &mut context, &param_values, rng).map_err(|err| format!("{} in inline header", err))?;
+        let header = headers::parser::parse_header("inline header".as_ref(), header, world, &mut context, &param_values, rng)?;
 
         header_block += &header;
     }
@@ -202,17 +224,18 @@ where R: Rng + ?Sized
 
         log::trace!("Parsing header {}", path.display());
         let header = util::read_file(&path, "headers")?;
-        let header = headers::parser::parse_header(&path, &header, world, &mut context, &param_values, rng).map_err(|err| format!("{} in header {}", err, path.display()))?;
+        let header = headers::parser::parse_header(&path, &header, world, &mut context, &param_values, rng)?;
 
         parsed.push(path);
         header_block += &header;
     }
 
+    // The dependency graph doesn't know about inline headers
     for header in parsed {
         let name = header.file_stem().unwrap().to_string_lossy().to_string();
 
-        if let Some(incompability) = context.excludes.get(&name) {
-            return Err(format!("{} and {} are incompatible", header.display(), incompability));
+        if let Some(incompability) = context.excludes.remove(&name) {
+            return Err(Error::HeaderConflicts(vec![Conflict::Excluded { header: incompability, excluded: name }]));
         }
     }
 
@@ -225,37 +248,38 @@ where R: Rng + ?Sized
 
 fn generate_placements<'a, R>(
     graph: &'a Graph,
-    worlds: Vec<World<'a>>,
+    worlds: &[World<'a>],
     settings: &Settings,
     spawn_pickup_node: &'a Node,
     custom_items: &HashMap<String, ItemDetails>,
+    retries: usize,
     rng: &mut R
-) -> Result<(Vec<Vec<Placement<'a>>>, Vec<&'a Node>), String>
+) -> Result<(Placements<'a>, Spheres<'a>, Vec<&'a Node>, usize), Error>
 where R: Rng
 {
-    let mut index = 0;
-    loop {
+    for attempt in 1..=retries {
+        // Each attempt gets its own rng stream derived from the seed, so the result only depends on which attempt succeeded
+        let mut attempt_rng = StdRng::seed_from_u64(rng.gen());
+
         let spawn_locs = (0..settings.worlds)
-            .map(|_| pick_spawn(graph, settings, rng))
-            .collect::<Result<Vec<_>, String>>()?;
+            .map(|_| pick_spawn(graph, settings, &mut attempt_rng))
+            .collect::<Result<Vec<_>, _>>()?;
         let identifiers = spawn_locs.iter().map(|spawn_loc| spawn_loc.identifier()).collect::<Vec<_>>();
         log::trace!("Spawning on {}", identifiers.join(", "));
 
-        match generator::generate_placements(worlds.clone(), &spawn_locs, spawn_pickup_node, custom_items, settings, rng) {
-            Ok(seed) => {
-                if index > 0 {
-                    log::info!("Generated seed after {} tries{}", index + 1, if index < RETRIES / 2 { "" } else { " (phew)" });
+        match generator::generate_placements(worlds.to_vec(), &spawn_locs, spawn_pickup_node, custom_items, settings, &mut attempt_rng) {
+            Ok((placements, spheres)) => {
+                if attempt > 1 {
+                    log::info!("Generated seed after {} tries{}", attempt, if attempt <= retries / 2 { "" } else { " (phew)" });
                 }
-                return Ok((seed, spawn_locs));
+                return Ok((placements, spheres, spawn_locs, attempt));
             },
-            Err(err) => log::error!("{}\nRetrying...", err),
+            Err(err) if err.is_retryable() => log::warn!("Attempt {}/{} failed: {}", attempt, retries, err),
+            Err(err) => return Err(err),
         }
+    }
 
-        index += 1;
-        if index == RETRIES {
-            return Err(format!("All {} attempts to generate a seed failed :(", RETRIES));
-        }
-    };
+    Err(Error::GenerationFailed { attempts: retries })
 }
 
 #[inline]
@@ -278,9 +302,7 @@ fn format_placements(world_placements: Vec<Placement>, custom_items: &HashMap<St
             );
 
             util::add_trailing_spaces(&mut placement_line, 46);
-            let item = custom_items.get(&placement.item.code())
-                .and_then(|details| details.name.clone())
-                .unwrap_or_else(|| placement.item.to_string());
+            let item = spoiler::item_name(&placement.item, custom_items);
             let item = util::with_leading_spaces(&item, 36);
 
             placement_line += &format!("  // {} from {}", item, location);
@@ -293,109 +315,332 @@ fn format_placements(world_placements: Vec<Placement>, custom_items: &HashMap<St
     placement_block
 }
 
-type Seeds = Vec<String>;
-type Spoilers = Vec<String>;
-pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String], seed: Option<String>) -> Result<(Seeds, Spoilers), String> {
-    let mut settings = settings.apply_presets()?;
+fn format_sphere_item(progression: &SphereItem, custom_items: &HashMap<String, ItemDetails>, settings: &Settings) -> String {
+    let mut item = spoiler::item_name(&progression.item, custom_items);
+    if settings.worlds > 1 {
+        let player_name = settings.players.get(progression.target_world_index).cloned().unwrap_or_else(|| format!("Player {}", progression.target_world_index + 1));
+        item = format!("{}'s {}", player_name, item);
+    }
+    let item = util::with_leading_spaces(&item, 36);
+
+    format!("//   {} from {}\n", item, progression.node)
+}
+
+fn format_spheres(world_spheres: &[Sphere], custom_items: &HashMap<String, ItemDetails>, settings: &Settings) -> String {
+    let mut sphere_block = String::new();
 
-    let seed = seed.unwrap_or_else(|| {
-        let mut generated_seed = String::new();
-        let numeric = Uniform::from('0'..='9');
-        let mut rng = rand::thread_rng();
+    for (index, sphere) in world_spheres.iter().enumerate() {
+        let length = sphere.locations.len();
+        sphere_block += &format!("// Sphere {}: {} new location{}\n", index, length, if length == 1 { "" } else { "s" });
 
-        for _ in 0..16 {
-            generated_seed.push(numeric.sample(&mut rng));
+        for progression in &sphere.progressions {
+            sphere_block += &format_sphere_item(progression, custom_items, settings);
         }
+    }
 
-        generated_seed
-    });
+    sphere_block
+}
 
-    let slug = settings.slugify(&seed);
+fn format_playthrough(world_playthrough: &[SphereItem], custom_items: &HashMap<String, ItemDetails>, settings: &Settings) -> String {
+    let mut playthrough_block = format!("// Playthrough: {} required items\n", world_playthrough.len());
 
-    let config = settings.write()?;
-    log::trace!("Generating with Settings: {}", config);
+    for progression in world_playthrough {
+        playthrough_block += &format_sphere_item(progression, custom_items, settings);
+    }
 
-    let mut rng: StdRng = Seeder::from(&seed).make_rng();
-    log::trace!("Seeded RNG with {}", seed);
+    playthrough_block
+}
 
-    let mut world = World::new(graph);
-    world.pool = Pool::preset();
-    world.player.spawn(&settings);
+fn random_seed() -> String {
+    let mut generated_seed = String::new();
+    let numeric = Uniform::from('0'..='9');
+    let mut rng = rand::thread_rng();
 
-    let (header_block, custom_flags, custom_items, sets) = parse_headers(&mut world, inline_headers, &settings, &mut rng)?;
+    for _ in 0..16 {
+        generated_seed.push(numeric.sample(&mut rng));
+    }
 
-    let flag_line = write_flags(&settings, custom_flags);
+    generated_seed
+}
 
-    let mut worlds = vec![world];
-    for _ in 1..settings.worlds {
-        worlds.push(worlds[0].clone());
+/// Generates a seed step by step, allowing to inspect the intermediate results before rendering the seed files
+#[derive(Debug)]
+pub struct SeedBuilder<'a> {
+    graph: &'a Graph,
+    settings: Settings,
+    seed: String,
+    inline_headers: Vec<String>,
+    retries: usize,
+}
+impl<'a> SeedBuilder<'a> {
+    /// Applies the presets from the settings and picks a random seed
+    pub fn new(graph: &'a Graph, settings: Settings) -> Result<SeedBuilder<'a>, Error> {
+        Ok(SeedBuilder {
+            graph,
+            settings: settings.apply_presets()?,
+            seed: random_seed(),
+            inline_headers: Vec::new(),
+            retries: RETRIES,
+        })
     }
 
-    let spawn_pickup_node = Node::Pickup(Pickup {
-        identifier: String::from("Spawn"),
-        zone: Zone::Spawn,
-        index: usize::MAX,
-        uber_state: UberState::spawn(),
-        position: Position::default(),
-    });
+    /// Sets the seed used to seed the rng
+    pub fn seed(mut self, seed: String) -> SeedBuilder<'a> {
+        self.seed = seed;
+        self
+    }
+    /// Sets headers that will be parsed in addition to the ones from the settings
+    pub fn inline_headers(mut self, inline_headers: Vec<String>) -> SeedBuilder<'a> {
+        self.inline_headers = inline_headers;
+        self
+    }
+    /// Sets how many attempts to make at placing items before giving up, at least one attempt is always made
+    pub fn retries(mut self, retries: usize) -> SeedBuilder<'a> {
+        self.retries = retries.max(1);
+        self
+    }
 
-    let (placements, spawn_locs) = generate_placements(graph, worlds, &settings, &spawn_pickup_node, &custom_items, &mut rng)?;
+    /// The settings with all presets applied
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
 
-    let spawn_lines = spawn_locs.into_iter().map(|spawn_loc| {
-        let identifier = spawn_loc.identifier();
+    pub fn parse_headers(self) -> Result<ParsedHeaders<'a>, Error> {
+        let SeedBuilder { graph, settings, seed, inline_headers, retries } = self;
+
+        log::trace!("Generating with Settings: {}", settings.write()?);
+
+        let mut rng: StdRng = Seeder::from(&seed).make_rng();
+        log::trace!("Seeded RNG with {}", seed);
+
+        let mut world = World::new(graph);
+        world.pool = Pool::preset();
+        world.player.spawn(&settings);
+
+        let (header_block, flags, custom_items, sets) = parse_headers(&mut world, &inline_headers, &settings, &mut rng)?;
+
+        let spawn_pickup_node = Node::Pickup(Pickup {
+            identifier: String::from("Spawn"),
+            zone: Zone::Spawn,
+            index: usize::MAX,
+            uber_state: UberState::spawn(),
+            position: Position::default(),
+        });
+
+        Ok(ParsedHeaders {
+            graph,
+            settings,
+            seed,
+            rng,
+            retries,
+            world,
+            spawn_pickup_node,
+            header_block,
+            flags,
+            custom_items,
+            sets,
+        })
+    }
+}
 
-        if identifier != DEFAULT_SPAWN {
-            let mut spawn_item = String::new();
-            if let Some(spawn_grant) = SPAWN_GRANTS.iter().find_map(|(spawn, item)| if *spawn == identifier { Some(item) } else { None }) {
-                spawn_item = format!("{}|{}|mute\n", UberState::spawn(), spawn_grant.code());
-            }
+/// A seed after parsing its headers, ready to generate placements
+#[derive(Debug)]
+pub struct ParsedHeaders<'a> {
+    graph: &'a Graph,
+    settings: Settings,
+    seed: String,
+    rng: StdRng,
+    retries: usize,
+    world: World<'a>,
+    spawn_pickup_node: Node,
+    /// The header lines that will be added to the seed
+    pub header_block: String,
+    /// Flags added by headers
+    pub flags: Flags,
+    /// Names, icons and other details for items, as set by headers
+    pub custom_items: HashMap<String, ItemDetails>,
+    /// States that headers set to be reached from the start
+    pub sets: Sets,
+}
+impl<'a> ParsedHeaders<'a> {
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+    pub fn seed(&self) -> &str {
+        &self.seed
+    }
+    /// The world every player starts out with, including the changes made by headers
+    pub fn world(&self) -> &World<'a> {
+        &self.world
+    }
 
-            let position = spawn_loc.position().ok_or_else(|| format!("Tried to spawn on {} which has no specified coordinates", identifier))?;
-            return Ok(format!("Spawn: {}  // {}\n{}", position, identifier, spawn_item));
+    /// Places items into every world
+    ///
+    /// Calling this again continues with the same rng and will yield different placements
+    pub fn generate(&mut self) -> Result<GeneratedSeed<'_>, Error> {
+        self.generate_with(true)
+    }
+
+    /// Places items like [`generate`](Self::generate), but skips finding the playthrough, which is the slow part for seeds that won't be rendered
+    pub fn generate_without_playthrough(&mut self) -> Result<GeneratedSeed<'_>, Error> {
+        self.generate_with(false)
+    }
+
+    fn generate_with(&mut self, playthrough: bool) -> Result<GeneratedSeed<'_>, Error> {
+        let mut worlds = vec![self.world.clone()];
+        for _ in 1..self.settings.worlds {
+            worlds.push(worlds[0].clone());
         }
-        Ok(String::new())
-    }).collect::<Result<Vec<_>, String>>()?;
-
-    let spoiler_blocks = if settings.race {
-        Some(placements.iter()
-            .map(|world_placements| format_placements(world_placements.clone(), &custom_items, false))
-            .collect::<Vec<_>>())
-    } else { None };
-    let placement_blocks = placements.into_iter()
-        .map(|world_placements| format_placements(world_placements, &custom_items, settings.race))
-        .collect::<Vec<_>>();
-
-    let slug_line = format!("// Slug: {}", slug);
-    let seed_line = format!("// Seed: {}", seed);
-    let set_line = if sets.is_empty() {
-        String::new()
-    } else {
-        format!("// Sets: {}", sets.join(", "))
-    };
-    let config_line = format!("// Config: {}", config);
-
-    let mut seeds = (0..settings.worlds).map(|index| {
-        format!("{}{}\n{}\n{}{}\n{}\n{}\n{}", flag_line, spawn_lines[index], placement_blocks[index], header_block, slug_line, seed_line, set_line, config_line)
-    }).collect::<Vec<_>>();
-    headers::parser::postprocess(&mut seeds, graph, &settings)?;
-
-    let spoilers = spoiler_blocks.map_or_else::<Result<_, String>, _, _>(
-        || Ok(Vec::new()),
-        |spoiler_blocks| {
-            settings.race = false;
-            settings.disable_logic_filter = false;
-            let spoiler_config = settings.write()?;
-            let spoiler_config_line = format!("// Config: {}", spoiler_config);
-
-            let mut spoiler_seeds = (0..settings.worlds).map(|index| {
-                format!("{}{}\n{}\n{}{}\n{}\n{}\n{}", flag_line, spawn_lines[index], spoiler_blocks[index], header_block, slug_line, seed_line, set_line, spoiler_config_line)
-            }).collect::<Vec<_>>();
-            headers::parser::postprocess(&mut spoiler_seeds, graph, &settings)?;
-
-            Ok(spoiler_seeds)
-        })?;
-
-    Ok((seeds, spoilers))
+
+        let (placements, spheres, spawns, attempts) = generate_placements(self.graph, &worlds, &self.settings, &self.spawn_pickup_node, &self.custom_items, self.retries, &mut self.rng)?;
+        // Every removed item would need all worlds to be checked again, which is too slow for multiworld
+        let playthrough = if playthrough && self.settings.worlds == 1 {
+            Some(playthrough::minimal_playthrough(&worlds, &spawns, &placements, &spheres, &self.settings)?)
+        } else { None };
+
+        Ok(GeneratedSeed {
+            headers: self,
+            placements,
+            spheres,
+            playthrough,
+            spawns,
+            attempts,
+        })
+    }
+}
+
+type Seeds = Vec<String>;
+type Spoilers = Vec<String>;
+/// A seed after generating placements for every world, ready to be rendered
+#[derive(Debug)]
+pub struct GeneratedSeed<'a> {
+    pub headers: &'a ParsedHeaders<'a>,
+    pub placements: Placements<'a>,
+    pub spheres: Spheres<'a>,
+    /// The progression items needed to reach the goal in each world, `None` if it wasn't calculated, like for multiworld seeds
+    pub playthrough: Option<Vec<Vec<SphereItem<'a>>>>,
+    pub spawns: Vec<&'a Node>,
+    /// How many attempts it took to place the items
+    pub attempts: usize,
+}
+impl GeneratedSeed<'_> {
+    fn player_name(&self, index: usize) -> String {
+        self.headers.settings.players.get(index).cloned().unwrap_or_else(|| format!("Player {}", index + 1))
+    }
+
+    pub fn spoiler(&self) -> SeedSpoiler {
+        let ParsedHeaders { settings, seed, custom_items, .. } = self.headers;
+
+        SeedSpoiler {
+            seed: seed.clone(),
+            slug: settings.slugify(seed),
+            attempts: self.attempts,
+            flags: collect_flags(settings, self.headers.flags.clone()),
+            sets: self.headers.sets.clone(),
+            settings: settings.clone(),
+            worlds: self.placements.iter().zip(&self.spawns).enumerate().map(|(index, (world_placements, &spawn))| WorldSpoiler {
+                player_name: self.player_name(index),
+                spawn: SpoilerSpawn::new(spawn),
+                placements: world_placements.iter().map(|placement| SpoilerPlacement::new(placement, custom_items)).collect(),
+                spheres: self.spheres[index].iter().map(|sphere| SpoilerSphere::new(sphere, custom_items)).collect(),
+                playthrough: self.playthrough.as_ref().map(|playthrough| playthrough[index].iter().map(|progression| SpoilerSphereItem::new(progression, custom_items)).collect()),
+            }).collect(),
+        }
+    }
+
+    /// Writes the `.wotwr` files for every world, and the spoiler files if it's a race seed
+    pub fn render(self) -> Result<(Seeds, Spoilers), Error> {
+        let ParsedHeaders { graph, settings, seed, header_block, custom_items, sets, .. } = self.headers;
+        let mut settings = settings.clone();
+
+        let flag_line = write_flags(&settings, self.headers.flags.clone());
+
+        let spawn_lines = self.spawns.iter().map(|spawn_loc| {
+            let identifier = spawn_loc.identifier();
+
+            if identifier != DEFAULT_SPAWN {
+                let mut spawn_item = String::new();
+                if let Some(spawn_grant) = SPAWN_GRANTS.iter().find_map(|(spawn, item)| if *spawn == identifier { Some(item) } else { None }) {
+                    spawn_item = format!("{}|{}|mute\n", UberState::spawn(), spawn_grant.code());
+                }
+
+                let position = spawn_loc.position().ok_or_else(|| Error::MissingSpawnPosition { identifier: identifier.to_string() })?;
+                return Ok(format!("Spawn: {}  // {}\n{}", position, identifier, spawn_item));
+            }
+            Ok(String::new())
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        let sphere_blocks = self.spheres.iter().enumerate()
+            .map(|(index, world_spheres)| {
+                let mut sphere_block = format_spheres(world_spheres, custom_items, &settings);
+                match &self.playthrough {
+                    Some(playthrough) => sphere_block += &format_playthrough(&playthrough[index], custom_items, &settings),
+                    None => sphere_block += "// Playthrough: not computed\n",
+                }
+                sphere_block
+            })
+            .collect::<Vec<_>>();
+
+        let spoiler_blocks = if settings.race {
+            Some(self.placements.iter().zip(&sphere_blocks)
+                .map(|(world_placements, sphere_block)| format_placements(world_placements.clone(), custom_items, false) + sphere_block)
+                .collect::<Vec<_>>())
+        } else { None };
+        let placement_blocks = self.placements.into_iter().zip(&sphere_blocks)
+            .map(|(world_placements, sphere_block)| {
+                let placement_block = format_placements(world_placements, custom_items, settings.race);
+                if settings.race { placement_block } else { placement_block + sphere_block }
+            })
+            .collect::<Vec<_>>();
+
+        let slug_line = format!("// Slug: {}", settings.slugify(seed));
+        let seed_line = format!("// Seed: {}", seed);
+        let attempts_line = format!("// Attempts: {}", self.attempts);
+        let set_line = if sets.is_empty() {
+            String::new()
+        } else {
+            format!("// Sets: {}", sets.join(", "))
+        };
+        let config_line = format!("// Config: {}", settings.write()?);
+
+        let mut seeds = (0..settings.worlds).map(|index| {
+            format!("{}{}\n{}\n{}{}\n{}\n{}\n{}\n{}", flag_line, spawn_lines[index], placement_blocks[index], header_block, slug_line, seed_line, attempts_line, set_line, config_line)
+        }).collect::<Vec<_>>();
+        headers::parser::postprocess(&mut seeds, graph, &settings)?;
+
+        let spoilers = spoiler_blocks.map_or_else::<Result<_, Error>, _, _>(
+            || Ok(Vec::new()),
+            |spoiler_blocks| {
+                settings.race = false;
+                settings.disable_logic_filter = false;
+                let spoiler_config = settings.write()?;
+                let spoiler_config_line = format!("// Config: {}", spoiler_config);
+
+                let mut spoiler_seeds = (0..settings.worlds).map(|index| {
+                    format!("{}{}\n{}\n{}{}\n{}\n{}\n{}\n{}", flag_line, spawn_lines[index], spoiler_blocks[index], header_block, slug_line, seed_line, attempts_line, set_line, spoiler_config_line)
+                }).collect::<Vec<_>>();
+                headers::parser::postprocess(&mut spoiler_seeds, graph, &settings)?;
+
+                Ok(spoiler_seeds)
+            })?;
+
+        Ok((seeds, spoilers))
+    }
+}
+
+pub fn generate_seed(graph: &Graph, settings: Settings, inline_headers: &[String], seed: Option<String>) -> Result<(Seeds, Spoilers, SeedSpoiler), Error> {
+    let mut builder = SeedBuilder::new(graph, settings)?.inline_headers(inline_headers.to_vec());
+    if let Some(seed) = seed {
+        builder = builder.seed(seed);
+    }
+
+    let mut headers = builder.parse_headers()?;
+    let generated = headers.generate()?;
+
+    let seed_spoiler = generated.spoiler();
+    let (seeds, spoilers) = generated.render()?;
+
+    Ok((seeds, spoilers, seed_spoiler))
 }
 
 #[cfg(test)]
@@ -411,7 +656,8 @@ mod tests {
         let mut settings = Settings::default();
         let mut graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
 
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        let (_, _, seed_spoiler) = generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        seed_spoiler.to_json().unwrap();
 
         settings.hard = true;
         generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
@@ -448,6 +694,110 @@ mod tests {
         settings = Settings::default();
         graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
         settings.worlds = 5;
-        generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        let (seeds, _, seed_spoiler) = generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        assert!(seeds.iter().all(|seed| seed.contains("// Playthrough: not computed")));
+        assert!(seed_spoiler.worlds.iter().all(|world| world.playthrough.is_none()));
+    }
+
+    #[test]
+    fn weighted_seed() {
+        use crate::item::Item;
+
+        let mut settings = Settings {
+            placement_weights: serde_json::from_str(r#"[
+                { "item": { "category": "spiritLight" }, "weight": 4 },
+                { "item": { "category": "resource" }, "weight": 0.25 }
+            ]"#).unwrap(),
+            ..Settings::default()
+        };
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        generate_seed(&graph, settings.clone(), &Vec::new(), Some(String::from("weights"))).unwrap();
+
+        let skills_in_wastes = |settings: Settings| {
+            let mut headers = SeedBuilder::new(&graph, settings).unwrap().seed(String::from("weights")).parse_headers().unwrap();
+            let generated = headers.generate().unwrap();
+            generated.placements[0].iter()
+                .filter(|placement| placement.uber_state != UberState::spawn() && matches!(placement.item, Item::Skill(_)))
+                .filter(|placement| placement.node.map_or(false, |node| node.zone() == Some(Zone::Wastes)))
+                .count()
+        };
+        assert!(skills_in_wastes(settings.clone()) > 0);
+        // A weight of 0 keeps the same seed from placing any skills there
+        settings.placement_weights.push(serde_json::from_str(r#"{ "item": { "category": "skill" }, "location": { "zone": "Wastes" }, "weight": 0 }"#).unwrap());
+        assert_eq!(skills_in_wastes(settings), 0);
+    }
+
+    #[test]
+    fn rules_seed() {
+        use crate::{item::{Item, Skill}, settings::rules::{self, PlacementRule}};
+
+        let rules: Vec<PlacementRule> = serde_json::from_str(r#"[
+            { "rule": "never", "item": { "category": "skill" }, "location": { "zone": "Depths" } },
+            { "rule": "only", "item": { "code": "2|8" }, "location": { "zone": "Glades" } },
+            { "rule": "junkOnly", "location": { "zone": "Wastes" } }
+        ]"#).unwrap();
+        let settings = Settings {
+            placement_rules: rules.clone(),
+            ..Settings::default()
+        };
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let mut headers = SeedBuilder::new(&graph, settings.clone()).unwrap().seed(String::from("rules")).parse_headers().unwrap();
+        let generated = headers.generate().unwrap();
+
+        let placements = generated.placements[0].iter()
+            .filter(|placement| placement.uber_state != UberState::spawn())
+            .filter_map(|placement| placement.node.map(|node| (node, &placement.item)))
+            .collect::<Vec<_>>();
+        for (node, item) in &placements {
+            assert!(rules::allows(&rules, item, node, settings.difficulty), "{} on {} breaks the rules", item, node.identifier());
+        }
+        assert!(placements.iter().any(|(node, _)| node.zone() == Some(Zone::Wastes)));
+        let (launch, _) = placements.iter().find(|(_, item)| **item == Item::Skill(Skill::Launch)).unwrap();
+        assert_eq!(launch.zone(), Some(Zone::Glades));
+    }
+
+    #[test]
+    fn seed_builder() {
+        let settings = Settings {
+            presets: vec![PathBuf::from("gorlek")],
+            ..Settings::default()
+        };
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let builder = SeedBuilder::new(&graph, settings.clone()).unwrap().seed(String::from("builder"));
+        assert_eq!(builder.settings().difficulty, Difficulty::Gorlek);
+
+        let mut headers = builder.parse_headers().unwrap();
+        let generated = headers.generate().unwrap();
+        assert_eq!(generated.placements.len(), 1);
+        assert_eq!(generated.spawns.len(), 1);
+        let attempts_line = format!("// Attempts: {}", generated.attempts);
+        let (seeds, _) = generated.render().unwrap();
+        assert!(seeds[0].lines().any(|line| line == attempts_line));
+
+        let (expected, _, _) = generate_seed(&graph, settings, &Vec::new(), Some(String::from("builder"))).unwrap();
+        assert_eq!(seeds, expected);
+    }
+
+    #[test]
+    fn deterministic_retries() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        // find a seed whose first attempt fails
+        let retried = (0..50).map(|index| format!("retry_{}", index)).find(|seed| {
+            let mut headers = SeedBuilder::new(&graph, settings.clone()).unwrap().seed(seed.clone()).parse_headers().unwrap();
+            headers.generate_without_playthrough().map_or(false, |generated| generated.attempts > 1)
+        }).expect("no seed needed a retry");
+
+        let (seeds, _, spoiler) = generate_seed(&graph, settings.clone(), &Vec::new(), Some(retried.clone())).unwrap();
+        assert!(spoiler.attempts > 1);
+        let attempts_line = format!("// Attempts: {}", spoiler.attempts);
+        assert!(seeds[0].lines().any(|line| line == attempts_line));
+
+        let (again, _, _) = generate_seed(&graph, settings, &Vec::new(), Some(retried)).unwrap();
+        assert_eq!(seeds, again);
     }
 }
@@ -0,0 +1,451 @@
+pub mod logic;
+pub mod header;
+
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use rustc_hash::FxHashMap;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::error::Error;
+
+/// A position as the language server protocol counts it, `character` is in UTF-16 code units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+impl Range {
+    /// The range between two byte offsets into `text`
+    pub fn from_offsets(text: &str, start: usize, end: usize) -> Range {
+        Range {
+            start: offset_to_position(text, start),
+            end: offset_to_position(text, end),
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(into = "u8")]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+}
+impl From<Severity> for u8 {
+    fn from(severity: Severity) -> u8 {
+        severity as u8
+    }
+}
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub source: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(into = "u8")]
+pub enum CompletionKind {
+    Function = 3,
+    Variable = 6,
+    Module = 9,
+    Keyword = 14,
+    EnumMember = 20,
+}
+impl From<CompletionKind> for u8 {
+    fn from(kind: CompletionKind) -> u8 {
+        kind as u8
+    }
+}
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompletionItem {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: &'static str,
+}
+impl CompletionItem {
+    pub fn new(label: impl Into<String>, kind: CompletionKind, detail: &'static str) -> CompletionItem {
+        CompletionItem { label: label.into(), kind, detail }
+    }
+}
+
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    Position {
+        line: text[..offset].matches('\n').count(),
+        character: text[line_start..offset].encode_utf16().count(),
+    }
+}
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut character = 0;
+    for (index, char) in line.char_indices() {
+        if character >= position.character {
+            return line_start + index;
+        }
+        character += char.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// The file a `file://` uri points to
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail.get(..2).and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(escaped) if byte == b'%' => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            },
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            },
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+
+    // windows paths look like file:///C:/...
+    let path = match path.strip_prefix('/') {
+        Some(windows) if windows.get(1..2) == Some(":") => windows.to_string(),
+        _ => path,
+    };
+    Some(PathBuf::from(path))
+}
+/// The `file://` uri for `path`
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') { uri.push('/') }
+    for char in path.chars() {
+        match char {
+            ' ' => uri.push_str("%20"),
+            '%' => uri.push_str("%25"),
+            '#' => uri.push_str("%23"),
+            _ => uri.push(char),
+        }
+    }
+    uri
+}
+
+/// The languages the server knows, decided by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    Logic,
+    Header,
+}
+impl Language {
+    fn of(uri: &str) -> Option<Language> {
+        if uri.ends_with(".wotw") {
+            Some(Language::Logic)
+        } else if uri.ends_with(".wotwrh") {
+            Some(Language::Header)
+        } else { None }
+    }
+}
+
+fn diagnostics(uri: &str, text: &str) -> Vec<Diagnostic> {
+    match Language::of(uri) {
+        Some(Language::Logic) => logic::diagnostics(text),
+        Some(Language::Header) => header::diagnostics(uri, text),
+        None => Vec::new(),
+    }
+}
+fn definition(uri: &str, text: &str, offset: usize) -> Option<Location> {
+    match Language::of(uri)? {
+        Language::Logic => logic::definition(text, offset).map(|range| Location { uri: uri.to_string(), range }),
+        Language::Header => header::definition(uri, text, offset),
+    }
+}
+fn hover(uri: &str, text: &str, offset: usize) -> Option<String> {
+    match Language::of(uri)? {
+        Language::Logic => logic::hover(text, offset),
+        Language::Header => header::hover(text, offset),
+    }
+}
+fn completion(uri: &str, text: &str, offset: usize) -> Vec<CompletionItem> {
+    match Language::of(uri) {
+        Some(Language::Logic) => logic::completion(text, offset),
+        Some(Language::Header) => header::completion(text, offset),
+        None => Vec::new(),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentItem {
+    uri: String,
+    text: String,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentIdentifier {
+    uri: String,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidOpenParams {
+    text_document: TextDocumentItem,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContentChange {
+    text: String,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidChangeParams {
+    text_document: TextDocumentIdentifier,
+    content_changes: Vec<ContentChange>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DidCloseParams {
+    text_document: TextDocumentIdentifier,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentPositionParams {
+    text_document: TextDocumentIdentifier,
+    position: Position,
+}
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_PARAMS: i32 = -32602;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+fn protocol_error(message: impl ToString) -> Error {
+    Error::LanguageServer(message.to_string())
+}
+
+/// Reads one message, returns `None` once the input ends
+fn read_message<R: BufRead>(input: &mut R) -> Result<Option<Vec<u8>>, Error> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).map_err(protocol_error)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            if length.is_some() { break }
+            continue;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().map_err(|_| protocol_error(format!("Invalid header {}", header)))?);
+        }
+    }
+
+    let mut content = vec![0; length.unwrap_or_default()];
+    input.read_exact(&mut content).map_err(protocol_error)?;
+    Ok(Some(content))
+}
+fn write_message<W: Write>(output: &mut W, message: &Value) -> Result<(), Error> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content).map_err(protocol_error)?;
+    output.flush().map_err(protocol_error)
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+fn error_response(id: Value, code: i32, message: impl ToString) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message.to_string() } })
+}
+fn publish_diagnostics(uri: &str, diagnostics: &[Diagnostic]) -> Value {
+    json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": uri, "diagnostics": diagnostics } })
+}
+
+fn params<T: DeserializeOwned>(message: &mut Value) -> Result<T, String> {
+    serde_json::from_value(message["params"].take()).map_err(|err| err.to_string())
+}
+
+#[derive(Default)]
+struct Server {
+    documents: FxHashMap<String, String>,
+}
+impl Server {
+    /// Handles one message, returning the messages to send back and whether the server should exit
+    fn handle(&mut self, mut message: Value) -> (Vec<Value>, bool) {
+        let id = message.get("id").cloned();
+        let method = match message["method"].as_str() {
+            Some(method) => method.to_string(),
+            None => return (Vec::new(), false),  // a response to something we never asked
+        };
+        log::trace!("Received {}", method);
+
+        let result = match method.as_str() {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["=", "+", "!", "("] },
+                },
+                "serverInfo": { "name": "seedgen", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "exit" => return (Vec::new(), true),
+            "textDocument/didOpen" => return match params::<DidOpenParams>(&mut message) {
+                Ok(params) => (self.update(params.text_document.uri, params.text_document.text), false),
+                Err(err) => { log::warn!("Invalid didOpen notification: {}", err); (Vec::new(), false) },
+            },
+            "textDocument/didChange" => return match params::<DidChangeParams>(&mut message) {
+                Ok(DidChangeParams { text_document, content_changes }) => match content_changes.into_iter().last() {
+                    Some(change) => (self.update(text_document.uri, change.text), false),
+                    None => (Vec::new(), false),
+                },
+                Err(err) => { log::warn!("Invalid didChange notification: {}", err); (Vec::new(), false) },
+            },
+            "textDocument/didClose" => return match params::<DidCloseParams>(&mut message) {
+                Ok(params) => {
+                    self.documents.remove(&params.text_document.uri);
+                    (vec![publish_diagnostics(&params.text_document.uri, &[])], false)
+                },
+                Err(err) => { log::warn!("Invalid didClose notification: {}", err); (Vec::new(), false) },
+            },
+            "textDocument/definition" | "textDocument/hover" | "textDocument/completion" =>
+                params::<TextDocumentPositionParams>(&mut message).map(|params| self.query(&method, params)),
+            _ => Err(String::new()),
+        };
+
+        let id = match id {
+            Some(id) => id,
+            None => return (Vec::new(), false),  // notifications we don't care about
+        };
+        let reply = match result {
+            Ok(result) => response(id, result),
+            Err(err) if err.is_empty() => error_response(id, METHOD_NOT_FOUND, format!("Unknown method {}", method)),
+            Err(err) => error_response(id, INVALID_PARAMS, err),
+        };
+        (vec![reply], false)
+    }
+
+    fn update(&mut self, uri: String, text: String) -> Vec<Value> {
+        let diagnostics = diagnostics(&uri, &text);
+        let notification = publish_diagnostics(&uri, &diagnostics);
+        self.documents.insert(uri, text);
+        vec![notification]
+    }
+
+    fn query(&self, method: &str, params: TextDocumentPositionParams) -> Value {
+        let uri = params.text_document.uri;
+        let text = match self.documents.get(&uri) {
+            Some(text) => text,
+            None => return Value::Null,
+        };
+        let offset = position_to_offset(text, params.position);
+
+        match method {
+            "textDocument/definition" => json!(definition(&uri, text, offset)),
+            "textDocument/hover" => hover(&uri, text, offset).map_or(Value::Null, |contents| json!({ "contents": { "kind": "markdown", "value": contents } })),
+            _ => json!(completion(&uri, text, offset)),
+        }
+    }
+}
+
+/// Runs a language server speaking the language server protocol over `input` and `output` until the client asks it to exit
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> Result<(), Error> {
+    let mut server = Server::default();
+
+    while let Some(content) = read_message(&mut input)? {
+        let message = match serde_json::from_slice::<Value>(&content) {
+            Ok(message) => message,
+            Err(err) => {
+                write_message(&mut output, &error_response(Value::Null, PARSE_ERROR, err))?;
+                continue;
+            },
+        };
+
+        let (replies, exit) = server.handle(message);
+        for reply in replies {
+            write_message(&mut output, &reply)?;
+        }
+        if exit { break }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(message: Value) -> String {
+        let content = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    #[test]
+    fn session() {
+        let text = "requirement Tough:\n  Health=5\nanchor Start:\n  conn End:\n    Tough\nanchor End:\n  conn Start: free\n";
+        let input = [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": { "uri": "file:///areas.wotw", "languageId": "wotw", "version": 1, "text": text } } }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": { "textDocument": { "uri": "file:///areas.wotw" }, "position": { "line": 4, "character": 6 } } }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "unknown/method" }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ].iter().cloned().map(frame).collect::<String>();
+
+        let mut output = Vec::new();
+        run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let mut replies = Vec::new();
+        let mut rest = output.as_bytes();
+        while let Some(content) = read_message(&mut rest).unwrap() {
+            replies.push(serde_json::from_slice::<Value>(&content).unwrap());
+        }
+
+        assert_eq!(replies.len(), 5);
+        assert_eq!(replies[0]["result"]["capabilities"]["definitionProvider"], json!(true));
+        assert_eq!(replies[1]["method"], json!("textDocument/publishDiagnostics"));
+        assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+        assert_eq!(replies[2]["result"]["range"]["start"], json!({ "line": 0, "character": 12 }));
+        assert_eq!(replies[3]["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(replies[4]["result"], Value::Null);
+    }
+
+    #[test]
+    fn positions() {
+        let text = "ab\nc\u{1F600}d\n";
+        let position = offset_to_position(text, 8);
+        assert_eq!(position, Position { line: 1, character: 3 });
+        assert_eq!(position_to_offset(text, position), 8);
+        assert_eq!(position_to_offset(text, Position { line: 5, character: 0 }), text.len());
+
+        assert_eq!(uri_to_path("file:///home/ori/my%20seeds/a.wotwrh").unwrap(), PathBuf::from("/home/ori/my seeds/a.wotwrh"));
+        assert_eq!(path_to_uri(Path::new("/home/ori/my seeds/a.wotwrh")), "file:///home/ori/my%20seeds/a.wotwrh");
+        assert!(uri_to_path("untitled:Untitled-1").is_none());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+};
+
+use super::{Range, Location, Diagnostic, Severity, CompletionItem, CompletionKind};
+use crate::{
+    item::Item,
+    languages::headers::{expression, parser},
+};
+
+/// The byte range of `line` within `text` without its surrounding whitespace
+fn line_range(text: &str, line_start: usize, line: &str) -> Range {
+    let start = line_start + (line.len() - line.trim_start().len());
+    let end = line_start + line.trim_end().len();
+    Range::from_offsets(text, start, end.max(start))
+}
+
+/// Splits `text` into lines along with the offset each starts at
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len() + 1;
+        Some((start, line.strip_suffix('\r').unwrap_or(line)))
+    })
+}
+
+/// Cuts off `//` comments, returns `None` for lines that shouldn't be checked at all
+fn strip_comment(line: &str) -> Option<&str> {
+    let trimmed = line.trim();
+    if trimmed.starts_with("////") {
+        return None;
+    }
+    match trimmed.find("//") {
+        Some(index) if trimmed[index..].contains("skip-validate") => None,
+        Some(index) => Some(trimmed[..index].trim_end()),
+        None => Some(trimmed),
+    }
+}
+
+/// The defaults of all `!!parameter`s declared in the header
+fn parameters(text: &str) -> HashMap<String, String> {
+    let mut parameters = HashMap::new();
+    for (_, line) in lines(text) {
+        if let Some(parameter) = strip_comment(line).and_then(|line| line.strip_prefix("!!parameter ")) {
+            parser::parameter_command(parameter.trim(), &mut parameters, &HashMap::new()).unwrap_or_default();
+        }
+    }
+    parameters
+}
+
+/// Where `!!include <name>` would find the header, relative to the document at `uri`
+fn include_path(uri: &str, include: &str) -> Option<PathBuf> {
+    let mut path = super::uri_to_path(uri)?.parent()?.join(include.trim());
+    path.set_extension("wotwrh");
+    Some(path)
+}
+
+/// Reports every line that can't be parsed, unlike the seed generation which stops at the first one
+pub fn diagnostics(uri: &str, text: &str) -> Vec<Diagnostic> {
+    let source_lines = lines(text).collect::<Vec<_>>();
+    let error = |index: usize, message: String| {
+        let (line_start, line) = source_lines[index.min(source_lines.len() - 1)];
+        Diagnostic {
+            range: line_range(text, line_start, line),
+            severity: Severity::Error,
+            source: "seedgen",
+            message,
+        }
+    };
+
+    let name = super::uri_to_path(uri).unwrap_or_else(|| PathBuf::from("header.wotwrh"));
+    let mut errors = parser::header_errors(&name, text);
+
+    // The seed generation looks for includes in the headers folder, the editor next to the document
+    for (index, (_, line)) in source_lines.iter().enumerate() {
+        if let Some(include) = strip_comment(line).and_then(|line| line.strip_prefix("!!include ")) {
+            let found = include_path(uri, include).map_or(true, |path| path.is_file());
+            if !found && !errors.iter().any(|(other, _)| *other == index) {
+                errors.push((index, format!("Included header {} not found", include.trim())));
+            }
+        }
+    }
+    errors.sort_by_key(|(index, _)| *index);
+
+    errors.into_iter().map(|(index, message)| error(index, message)).collect()
+}
+
+/// Describes an item the way a player would read it
+pub fn describe(item: &Item) -> String {
+    match item {
+        Item::UberState(command) => {
+            let action = match (command.signed, command.sign) {
+                (false, _) => "set",
+                (true, true) => "add to",
+                (true, false) => "subtract from",
+            };
+            format!("{} UberState {} ({}) {} {}", action, command.uber_identifier, command.uber_type, if command.signed { "by" } else { "to" }, command.operator)
+        },
+        Item::Command(command) => format!("command {:?}", command),
+        Item::WheelCommand(command) => format!("wheel command {:?}", command),
+        Item::ShopCommand(command) => format!("shop command {:?}", command),
+        _ => item.to_string(),
+    }
+}
+
+/// The `$PARAM(...)` around `column` in `line`, if any
+fn parameter_at(line: &str, column: usize) -> Option<&str> {
+    line.match_indices("$PARAM(").find_map(|(start, _)| {
+        let end = start + line[start..].find(')')?;
+        if start > column || column > end { return None }
+        Some(line[start + 7..end].trim())
+    })
+}
+
+/// Describes the item or parameter at `offset`
+pub fn hover(text: &str, offset: usize) -> Option<String> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line = text[line_start..].lines().next().unwrap_or_default();
+    let parameters = parameters(text);
+
+    if let Some(parameter) = parameter_at(line, offset - line_start) {
+        return Some(match parameters.get(parameter) {
+            Some(default) => format!("parameter `{}`, defaults to `{}`", parameter, default),
+            None => match expression::evaluate(parameter, &parameters) {
+                Ok(value) => format!("`{}`, with the defaults `{}`", parameter, value),
+                Err(err) => err.to_string(),
+            },
+        });
+    }
+
+    let mut line = strip_comment(line)?.to_string();
+    parser::apply_parameters(&mut line, &parameters).ok()?;
+
+    if let Some(command) = line.strip_prefix("!!") {
+        let (name, arguments) = command.split_once(' ')?;
+        let mut item = arguments.trim();
+        match name {
+            "add" | "remove" => { parser::parse_count(&mut item); },
+            "name" | "display" | "description" | "price" | "icon" => item = item.split(' ').next()?,
+            _ => return None,
+        }
+        let item = parser::parse_item(item).ok()?;
+        Some(describe(&item))
+    } else {
+        let (uber_state, item) = parser::parse_pickup(line.trim_start_matches('!')).ok()?;
+        Some(format!("{} on `{}`", describe(&item), uber_state))
+    }
+}
+
+/// Offers commands after `!!` and parameter names inside `$PARAM(...)`
+pub fn completion(text: &str, offset: usize) -> Vec<CompletionItem> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line = &text[line_start..offset];
+
+    if let Some(start) = line.rfind("$PARAM(") {
+        if !line[start..].contains(')') {
+            let mut parameters = parameters(text).into_iter().collect::<Vec<_>>();
+            parameters.sort_unstable();
+            return parameters.into_iter().map(|(parameter, _)| CompletionItem::new(parameter, CompletionKind::Variable, "Parameter")).collect();
+        }
+    }
+
+    if let Some(command) = line.trim_start().strip_prefix("!!") {
+        if !command.contains(' ') {
+            return parser::COMMANDS.iter().map(|&command| CompletionItem::new(command, CompletionKind::Keyword, "Command")).collect();
+        }
+    }
+
+    Vec::new()
+}
+
+/// Finds the header an `!!include` at `offset` refers to
+pub fn definition(uri: &str, text: &str, offset: usize) -> Option<Location> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line = text[line_start..].lines().next().unwrap_or_default();
+    let include = strip_comment(line)?.strip_prefix("!!include ")?;
+
+    let path = include_path(uri, include)?;
+    if !path.is_file() {
+        return None;
+    }
+    Some(Location {
+        uri: super::path_to_uri(&path),
+        range: Range::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::{Position, position_to_offset};
+
+    const HEADER: &str = "\
+#hide
+/// A test header
+!!parameter amount int:3
+!!parameter strict bool:false
+3|0|8|9|104|bool|true
+!!add $PARAM(amount)x2|8
+!!frobnicate everything
+3|0|99|nothing  // a broken pickup
+3|0|99|nothing  // skip-validate
+!!if !strict
+!!remove $PARAM(missing)
+3|0|6|done
+";
+
+    #[test]
+    fn header_diagnostics() {
+        let diagnostics = diagnostics("untitled:header.wotwrh", HEADER);
+        let lines = diagnostics.iter().map(|diagnostic| diagnostic.range.start.line).collect::<Vec<_>>();
+        assert_eq!(lines, [6, 7, 10, 11]);
+        assert_eq!(diagnostics[0].message, "Unknown command frobnicate everything");
+        assert_eq!(diagnostics[2].message, "Unknown parameter missing");
+        assert_eq!(diagnostics[3].message, "!!if without !!endif");
+
+        let lines = |text| super::diagnostics("untitled:header.wotwrh", text).iter().map(|diagnostic| diagnostic.range.start.line).collect::<Vec<_>>();
+        assert_eq!(lines("!!for i in 0..3\n!!add $i|nothing\n!!endfor\n!!missing()"), [3]);
+        assert_eq!(lines("!!for i in 0..3\n!!add 5|$i|nothing\n!!endfor"), [1]);
+        assert_eq!(lines("!!version 1.2\n!!requires seedgen >= 0.0.1\n!!requires seedgen >= 999"), [2]);
+    }
+
+    #[test]
+    fn header_hover_and_completion() {
+        let offset = |line: usize, character: usize| position_to_offset(HEADER, Position { line, character });
+
+        assert_eq!(hover(HEADER, offset(4, 8)).unwrap(), "set UberState 9|104 (bool) to true on `3|0`");
+        assert_eq!(hover(HEADER, offset(5, 2)).unwrap(), "Launch");
+        assert_eq!(hover(HEADER, offset(5, 10)).unwrap(), "parameter `amount`, defaults to `3`");
+
+        let text = format!("{}!!add $PARAM(", HEADER);
+        let items = completion(&text, text.len());
+        assert_eq!(items.iter().map(|item| item.label.as_str()).collect::<Vec<_>>(), ["amount", "strict"]);
+        let text = format!("{}!!inc", HEADER);
+        assert_eq!(completion(&text, text.len()).len(), parser::COMMANDS.len());
+    }
+
+    #[test]
+    fn include_definition() {
+        let directory = std::env::current_dir().unwrap().join("headers");
+        let uri = crate::lsp::path_to_uri(&directory.join("test.wotwrh"));
+        let text = "!!include bonus_items\n!!include missing_header\n";
+
+        let location = definition(&uri, text, 3).unwrap();
+        assert_eq!(crate::lsp::uri_to_path(&location.uri).unwrap(), directory.join("bonus_items.wotwrh"));
+        assert!(definition(&uri, text, 25).is_none());
+
+        let diagnostics = diagnostics(&uri, text);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Included header missing_header not found");
+    }
+}
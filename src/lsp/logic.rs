@@ -0,0 +1,240 @@
+use rustc_hash::FxHashMap;
+
+use super::{Range, Diagnostic, Severity, CompletionItem, CompletionKind};
+use crate::languages::logic::{
+    parser::{self, ParseError},
+    tokenizer::{self, Token, TokenType},
+};
+
+/// What a name in the logic refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SymbolKind {
+    Definition,
+    Anchor,
+    State,
+}
+
+/// The places where names are declared, as byte offsets
+#[derive(Default)]
+struct Symbols<'a> {
+    declarations: FxHashMap<(SymbolKind, &'a str), usize>,
+    /// Which anchor each state belongs to
+    state_anchors: FxHashMap<&'a str, &'a str>,
+}
+impl<'a> Symbols<'a> {
+    fn new(tokens: &[Token<'a>]) -> Symbols<'a> {
+        let mut symbols = Symbols::default();
+        let mut anchor = "";
+
+        for token in tokens {
+            let kind = match token.name {
+                TokenType::Definition => SymbolKind::Definition,
+                TokenType::Anchor => {
+                    anchor = token.value;
+                    SymbolKind::Anchor
+                },
+                TokenType::State | TokenType::Quest => {
+                    symbols.state_anchors.insert(token.value, anchor);
+                    SymbolKind::State
+                },
+                _ => continue,
+            };
+            symbols.declarations.entry((kind, token.value)).or_insert(token.position);
+        }
+
+        symbols
+    }
+
+    fn find(&self, kind: SymbolKind, name: &str) -> Option<usize> {
+        self.declarations.get(&(kind, name)).copied()
+    }
+}
+
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    tokenizer::tokenize(text).map_or_else(|_| Vec::new(), |(tokens, _)| tokens)
+}
+
+/// The name at `offset` and what it could refer to
+fn reference_at<'a>(tokens: &[Token<'a>], offset: usize) -> Option<(&'a str, &'static [SymbolKind])> {
+    let index = tokens.partition_point(|token| token.position <= offset).checked_sub(1)?;
+    let token = &tokens[index];
+    if offset > token.position + token.value.len() {
+        return None;
+    }
+
+    match token.name {
+        TokenType::Connection | TokenType::Anchor => Some((token.value, &[SymbolKind::Anchor])),
+        TokenType::Definition => Some((token.value, &[SymbolKind::Definition])),
+        TokenType::State | TokenType::Quest => Some((token.value, &[SymbolKind::State])),
+        TokenType::Requirement => {
+            let keyword = token.value.split('=').next().unwrap_or_default();
+            if offset > token.position + keyword.len() {
+                return None;
+            }
+            Some((keyword, &[SymbolKind::Definition, SymbolKind::State]))
+        },
+        _ => None,
+    }
+}
+
+fn diagnostic(text: &str, err: ParseError) -> Diagnostic {
+    let ParseError { description, position } = err;
+
+    // position usize::MAX means the input ended unexpectedly
+    let (start, end) = if position == usize::MAX {
+        (text.len(), text.len())
+    } else {
+        let length = text[position..].find(char::is_whitespace).unwrap_or(text.len() - position);
+        (position, position + length)
+    };
+
+    Diagnostic {
+        range: Range::from_offsets(text, start, end),
+        severity: Severity::Error,
+        source: "seedgen",
+        message: description,
+    }
+}
+
+/// Reports the first error in the logic, if any
+pub fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    let result = tokenizer::tokenize(text).and_then(|(tokens, metadata)| parser::parse_areas(tokens, &metadata).map(|_| ()));
+    match result {
+        Ok(()) => Vec::new(),
+        Err(err) => vec![diagnostic(text, err)],
+    }
+}
+
+/// Finds where the anchor, state or requirement macro at `offset` is declared
+pub fn definition(text: &str, offset: usize) -> Option<Range> {
+    let tokens = tokens(text);
+    let symbols = Symbols::new(&tokens);
+    let (name, kinds) = reference_at(&tokens, offset)?;
+
+    let position = kinds.iter().find_map(|&kind| symbols.find(kind, name))?;
+    Some(Range::from_offsets(text, position, position + name.len()))
+}
+
+/// The lines of a requirement macro, from its declaration up to the next unindented line
+fn definition_body(text: &str, position: usize) -> String {
+    let line_start = text[..position].rfind('\n').map_or(0, |index| index + 1);
+    let mut lines = text[line_start..].lines();
+
+    let mut body = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        if !line.trim().is_empty() && !line.starts_with(char::is_whitespace) {
+            break;
+        }
+        body.push('\n');
+        body.push_str(line);
+    }
+
+    body.trim_end().to_string()
+}
+
+/// Describes the name at `offset`, showing what a requirement macro expands to
+pub fn hover(text: &str, offset: usize) -> Option<String> {
+    let tokens = tokens(text);
+    let symbols = Symbols::new(&tokens);
+    let (name, kinds) = reference_at(&tokens, offset)?;
+
+    kinds.iter().find_map(|&kind| {
+        let position = symbols.find(kind, name)?;
+        Some(match kind {
+            SymbolKind::Definition => format!("```\n{}\n```", definition_body(text, position)),
+            SymbolKind::Anchor => format!("anchor `{}`", name),
+            SymbolKind::State => format!("state `{}` in anchor `{}`", name, symbols.state_anchors.get(name).unwrap_or(&"?")),
+        })
+    })
+}
+
+/// Offers the names that fit at `offset`
+pub fn completion(text: &str, offset: usize) -> Vec<CompletionItem> {
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    let line = &text[line_start..offset];
+    let word_start = line.rfind(|char: char| char.is_whitespace() || char == ',' || char == ':').map_or(0, |index| index + 1);
+    let (before, word) = line.split_at(word_start);
+
+    if word.starts_with("Combat=") {
+        return parser::ENEMIES.iter().map(|&(enemy, _)| CompletionItem::new(enemy, CompletionKind::EnumMember, "Enemy")).collect();
+    }
+
+    let tokens = tokens(text);
+    let symbols = Symbols::new(&tokens);
+    let declared = |kind| {
+        let mut names = symbols.declarations.keys().filter(move |(other, _)| *other == kind).map(|(_, name)| *name).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    };
+
+    if before.trim_start() == "conn " {
+        return declared(SymbolKind::Anchor).into_iter().map(|name| CompletionItem::new(name, CompletionKind::Module, "Anchor")).collect();
+    }
+
+    parser::SKILLS.iter().map(|&(skill, _)| CompletionItem::new(skill, CompletionKind::EnumMember, "Skill"))
+        .chain(parser::SHARDS.iter().map(|&(shard, _)| CompletionItem::new(shard, CompletionKind::EnumMember, "Shard")))
+        .chain(declared(SymbolKind::Definition).into_iter().map(|name| CompletionItem::new(name, CompletionKind::Function, "Requirement")))
+        .chain(declared(SymbolKind::State).into_iter().map(|name| CompletionItem::new(name, CompletionKind::Variable, "State")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::Position;
+
+    const AREAS: &str = "\
+requirement Tough:
+  Health=5, Combat=2xMantis
+  Bash
+anchor Start at 1, 2:
+  state Start.Lever: free
+  conn End:
+    Tough OR Start.Lever
+anchor End:
+  conn Start: free
+";
+
+    #[test]
+    fn navigation() {
+        let offset = |line: usize, character: usize| crate::lsp::position_to_offset(AREAS, Position { line, character });
+
+        let range = definition(AREAS, offset(6, 5)).unwrap();
+        assert_eq!(range.start, Position { line: 0, character: 12 });
+        let range = definition(AREAS, offset(6, 14)).unwrap();
+        assert_eq!(range.start, Position { line: 4, character: 8 });
+        let range = definition(AREAS, offset(5, 8)).unwrap();
+        assert_eq!(range.start, Position { line: 7, character: 7 });
+        assert!(definition(AREAS, offset(1, 3)).is_none());
+
+        assert_eq!(hover(AREAS, offset(6, 5)).unwrap(), "```\nrequirement Tough:\n  Health=5, Combat=2xMantis\n  Bash\n```");
+        assert_eq!(hover(AREAS, offset(6, 14)).unwrap(), "state `Start.Lever` in anchor `Start`");
+
+        let items = completion(AREAS, offset(1, 20));
+        assert_eq!(items.len(), parser::ENEMIES.len());
+        let items = completion(AREAS, offset(6, 4));
+        assert!(items.iter().any(|item| item.label == "DoubleJump"));
+        assert!(items.iter().any(|item| item.label == "Tough"));
+        let items = completion(AREAS, offset(5, 7));
+        assert_eq!(items.iter().map(|item| item.label.as_str()).collect::<Vec<_>>(), ["End", "Start"]);
+    }
+
+    #[test]
+    fn diagnostics_and_names() {
+        assert!(diagnostics(AREAS).is_empty());
+        let broken = AREAS.replace("Bash", "Bsah");
+        let diagnostics = diagnostics(&broken);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, Range { start: Position { line: 2, character: 2 }, end: Position { line: 2, character: 6 } });
+
+        // Every name offered for completion has to be understood by the parser
+        let names = parser::SKILLS.iter().map(|(name, _)| name.to_string())
+            .chain(parser::SHARDS.iter().map(|(name, _)| name.to_string()))
+            .chain(parser::ENEMIES.iter().map(|(enemy, _)| format!("Combat={}", enemy)));
+        for name in names {
+            let areas = format!("anchor A:\n  conn B:\n    {}\n", name);
+            let (tokens, metadata) = tokenizer::tokenize(&areas).unwrap();
+            assert!(parser::parse_areas(tokens, &metadata).is_ok(), "{}", name);
+        }
+    }
+}
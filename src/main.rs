@@ -1,12 +1,10 @@
 use std::{
     fs,
-    str::FromStr,
     path::PathBuf,
-    convert::TryFrom,
     io::{self, Read},
-    time::Instant,
+    time::{Duration, Instant},
     collections::HashMap,
-    process, env,
+    process, env, thread, iter,
 };
 
 use structopt::StructOpt;
@@ -14,15 +12,18 @@ use bugsalot::debugger;
 
 use log::LevelFilter;
 
-use wotw_seedgen::{self, item, world, settings, util, languages::{headers::{self, parser::HeaderContext}, self}};
+use wotw_seedgen::{self, SeedBuilder, world, settings, stats, util, verify, error::Error, server::{Server, ServerOptions, GraphCache}, languages::{headers::{self, parser::HeaderContext}, logic::{lint, formatter}, self}};
 
-use item::{Item, Resource, Skill, Shard, Teleporter};
 use world::{
     World,
     graph::Graph,
+    export::{self, ExportFilter},
+    diff,
+    path,
+    player::Player,
 };
 use settings::{Settings, Spawn};
-use util::{Difficulty, Glitch, GoalMode, UberState};
+use util::{Difficulty, Glitch, GoalMode, Zone};
 
 #[derive(StructOpt, Debug)]
 /// Generate seeds for the Ori 2 randomizer.
@@ -55,6 +56,33 @@ enum SeedGenCommand {
         #[structopt(flatten)]
         args: ReachCheckArgs,
     },
+    /// Check whether a finished seed is beatable under its own settings
+    Verify {
+        #[structopt(flatten)]
+        args: VerifyArgs,
+    },
+    /// Generate many seeds in parallel and report statistics about their placements
+    Stats {
+        #[structopt(flatten)]
+        args: StatsArgs,
+    },
+    /// Run a language server for the logic file and headers over stdin and stdout
+    Lsp,
+    /// Keep the logic in memory and generate seeds over a local HTTP/JSON API
+    Serve {
+        #[structopt(flatten)]
+        args: ServeArgs,
+    },
+    /// Generate seeds and inspect headers through newline-delimited JSON-RPC over stdin and stdout
+    Rpc {
+        #[structopt(flatten)]
+        args: RpcArgs,
+    },
+    /// Inspect the logic
+    Logic {
+        #[structopt(subcommand)]
+        command: LogicCommand,
+    },
     /// Inspect the available headers
     Headers {
         /// headers to look at in detail
@@ -91,6 +119,9 @@ struct SeedArgs {
     /// skip validating the input files for a slight performance gain
     #[structopt(short, long)]
     trust: bool,
+    /// keep the compiled logic in this folder and reuse it while the input files, difficulty and glitches stay the same
+    #[structopt(parse(from_os_str), long)]
+    logic_cache: Option<PathBuf>,
     /// write the seed to stdout instead of a file
     #[structopt(long)]
     tostdout: bool,
@@ -100,6 +131,14 @@ struct SeedArgs {
     /// launch the seed after generating
     #[structopt(short, long)]
     launch: bool,
+    /// format of the spoiler
+    ///
+    /// one of text, json. The json spoiler is written next to the seed as <filename>.spoiler.json
+    #[structopt(long, default_value = "text")]
+    spoiler_format: String,
+    /// how many attempts to make at placing items before giving up
+    #[structopt(long)]
+    retries: Option<usize>,
     #[structopt(flatten)]
     settings: SeedSettings,
     /// inline headers
@@ -201,6 +240,242 @@ struct ReachCheckArgs {
     items: Vec<String>,
 }
 
+#[derive(StructOpt, Debug)]
+struct VerifyArgs {
+    /// the seed file to verify
+    #[structopt(parse(from_os_str))]
+    seed_file: PathBuf,
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", short, long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", short, long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", short, long)]
+    uber_states: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct StatsArgs {
+    /// how many seeds to generate
+    #[structopt(long, default_value = "100")]
+    count: usize,
+    /// how many seeds to generate at once, defaults to the number of available cores
+    #[structopt(long)]
+    threads: Option<usize>,
+    /// the seeds will be named <seed>_<index>; without this flag a random name is used
+    #[structopt(long)]
+    seed: Option<String>,
+    /// format of the report
+    ///
+    /// one of json, csv
+    #[structopt(long, default_value = "json")]
+    format: String,
+    /// write the report to this file instead of stdout
+    #[structopt(parse(from_os_str), long)]
+    output: Option<PathBuf>,
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", long)]
+    uber_states: PathBuf,
+    #[structopt(flatten)]
+    settings: SeedSettings,
+    /// inline headers
+    #[structopt(short, long = "inline")]
+    inline_headers: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ServeArgs {
+    /// the address to listen on
+    #[structopt(long, default_value = "127.0.0.1")]
+    host: String,
+    /// the port to listen on
+    #[structopt(long, default_value = "8080")]
+    port: u16,
+    /// seconds a request may take before it is answered with a timeout
+    #[structopt(long, default_value = "60")]
+    timeout: u64,
+    /// how many seeds may be generated at once
+    #[structopt(long, default_value = "4")]
+    max_concurrent: usize,
+    /// how many connections may be handled at once
+    #[structopt(long, default_value = "64")]
+    max_connections: usize,
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", long)]
+    uber_states: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct RpcArgs {
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", long)]
+    uber_states: PathBuf,
+    /// write stderr logs in json format
+    #[structopt(long)]
+    json_stderr: bool,
+}
+
+#[derive(StructOpt, Debug)]
+struct LogicArgs {
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", long)]
+    uber_states: PathBuf,
+    /// difficulty of execution you may be required to perform
+    ///
+    /// one of moki, gorlek, kii, unsafe
+    #[structopt(long, default_value = "moki")]
+    difficulty: String,
+    /// glitches you may be required to use
+    #[structopt(long)]
+    glitches: Vec<String>,
+}
+
+#[derive(StructOpt, Debug)]
+struct ExportArgs {
+    /// format of the export
+    ///
+    /// one of dot, json
+    #[structopt(long, default_value = "dot")]
+    format: String,
+    /// only export these regions, which are the part of an anchor name before the dot
+    #[structopt(long)]
+    region: Vec<String>,
+    /// only export these zones, like Marsh or Depths
+    #[structopt(long)]
+    zone: Vec<Zone>,
+    /// write the export to this file instead of stdout
+    #[structopt(parse(from_os_str), long)]
+    output: Option<PathBuf>,
+    #[structopt(flatten)]
+    logic: LogicArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct DiffArgs {
+    /// the old revision of the logic
+    #[structopt(parse(from_os_str))]
+    old: PathBuf,
+    /// the new revision of the logic
+    #[structopt(parse(from_os_str))]
+    new: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", long)]
+    locations: PathBuf,
+    /// the input file representing state namings
+    #[structopt(parse(from_os_str), default_value = "state_data.csv", long)]
+    uber_states: PathBuf,
+    /// difficulties to compare, defaults to all of them
+    ///
+    /// one of moki, gorlek, kii, unsafe
+    #[structopt(long)]
+    difficulty: Vec<String>,
+    /// glitches to compare, each is compared on its own in addition to no glitches
+    #[structopt(long)]
+    glitches: Vec<String>,
+    /// the anchor to start from
+    #[structopt(long, default_value = "MarshSpawn.Main")]
+    spawn: String,
+}
+
+#[derive(StructOpt, Debug)]
+struct PathArgs {
+    /// the anchor to start from
+    from: String,
+    /// the node to reach
+    to: String,
+    /// items the player already has, as item codes like in headers
+    ///
+    /// prefix with <amount>x to have an item multiple times, e.g. 3x0|5 for three Health Fragments
+    #[structopt(long)]
+    items: Vec<String>,
+    /// how many routes to print at most
+    #[structopt(long, default_value = "5")]
+    limit: usize,
+    #[structopt(flatten)]
+    logic: LogicArgs,
+}
+
+#[derive(StructOpt, Debug)]
+struct LintArgs {
+    /// the input file representing the logic
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", long)]
+    areas: PathBuf,
+    /// the input file representing pickup locations
+    #[structopt(parse(from_os_str), default_value = "loc_data.csv", long)]
+    locations: PathBuf,
+}
+
+#[derive(StructOpt, Debug)]
+struct FmtArgs {
+    /// the logic file to format
+    #[structopt(parse(from_os_str), default_value = "areas.wotw", long)]
+    areas: PathBuf,
+    /// only check whether the file is formatted instead of writing it
+    #[structopt(long)]
+    check: bool,
+}
+
+#[derive(StructOpt, Debug)]
+enum LogicCommand {
+    /// Export the logic graph for the given difficulty and glitches
+    Export {
+        #[structopt(flatten)]
+        args: ExportArgs,
+    },
+    /// Compare which items each pickup needs between two revisions of the logic
+    ///
+    /// Exits with code 1 if anything changed
+    Diff {
+        #[structopt(flatten)]
+        args: DiffArgs,
+    },
+    /// Find the minimal items needed to travel between two nodes and the paths taken
+    Path {
+        #[structopt(flatten)]
+        args: PathArgs,
+    },
+    /// Report unreachable anchors, unused states and macros, duplicate connections and shadowed requirements
+    ///
+    /// Exits with code 1 if anything was reported
+    Lint {
+        #[structopt(flatten)]
+        args: LintArgs,
+    },
+    /// Normalize indentation, spacing and the order of difficulty lines in the logic file
+    ///
+    /// With --check, exits with code 1 if the file isn't formatted
+    Fmt {
+        #[structopt(flatten)]
+        args: FmtArgs,
+    },
+}
+
 #[derive(StructOpt, Debug)]
 enum HeaderCommand {
     /// Check header compability
@@ -214,7 +489,25 @@ enum HeaderCommand {
         /// The file to parse
         #[structopt(parse(from_os_str))]
         path: PathBuf,
-    }
+    },
+    /// Show what the headers include and exclude and whether they conflict with each other
+    Graph {
+        /// The headers to start from
+        #[structopt(parse(from_os_str), required = true)]
+        headers: Vec<PathBuf>,
+    },
+    /// Install a header package archive into the headers folder
+    Install {
+        /// The .wotwrp archive to install
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+    /// Bundle a header package into a .wotwrp archive to share it
+    Pack {
+        /// The package directory or name of an installed package
+        #[structopt(parse(from_os_str))]
+        package: PathBuf,
+    },
 }
 
 fn read_header() -> String {
@@ -240,6 +533,48 @@ fn read_header() -> String {
     output
 }
 
+#[derive(Debug, PartialEq)]
+enum SpoilerFormat {
+    Text,
+    Json,
+}
+
+fn parse_spoiler_format(format: &str) -> Result<SpoilerFormat, String> {
+    match &format.to_lowercase()[..] {
+        "text" => Ok(SpoilerFormat::Text),
+        "json" => Ok(SpoilerFormat::Json),
+        _ => Err(format!("Unknown spoiler format {}", format)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ReportFormat {
+    Json,
+    Csv,
+}
+
+fn parse_report_format(format: &str) -> Result<ReportFormat, String> {
+    match &format.to_lowercase()[..] {
+        "json" => Ok(ReportFormat::Json),
+        "csv" => Ok(ReportFormat::Csv),
+        _ => Err(format!("Unknown report format {}", format)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ExportFormat {
+    Dot,
+    Json,
+}
+
+fn parse_export_format(format: &str) -> Result<ExportFormat, String> {
+    match &format.to_lowercase()[..] {
+        "dot" => Ok(ExportFormat::Dot),
+        "json" => Ok(ExportFormat::Json),
+        _ => Err(format!("Unknown export format {}", format)),
+    }
+}
+
 fn parse_difficulty(difficulty: &str) -> Result<Difficulty, String> {
     match &difficulty.to_lowercase()[..] {
         "moki" => Ok(Difficulty::Moki),
@@ -383,12 +718,14 @@ fn parse_settings(settings: SeedSettings) -> Result<Settings, String> {
         hard,
         header_list: header_paths,
         header_args,
+        ..Settings::default()
     })
 }
 
-fn write_seeds_to_files(seeds: &[String], spoilers: &[String], mut filename: String, mut folder: PathBuf, players: &[String], race: bool) -> Result<(), String> {
+fn write_seeds_to_files(seeds: &[String], spoilers: &[String], json_spoiler: Option<&str>, mut filename: String, mut folder: PathBuf, players: &[String], race: bool) -> Result<(), String> {
     let seed_count = seeds.len();
     let multiworld = seed_count > 1;
+    let seed_name = filename.clone();
 
     if multiworld {
         let mut multi_folder = folder.clone();
@@ -408,7 +745,7 @@ fn write_seeds_to_files(seeds: &[String], spoilers: &[String], mut filename: Str
         path.push(filename.clone());
         path.set_extension("wotwr");
 
-        let file = util::create_file(&path, seed, "", true)?;
+        let file = util::create_file(&path, seed, "", true).map_err(|err| err.to_string())?;
         log::info!("Wrote seed for {} to {}", player, file.display());
 
         if race {
@@ -417,7 +754,7 @@ fn write_seeds_to_files(seeds: &[String], spoilers: &[String], mut filename: Str
             let spoiler_filename = format!("{}.spoiler.wotwr", file.file_stem().unwrap().to_string_lossy());
             path.set_file_name(spoiler_filename);
 
-            let file = util::create_file(&path, spoiler, "", true)?;
+            let file = util::create_file(&path, spoiler, "", true).map_err(|err| err.to_string())?;
             log::info!("Wrote spoiler for {} to {}", player, file.display());
         }
 
@@ -431,6 +768,14 @@ fn write_seeds_to_files(seeds: &[String], spoilers: &[String], mut filename: Str
         }
     }
 
+    if let Some(json_spoiler) = json_spoiler {
+        let mut path = folder;
+        path.push(format!("{}.spoiler.json", seed_name));
+
+        let file = util::create_file(&path, json_spoiler, "", true).map_err(|err| err.to_string())?;
+        log::info!("Wrote json spoiler to {}", file.display());
+    }
+
     Ok(())
 }
 
@@ -446,9 +791,13 @@ fn generate_seeds(mut args: SeedArgs) -> Result<(), String> {
         |seed| Some(seed),
     ).cloned();
 
-    let settings = parse_settings(args.settings)?.apply_presets()?;
+    let spoiler_format = parse_spoiler_format(&args.spoiler_format)?;
+    let settings = parse_settings(args.settings)?.apply_presets().map_err(|err| err.to_string())?;
 
-    let graph = languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, !args.trust)?;
+    let graph = match &args.logic_cache {
+        Some(cache_folder) => languages::parse_logic_cached(&args.areas, &args.locations, &args.uber_states, &settings, !args.trust, cache_folder).map_err(|err| err.to_string())?,
+        None => languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, !args.trust).map_err(|err| err.to_string())?,
+    };
     log::info!("Parsed logic in {:?}", now.elapsed());
 
     let header = read_header();
@@ -459,23 +808,41 @@ fn generate_seeds(mut args: SeedArgs) -> Result<(), String> {
     let worlds = settings.worlds;
     let race = settings.race;
     let players = settings.players.clone();
-    let (seeds, spoilers) = wotw_seedgen::generate_seed(&graph, settings, &args.inline_headers, seed).map_err(|err| format!("Error generating seed: {}", err))?;
+    let generation_error = |err: Error| format!("Error generating seed: {}", err);
+    let mut builder = SeedBuilder::new(&graph, settings).map_err(generation_error)?.inline_headers(args.inline_headers);
+    if let Some(seed) = seed {
+        builder = builder.seed(seed);
+    }
+    if let Some(retries) = args.retries {
+        builder = builder.retries(retries);
+    }
+    let mut headers = builder.parse_headers().map_err(generation_error)?;
+    let generated = headers.generate().map_err(generation_error)?;
+    let seed_spoiler = generated.spoiler();
+    let (seeds, spoilers) = generated.render().map_err(generation_error)?;
     if worlds == 1 {
         log::info!("Generated seed in {:?}", now.elapsed());
     } else {
         log::info!("Generated {} worlds in {:?}", worlds, now.elapsed());
     }
 
+    let json_spoiler = if spoiler_format == SpoilerFormat::Json {
+        Some(seed_spoiler.to_json().map_err(|err| err.to_string())?)
+    } else { None };
+
     if args.tostdout {
         write_seeds_to_stdout(seeds);
-        if race {
+        if let Some(json_spoiler) = json_spoiler {
+            println!("\n======= SPOILERS =======\n");
+            println!("{}", json_spoiler);
+        } else if race {
             println!("\n======= SPOILERS =======\n");
             write_seeds_to_stdout(spoilers);
         }
     } else {
         let filename = args.filename.unwrap_or_else(|| String::from("seed"));
 
-        write_seeds_to_files(&seeds, &spoilers, filename, args.seed_folder, &players, race).unwrap_or_else(|err| log::error!("{}", err));
+        write_seeds_to_files(&seeds, &spoilers, json_spoiler.as_deref(), filename, args.seed_folder, &players, race).unwrap_or_else(|err| log::error!("{}", err));
     }
 
     if args.launch {
@@ -498,11 +865,11 @@ fn play_last_seed() -> Result<(), String> {
 
 fn create_preset(mut args: PresetArgs) -> Result<(), String> {
     let settings = parse_settings(args.settings)?;
-    let settings = settings.write()?;
+    let settings = settings.write().map_err(|err| err.to_string())?;
 
     args.name.set_extension("json");
 
-    let path = util::create_file(&args.name, &settings, "presets", false)?;
+    let path = util::create_file(&args.name, &settings, "presets", false).map_err(|err| err.to_string())?;
     log::info!("Created preset {}", path.display());
 
     Ok(())
@@ -513,82 +880,238 @@ fn reach_check(mut args: ReachCheckArgs) -> Result<String, String> {
     log::trace!("{}", command);
 
     args.seed_file.set_extension("wotwr");
-    let contents = util::read_file(&args.seed_file, "seeds")?;
+    let contents = util::read_file(&args.seed_file, "seeds").map_err(|err| err.to_string())?;
+
+    let settings = Settings::from_seed(&contents).map_err(|err| err.to_string())?;
+    let graph = &languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false).map_err(|err| err.to_string())?;
+
+    let state = verify::ReachState {
+        health: args.health,
+        energy: args.energy,
+        keystones: args.keystones,
+        ore: args.ore,
+        spirit_light: args.spirit_light,
+        items: args.items,
+    };
+    let reached = verify::reach_check(graph, &settings, &contents, &state)?;
 
-    let settings = Settings::from_seed(&contents)?;
-    let graph = &languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false)?;
-    let mut world = World::new(graph);
+    let identifiers = reached.iter()
+        .map(|&node| node.identifier())
+        .collect::<Vec<_>>()
+        .join(", ");
+    log::info!("reachable locations: {}", identifiers);
 
-    world.player.apply_settings(&settings);
+    let reached = reached.into_iter()
+        .filter_map(|node| node.uber_state())
+        .map(|uber_state| uber_state.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
 
-    world.player.inventory.grant(Item::Resource(Resource::Health), args.health / 5);
-    #[allow(clippy::cast_possible_truncation)]
-    world.player.inventory.grant(Item::Resource(Resource::Energy), util::float_to_int(args.energy * 2.0).map_err(|_| format!("Invalid energy parameter {}", args.energy))?);
-    world.player.inventory.grant(Item::Resource(Resource::Keystone), args.keystones);
-    world.player.inventory.grant(Item::Resource(Resource::Ore), args.ore);
-    world.player.inventory.grant(Item::SpiritLight(1), u16::try_from(args.spirit_light).unwrap_or(u16::MAX));  // Higher amounts of Spirit Light are irrelevant, just want to accept high values in case the player has that much);
+    Ok(reached)
+}
 
-    for item in args.items {
-        if let Some(skill) = item.strip_prefix("s:") {
-            let id: u8 = skill.parse().map_err(|_| format!("expected numeric skill id in {}", item))?;
-            world.player.inventory.grant(Item::Skill(Skill::try_from(id).map_err(|_| format!("{} is not a valid skill id", id))?), 1);
-        }
-        else if let Some(teleporter) = item.strip_prefix("t:") {
-            let id: u8 = teleporter.parse().map_err(|_| format!("expected numeric teleporter id in {}", item))?;
-            world.player.inventory.grant(Item::Teleporter(Teleporter::try_from(id).map_err(|_| format!("{} is not a valid teleporter id", id))?), 1);
-        }
-        else if let Some(shard) = item.strip_prefix("sh:") {
-            let id: u8 = shard.parse().map_err(|_| format!("expected numeric shard id in {}", item))?;
-            world.player.inventory.grant(Item::Shard(Shard::try_from(id).map_err(|_| format!("{} is not a valid shard id", id))?), 1);
-        }
-        else if let Some(world_event) = item.strip_prefix("w:") {
-            let id: u8 = world_event.parse().map_err(|_| format!("expected numeric world event id in {}", item))?;
-            if id != 0 { return Err(format!("{} is not a valid world event id (only 0 is)", id)); } 
-            world.player.inventory.grant(Item::Water, 1);
-        }
-        else if let Some(uber_state) = item.strip_prefix("u:") {
-            let uber_state = UberState::from_str(uber_state).map_err(|err| format!("failed to parse uber state in {}: {}", item, err))?;
+fn verify_seed(mut args: VerifyArgs) -> Result<bool, String> {
+    if args.seed_file.extension().is_none() {
+        args.seed_file.set_extension("wotwr");
+    }
+    let contents = util::read_file(&args.seed_file, "seeds").map_err(|err| err.to_string())?;
 
-            world.uber_states.insert(uber_state.identifier, uber_state.value);
-        }
-        else {
-            return Err(format!("items have to start with s:, t:, sh:, w: or u: (for skill, teleporter, shard, world event or uber state), except found {}", item));
+    let settings = Settings::from_seed(&contents).map_err(|err| err.to_string())?;
+    let graph = languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false).map_err(|err| err.to_string())?;
+
+    let verification = verify::verify(&graph, &settings, &contents).map_err(|err| err.to_string())?;
+
+    if !verification.out_of_logic.is_empty() {
+        let identifiers = verification.out_of_logic.iter().map(|node| node.identifier()).collect::<Vec<_>>();
+        log::info!("{} locations are out of logic on these settings: {}", identifiers.len(), identifiers.join(", "));
+    }
+
+    if let Some(softlock) = &verification.softlock {
+        let identifiers = verification.unreachable.iter().map(|node| node.identifier()).collect::<Vec<_>>();
+        log::error!("{} locations are unreachable: {}", identifiers.len(), identifiers.join(", "));
+        log::error!("Got stuck with {}", softlock.inventory);
+
+        if softlock.missing.is_empty() {
+            log::error!("No items would allow further progress");
+        } else {
+            let missing = softlock.missing.iter().map(|missing| format!("[{}]", missing)).collect::<Vec<_>>();
+            log::error!("Any of these would allow further progress: {}", missing.join(", "));
         }
+    } else {
+        log::info!("All locations are reachable");
+    }
+
+    Ok(verification.is_beatable())
+}
+
+fn generate_stats(args: StatsArgs) -> Result<(), String> {
+    let now = Instant::now();
+
+    let format = parse_report_format(&args.format)?;
+    let settings = parse_settings(args.settings)?.apply_presets().map_err(|err| err.to_string())?;
+    let graph = languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false).map_err(|err| err.to_string())?;
+
+    let threads = args.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+    let seed = args.seed.unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+    log::info!("Generating {} seeds named {}_<index> on {} threads", args.count, seed, threads);
+
+    let stats = stats::generate_stats(&graph, &settings, &args.inline_headers, &seed, args.count, threads).map_err(|err| err.to_string())?;
+    log::info!("Generated {} seeds in {:?}, {} failed", stats.seeds, now.elapsed(), stats.failures);
+
+    let report = match format {
+        ReportFormat::Json => stats.to_json().map_err(|err| err.to_string())?,
+        ReportFormat::Csv => stats.to_csv().map_err(|err| err.to_string())?,
+    };
+
+    match args.output {
+        Some(path) => { fs::write(&path, report).map_err(|err| format!("Failed to write report to {}: {}", path.display(), err))?; },
+        None => println!("{}", report),
     }
 
-    for line in contents.lines() {
-        if let Some(sets) = line.strip_prefix("// Sets: ") {
-            if !sets.is_empty() {
-                for identifier in sets.split(',').map(str::trim) {
-                    let node = world.graph.nodes.iter().find(|&node| node.identifier() == identifier).ok_or_else(|| format!("target {} not found", identifier))?;
-                    log::trace!("Setting state {}", identifier);
-                    world.sets.push(node.index());
+    Ok(())
+}
+
+fn load_logic(args: LogicArgs) -> Result<Graph, String> {
+    let settings = Settings {
+        difficulty: parse_difficulty(&args.difficulty)?,
+        glitches: parse_glitches(&args.glitches),
+        ..Settings::default()
+    };
+    let graph = languages::parse_logic(&args.areas, &args.locations, &args.uber_states, &settings, false).map_err(|err| err.to_string())?;
+    Ok(graph)
+}
+
+fn export_logic(args: ExportArgs) -> Result<(), String> {
+    let format = parse_export_format(&args.format)?;
+    let filter = ExportFilter {
+        regions: args.region,
+        zones: args.zone,
+    };
+    let graph = load_logic(args.logic)?;
+
+    let export = match format {
+        ExportFormat::Dot => export::to_dot(&graph, &filter),
+        ExportFormat::Json => export::to_json(&graph, &filter).map_err(|err| err.to_string())?,
+    };
+
+    match args.output {
+        Some(path) => { fs::write(&path, export).map_err(|err| format!("Failed to write export to {}: {}", path.display(), err))?; },
+        None => print!("{}", export),
+    }
+
+    Ok(())
+}
+
+fn serve(args: ServeArgs) -> Result<(), String> {
+    let options = ServerOptions {
+        areas: args.areas,
+        locations: args.locations,
+        uber_states: args.uber_states,
+        timeout: Duration::from_secs(args.timeout),
+        max_concurrent: args.max_concurrent,
+        max_connections: args.max_connections,
+    };
+    let server = Server::bind((&args.host[..], args.port), options).map_err(|err| err.to_string())?;
+    log::info!("Listening on http://{}", server.local_addr().map_err(|err| err.to_string())?);
+
+    server.run().map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+fn diff_logic(args: DiffArgs) -> Result<bool, String> {
+    let difficulties = if args.difficulty.is_empty() {
+        vec![Difficulty::Moki, Difficulty::Gorlek, Difficulty::Kii, Difficulty::Unsafe]
+    } else {
+        args.difficulty.iter().map(|difficulty| parse_difficulty(difficulty)).collect::<Result<_, _>>()?
+    };
+    let glitches = parse_glitches(&args.glitches);
+
+    let mut unchanged = true;
+    for difficulty in difficulties {
+        let glitch_sets = iter::once(Vec::new()).chain(glitches.iter().map(|&glitch| vec![glitch]));
+        for glitches in glitch_sets {
+            let label = iter::once(format!("{:?}", difficulty)).chain(glitches.iter().map(|glitch| format!("{:?}", glitch))).collect::<Vec<_>>().join(" + ");
+            let settings = Settings { difficulty, glitches, ..Settings::default() };
+
+            let old = languages::parse_logic(&args.old, &args.locations, &args.uber_states, &settings, false).map_err(|err| err.to_string())?;
+            let new = languages::parse_logic(&args.new, &args.locations, &args.uber_states, &settings, false).map_err(|err| err.to_string())?;
+            let changes = diff::diff(&old, &new, &settings, &args.spawn).map_err(|err| err.to_string())?;
+
+            if changes.is_empty() {
+                println!("{}: no changes", label);
+            } else {
+                unchanged = false;
+                println!("{}: {} pickups changed", label, changes.len());
+                for change in changes {
+                    println!("  {}", change);
                 }
             }
-
-            break;
         }
     }
 
-    let spawn = settings::read_spawn(&contents)?;
-    let spawn = world.graph.find_spawn(&spawn)?;
+    Ok(unchanged)
+}
 
-    let mut reached = world.graph.reached_locations(&world.player, spawn, &world.uber_states, &world.sets).expect("Invalid Reach Check");
-    reached.retain(|&node| node.can_place());
+fn find_path(args: PathArgs) -> Result<(), String> {
+    let mut player = Player {
+        difficulty: parse_difficulty(&args.logic.difficulty)?,
+        ..Player::default()
+    };
+    for item in &args.items {
+        let (amount, code) = match item.split_once('x') {
+            Some((amount, code)) if amount.parse::<u16>().is_ok() => (amount.parse().unwrap(), code),
+            _ => (1, &item[..]),
+        };
+        player.inventory.grant(headers::parser::parse_item(code).map_err(|err| err.to_string())?, amount);
+    }
 
-    let identifiers = reached.iter()
-        .map(|&node| node.identifier())
-        .collect::<Vec<_>>()
-        .join(", ");
-    log::info!("reachable locations: {}", identifiers);
+    let graph = load_logic(args.logic)?;
+    let path::Routes { routes, truncated } = path::find_routes(&graph, &player, &args.from, &args.to).map_err(|err| err.to_string())?;
 
-    let reached = reached.into_iter()
-        .filter_map(|node| node.uber_state())
-        .map(|uber_state| uber_state.to_string())
-        .collect::<Vec<_>>()
-        .join(", ");
+    if routes.is_empty() {
+        println!("{} can't be reached from {}", args.to, args.from);
+    }
+    for route in routes.iter().take(args.limit) {
+        println!("{}", route.describe(&graph));
+    }
+    if routes.len() > args.limit {
+        println!("and {} more", routes.len() - args.limit);
+    }
+    if truncated {
+        println!("Only the {} smallest routes were kept on the way, so some routes may be missing", path::MAX_ROUTES);
+    }
 
-    Ok(reached)
+    Ok(())
+}
+
+fn lint_logic(args: LintArgs) -> Result<bool, String> {
+    let lints = lint::lint(&args.areas, &args.locations).map_err(|err| err.to_string())?;
+
+    for lint in &lints {
+        println!("{}", lint);
+    }
+    if !lints.is_empty() {
+        println!("{} problems found", lints.len());
+    }
+
+    Ok(lints.is_empty())
+}
+
+fn format_logic(args: FmtArgs) -> Result<bool, String> {
+    let input = fs::read_to_string(&args.areas).map_err(|err| format!("Failed to read {}: {}", args.areas.display(), err))?;
+    let formatted = formatter::format(&input, &args.areas).map_err(|err| err.to_string())?;
+
+    if args.check {
+        if let Some(line) = formatter::first_difference(&input, &formatted) {
+            println!("{}:{}: not formatted", args.areas.display(), line);
+            return Ok(false);
+        }
+    } else if input != formatted {
+        fs::write(&args.areas, formatted).map_err(|err| format!("Failed to write {}: {}", args.areas.display(), err))?;
+        log::info!("Formatted {}", args.areas.display());
+    }
+
+    Ok(true)
 }
 
 fn compile_seed(mut path: PathBuf) -> Result<(), String> {
@@ -605,13 +1128,13 @@ fn compile_seed(mut path: PathBuf) -> Result<(), String> {
 
     let mut context = HeaderContext::default();
 
-    let header_block = headers::parser::parse_header(&path, &header, &mut world, &mut context, &HashMap::default(), &mut rng)?;
+    let header_block = headers::parser::parse_header(&path, &header, &mut world, &mut context, &HashMap::default(), &mut rng).map_err(|err| err.to_string())?;
     let flag_line = wotw_seedgen::write_flags(&settings, context.flags);
 
     let compiled = format!("{}{}", flag_line, header_block);
 
     path.set_extension("wotwr");
-    let path = util::create_file(path.file_name().unwrap(), &compiled, "target", false)?;
+    let path = util::create_file(path.file_name().unwrap(), &compiled, "target", false).map_err(|err| err.to_string())?;
     log::info!("Compiled to {}", path.display());
 
     Ok(())
@@ -655,6 +1178,22 @@ fn main() {
                 Some(HeaderCommand::Parse { path }) => {
                     compile_seed(path).unwrap_or_else(|err| log::error!("{}", err));
                 },
+                Some(HeaderCommand::Graph { headers }) => {
+                    match headers::graph(headers) {
+                        Ok(true) => {},
+                        Ok(false) => process::exit(1),
+                        Err(err) => {
+                            log::error!("{}", err);
+                            process::exit(2);
+                        },
+                    }
+                },
+                Some(HeaderCommand::Install { archive }) => {
+                    headers::install(archive).unwrap_or_else(|err| log::error!("{}", err));
+                },
+                Some(HeaderCommand::Pack { package }) => {
+                    headers::pack(package).unwrap_or_else(|err| log::error!("{}", err));
+                },
                 None => {
                     if headers.is_empty() {
                         headers::list().unwrap_or_else(|err| log::error!("{}", err));
@@ -672,5 +1211,69 @@ fn main() {
                 Err(err) => log::error!("{}", err),
             }
         },
+        SeedGenCommand::Verify { args } => {
+            wotw_seedgen::initialize_log(None, LevelFilter::Info, false).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            match verify_seed(args) {
+                Ok(true) => {},
+                Ok(false) => process::exit(1),
+                Err(err) => {
+                    log::error!("{}", err);
+                    process::exit(2);
+                },
+            }
+        },
+        SeedGenCommand::Stats { args } => {
+            wotw_seedgen::initialize_log(None, LevelFilter::Info, false).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            generate_stats(args).unwrap_or_else(|err| {
+                log::error!("{}", err);
+                process::exit(2);
+            });
+        },
+        SeedGenCommand::Lsp => {
+            wotw_seedgen::initialize_log(None, LevelFilter::Warn, false).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            wotw_seedgen::lsp::run(io::stdin().lock(), io::stdout().lock()).unwrap_or_else(|err| {
+                log::error!("{}", err);
+                process::exit(2);
+            });
+        },
+        SeedGenCommand::Serve { args } => {
+            wotw_seedgen::initialize_log(None, LevelFilter::Info, false).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            serve(args).unwrap_or_else(|err| {
+                log::error!("{}", err);
+                process::exit(2);
+            });
+        },
+        SeedGenCommand::Rpc { args } => {
+            wotw_seedgen::initialize_log(None, LevelFilter::Warn, args.json_stderr).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            let graphs = GraphCache::new(args.areas, args.locations, args.uber_states);
+            wotw_seedgen::rpc::run(io::stdin().lock(), io::stdout().lock(), &graphs).unwrap_or_else(|err| {
+                log::error!("{}", err);
+                process::exit(2);
+            });
+        },
+        SeedGenCommand::Logic { command } => {
+            wotw_seedgen::initialize_log(None, LevelFilter::Info, false).unwrap_or_else(|err| eprintln!("Failed to initialize log: {}", err));
+
+            let result = match command {
+                LogicCommand::Export { args } => export_logic(args).map(|_| true),
+                LogicCommand::Diff { args } => diff_logic(args),
+                LogicCommand::Path { args } => find_path(args).map(|_| true),
+                LogicCommand::Lint { args } => lint_logic(args),
+                LogicCommand::Fmt { args } => format_logic(args),
+            };
+            match result {
+                Ok(true) => {},
+                Ok(false) => process::exit(1),
+                Err(err) => {
+                    log::error!("{}", err);
+                    process::exit(2);
+                },
+            }
+        },
     }
 }
@@ -0,0 +1,278 @@
+use rustc_hash::FxHashMap;
+
+use crate::{
+    error::Error,
+    generator::{Placement, Sphere, SphereItem},
+    item::Item,
+    settings::Settings,
+    util::{
+        GoalMode, UberState,
+        constants::{GOAL_ANCHOR, WISP_STATES},
+    },
+    world::{World, graph::Node},
+};
+
+/// State of collecting items across all worlds, which can be snapshotted and resumed
+#[derive(Clone)]
+struct Simulation<'a> {
+    worlds: Vec<World<'a>>,
+    /// Maps the index of every reached node to the step in which it was reached
+    reached: Vec<FxHashMap<usize, usize>>,
+    finished: Vec<bool>,
+    dirty: Vec<bool>,
+}
+
+struct Goal {
+    finish: bool,
+    nodes: Vec<usize>,
+}
+
+type Collectibles = FxHashMap<(usize, usize), Vec<(usize, Item)>>;
+
+fn collectibles<'a>(spirit_light: &[(usize, usize, u16)], progressions: &[(usize, &SphereItem<'a>)]) -> Collectibles {
+    let mut collectibles = Collectibles::default();
+
+    for &(world_index, node_index, amount) in spirit_light {
+        collectibles.entry((world_index, node_index)).or_default().push((world_index, Item::SpiritLight(amount)));
+    }
+    for &(origin_world_index, progression) in progressions {
+        collectibles.entry((origin_world_index, progression.node.index())).or_default().push((progression.target_world_index, progression.item.clone()));
+    }
+
+    collectibles
+}
+
+impl<'a> Simulation<'a> {
+    fn new(worlds: &[World<'a>], collectibles: &Collectibles) -> Simulation<'a> {
+        let mut simulation = Simulation {
+            worlds: worlds.to_vec(),
+            reached: vec![FxHashMap::default(); worlds.len()],
+            finished: vec![false; worlds.len()],
+            dirty: vec![true; worlds.len()],
+        };
+
+        for world_index in 0..worlds.len() {
+            simulation.worlds[world_index].collect_preplacements(&UberState::spawn());
+            simulation.collect_location(world_index, usize::MAX, collectibles);
+        }
+
+        simulation
+    }
+
+    fn collect_location(&mut self, world_index: usize, node_index: usize, collectibles: &Collectibles) {
+        if let Some(items) = collectibles.get(&(world_index, node_index)) {
+            for (target_world_index, item) in items {
+                self.worlds[*target_world_index].grant_player(item.clone(), 1).unwrap_or_else(|err| log::error!("{}", err));
+                self.dirty[*target_world_index] = true;
+            }
+        }
+    }
+
+    fn is_reached(&self, world_index: usize, node: &Node) -> bool {
+        node.index() == usize::MAX || self.reached[world_index].contains_key(&node.index())
+    }
+
+    fn goals_met(&self, goals: &[Goal]) -> bool {
+        goals.iter().enumerate().all(|(world_index, goal)|
+            (!goal.finish || self.finished[world_index]) &&
+            goal.nodes.iter().all(|node| self.reached[world_index].contains_key(node))
+        )
+    }
+
+    /// Collects everything reachable until nothing new can be reached or, if given, the goals are met
+    ///
+    /// The state before each step gets appended to `snapshots`, so that later simulations can resume from there
+    fn run(mut self, spawns: &[&'a Node], collectibles: &Collectibles, goals: Option<&[Goal]>, snapshots: &mut Vec<Simulation<'a>>) -> Result<Simulation<'a>, Error> {
+        while self.dirty.iter().any(|&dirty| dirty) {
+            let step = snapshots.len();
+            snapshots.push(self.clone());
+
+            for (world_index, spawn) in spawns.iter().enumerate() {
+                if !self.dirty[world_index] { continue; }
+                self.dirty[world_index] = false;
+
+                let world = &self.worlds[world_index];
+                let (world_reached, visited) = world.graph.reached_and_visited(&world.player, spawn, &world.uber_states, &world.sets)?;
+                self.finished[world_index] = visited.iter().any(|node| node.identifier() == GOAL_ANCHOR);
+
+                for node in world_reached {
+                    if self.reached[world_index].contains_key(&node.index()) {
+                        continue;
+                    }
+                    self.reached[world_index].insert(node.index(), step);
+
+                    if let Some(uber_state) = node.uber_state() {
+                        let granted = self.worlds[world_index].collect_preplacements(uber_state);
+                        if granted || WISP_STATES.contains(&uber_state.identifier) {
+                            self.dirty[world_index] = true;
+                        }
+                    }
+                    self.collect_location(world_index, node.index(), collectibles);
+                }
+            }
+
+            if goals.map_or(false, |goals| self.goals_met(goals)) {
+                break;
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+fn goal_nodes<'a>(world: &World<'a>, placements: &[Placement<'a>], settings: &Settings) -> Vec<usize> {
+    let mut nodes = Vec::new();
+
+    for goalmode in &settings.goalmodes {
+        match goalmode {
+            GoalMode::Wisps => nodes.extend(world.graph.nodes.iter()
+                .filter(|node| node.uber_state().map_or(false, |uber_state| WISP_STATES.contains(&uber_state.identifier)))
+                .map(Node::index)),
+            GoalMode::Trees => nodes.extend(world.graph.nodes.iter()
+                .filter(|node| node.can_place() && node.uber_state().map_or(false, |uber_state| uber_state.identifier.uber_group == 0))
+                .map(Node::index)),
+            GoalMode::Quests => nodes.extend(world.graph.nodes.iter()
+                .filter(|node| matches!(node, Node::Quest(_)))
+                .map(Node::index)),
+            GoalMode::Relics(_) | GoalMode::RelicChance(_) => nodes.extend(placements.iter()
+                .filter(|placement| matches!(placement.item, Item::Relic(_)))
+                .filter_map(|placement| placement.node.map(Node::index))),
+        }
+    }
+
+    nodes.sort_unstable();
+    nodes.dedup();
+    nodes
+}
+
+/// Every placed Spirit Light as world index, node index and amount
+fn spirit_light(placements: &[Vec<Placement>]) -> Vec<(usize, usize, u16)> {
+    placements.iter().enumerate()
+        .flat_map(|(world_index, world_placements)| world_placements.iter()
+            .filter_map(move |placement| match (placement.node, &placement.item) {
+                (Some(node), Item::SpiritLight(amount)) => Some((world_index, node.index(), *amount)),
+                _ => None,
+            }))
+        .collect()
+}
+
+/// The goals of every world that can be met when collecting everything, as reached in `full_reach`
+fn goals<'a>(worlds: &[World<'a>], placements: &[Vec<Placement<'a>>], settings: &Settings, full_reach: &Simulation) -> Vec<Goal> {
+    worlds.iter().enumerate().map(|(world_index, world)| {
+        let mut nodes = goal_nodes(world, &placements[world_index], settings);
+        nodes.retain(|node| {
+            let reached = full_reach.reached[world_index].contains_key(node);
+            if !reached {
+                log::warn!("Goal location {} is not reachable in world {}", world.graph.nodes[*node], world_index + 1);
+            }
+            reached
+        });
+
+        let finish = full_reach.finished[world_index];
+        if !finish {
+            log::warn!("{} is not reachable in world {}", GOAL_ANCHOR, world_index + 1);
+        }
+
+        Goal { finish, nodes }
+    }).collect()
+}
+
+/// Finds the progression items that are needed to reach the goal
+///
+/// Starting from every progression item in the spheres, items get removed one at a time, latest first, and stay removed if the goal can still be reached without them
+/// Spirit Light is always considered collected
+pub fn minimal_playthrough<'a>(worlds: &[World<'a>], spawns: &[&'a Node], placements: &[Vec<Placement<'a>>], spheres: &[Vec<Sphere<'a>>], settings: &Settings) -> Result<Vec<Vec<SphereItem<'a>>>, Error> {
+    let spirit_light = spirit_light(placements);
+
+    let candidates = spheres.iter().enumerate()
+        .flat_map(|(world_index, world_spheres)| world_spheres.iter()
+            .flat_map(move |sphere| sphere.progressions.iter().map(move |progression| (world_index, progression))))
+        .collect::<Vec<_>>();
+
+    let all_collectibles = collectibles(&spirit_light, &candidates);
+    let full_reach = Simulation::new(worlds, &all_collectibles).run(spawns, &all_collectibles, None, &mut Vec::new())?;
+
+    let goals = goals(worlds, placements, settings, &full_reach);
+
+    let mut required = candidates;
+    let mut untested = required.len();
+    let mut snapshots = Vec::new();
+    let mut reach = Simulation::new(worlds, &all_collectibles).run(spawns, &all_collectibles, Some(&goals), &mut snapshots)?;
+    drop_unreached(&mut required, &mut untested, &reach);
+
+    while untested > 0 {
+        untested -= 1;
+
+        let removed = required.remove(untested);
+        let collectibles = collectibles(&spirit_light, &required);
+
+        // Everything up to the step that reached the removed item stays the same
+        let (start, mut trial_snapshots) = match reach.reached[removed.0].get(&removed.1.node.index()) {
+            Some(&step) => (snapshots[step].clone(), snapshots[..step].to_vec()),
+            None => (Simulation::new(worlds, &collectibles), Vec::new()),
+        };
+        let trial = start.run(spawns, &collectibles, Some(&goals), &mut trial_snapshots)?;
+
+        if trial.goals_met(&goals) {
+            reach = trial;
+            snapshots = trial_snapshots;
+            drop_unreached(&mut required, &mut untested, &reach);
+        } else {
+            required.insert(untested, removed);
+        }
+    }
+
+    let mut playthrough = vec![Vec::new(); worlds.len()];
+    for (world_index, progression) in required {
+        playthrough[world_index].push(progression.clone());
+    }
+
+    Ok(playthrough)
+}
+
+/// Items that weren't picked up before meeting the goal can't have been needed
+fn drop_unreached(required: &mut Vec<(usize, &SphereItem)>, untested: &mut usize, reach: &Simulation) {
+    let mut index = 0;
+    required.retain(|(world_index, progression)| {
+        index += 1;
+        index > *untested || reach.is_reached(*world_index, progression.node)
+    });
+    *untested -= index - required.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SeedBuilder, languages};
+
+    #[test]
+    fn playthrough() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let mut headers = SeedBuilder::new(&graph, settings.clone()).unwrap().seed(String::from("playthrough")).parse_headers().unwrap();
+        let generated = headers.generate().unwrap();
+
+        let worlds = [generated.headers.world().clone()];
+        let required = generated.playthrough.as_ref().unwrap()[0].iter().map(|progression| (0, progression)).collect::<Vec<_>>();
+        assert!(!required.is_empty());
+
+        let spirit_light = spirit_light(&generated.placements);
+        let all = generated.spheres[0].iter().flat_map(|sphere| sphere.progressions.iter().map(|progression| (0, progression))).collect::<Vec<_>>();
+        let all_collectibles = collectibles(&spirit_light, &all);
+        let full_reach = Simulation::new(&worlds, &all_collectibles).run(&generated.spawns, &all_collectibles, None, &mut Vec::new()).unwrap();
+        let goals = goals(&worlds, &generated.placements, &settings, &full_reach);
+        assert!(goals[0].finish);
+
+        let reaches_goal = |items: &[(usize, &SphereItem)]| {
+            let collectibles = collectibles(&spirit_light, items);
+            let reach = Simulation::new(&worlds, &collectibles).run(&generated.spawns, &collectibles, Some(&goals), &mut Vec::new()).unwrap();
+            reach.goals_met(&goals)
+        };
+        assert!(reaches_goal(&required));
+        for index in 0..required.len() {
+            let mut fewer = required.clone();
+            let (_, removed) = fewer.remove(index);
+            assert!(!reaches_goal(&fewer), "the goal can be reached without {}", removed.item);
+        }
+    }
+}
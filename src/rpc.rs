@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::{Value, json};
+
+use crate::{
+    error::Error,
+    languages::headers::{self, parser},
+    server::{self, GraphCache},
+    settings::{self, Settings},
+    util,
+    verify::{self, ReachState},
+};
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+/// The method was understood, but doing it failed
+const FAILED: i32 = -32000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HeaderParams {
+    /// May leave out the folder and extension
+    name: PathBuf,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReachCheckParams {
+    /// May leave out the folder and extension
+    seed_file: PathBuf,
+    #[serde(flatten)]
+    state: ReachState,
+}
+
+type Failure = (i32, String);
+
+fn failed(err: impl ToString) -> Failure {
+    (FAILED, err.to_string())
+}
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, Failure> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|err| (INVALID_PARAMS, err.to_string()))
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, Failure> {
+    serde_json::to_value(value).map_err(failed)
+}
+
+fn validate_header(params: HeaderParams) -> Result<Value, Failure> {
+    let mut path = params.name;
+    path.set_extension("wotwrh");
+    let contents = util::read_file(&path, "headers").map_err(failed)?;
+
+    Ok(match parser::validate_header(&path, &contents, &HashMap::new()) {
+        Ok((occupied, _)) => json!({ "valid": true, "occupied": occupied.iter().map(ToString::to_string).collect::<Vec<_>>() }),
+        Err(err) => json!({ "valid": false, "error": err.to_string() }),
+    })
+}
+
+fn reach_check(graphs: &GraphCache, params: ReachCheckParams) -> Result<Value, Failure> {
+    let mut seed_file = params.seed_file;
+    seed_file.set_extension("wotwr");
+    let contents = util::read_file(&seed_file, "seeds").map_err(failed)?;
+
+    let settings = Settings::from_seed(&contents).map_err(failed)?;
+    let graph = graphs.get(&settings).map_err(failed)?;
+    let reached = verify::reach_check(&graph, &settings, &contents, &params.state).map_err(failed)?;
+
+    let identifiers = reached.iter().map(|node| node.identifier()).collect::<Vec<_>>();
+    let uber_states = reached.iter().filter_map(|node| node.uber_state()).map(ToString::to_string).collect::<Vec<_>>();
+    Ok(json!({ "reached": uber_states, "identifiers": identifiers }))
+}
+
+fn call(graphs: &GraphCache, method: &str, params_value: Value) -> Result<Value, Failure> {
+    match method {
+        "generate" => {
+            let response = server::generate(graphs, params(params_value)?).map_err(|failure| {
+                let code = if failure.status == 400 { INVALID_PARAMS } else { FAILED };
+                (code, failure.message)
+            })?;
+            to_value(response)
+        },
+        "listHeaders" => to_value(headers::summaries().map_err(failed)?),
+        "inspectHeader" => to_value(headers::details(params::<HeaderParams>(params_value)?.name).map_err(failed)?),
+        "validateHeader" => validate_header(params(params_value)?),
+        "reachCheck" => reach_check(graphs, params(params_value)?),
+        "listPresets" => to_value(settings::list_presets().map_err(failed)?),
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method {}", method))),
+    }
+}
+
+/// Handles one message, returning the reply if it wasn't a notification
+fn handle(graphs: &GraphCache, line: &str) -> Option<Value> {
+    let mut message = match serde_json::from_str::<Value>(line) {
+        Ok(message) => message,
+        Err(err) => return Some(json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": err.to_string() } })),
+    };
+    let id = message.get("id").cloned();
+
+    let result = match message["method"].as_str() {
+        Some(method) => {
+            let method = method.to_string();
+            log::trace!("Received {}", method);
+            call(graphs, &method, message["params"].take())
+        },
+        None => Err((INVALID_REQUEST, String::from("Expected a method"))),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    })
+}
+
+/// Answers JSON-RPC 2.0 requests, one per line on `input` and `output`, until the input ends
+///
+/// The methods are `generate`, taking the same parameters as the server's `/seed` endpoint,
+/// `listHeaders`, `inspectHeader` and `validateHeader` with a header `name`,
+/// `reachCheck` with a `seedFile` and what the player has as in [`ReachState`], and `listPresets`
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W, graphs: &GraphCache) -> Result<(), Error> {
+    for line in input.lines() {
+        let line = line.map_err(|err| Error::Server(err.to_string()))?;
+        if line.trim().is_empty() { continue }
+
+        if let Some(reply) = handle(graphs, &line) {
+            writeln!(output, "{}", reply).map_err(|err| Error::Server(err.to_string()))?;
+            output.flush().map_err(|err| Error::Server(err.to_string()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn session() {
+        let graphs = GraphCache::new(PathBuf::from("areas.wotw"), PathBuf::from("loc_data.csv"), PathBuf::from("state_data.csv"));
+        let mut input = [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "listPresets" }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "listHeaders" }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "inspectHeader", "params": { "name": "bingo" } }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "validateHeader", "params": { "name": "bingo" } }),
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "unknown" }),
+            json!({ "jsonrpc": "2.0", "method": "listPresets" }),
+            json!({ "jsonrpc": "2.0", "id": 6, "method": "generate", "params": { "seed": "rpc test" } }),
+        ].iter().map(|message| format!("{}\n", message)).collect::<String>();
+        input += "{\n";
+
+        let mut output = Vec::new();
+        run(input.as_bytes(), &mut output, &graphs).unwrap();
+        let replies = String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str::<Value>(line).unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(replies.len(), 7);
+        assert!(replies[0]["result"].as_array().unwrap().contains(&json!("moki")));
+        assert!(replies[1]["result"].as_array().unwrap().iter().any(|header| header["name"] == "bingo"));
+        assert_eq!(replies[2]["result"]["name"], "bingo");
+        assert_eq!(replies[3]["result"]["valid"], true);
+        assert_eq!(replies[4]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(replies[5]["id"], 6);
+        assert_eq!(replies[6]["error"]["code"], PARSE_ERROR);
+
+        let seed = replies[5]["result"]["seeds"][0].as_str().unwrap();
+        let seed_file = std::env::temp_dir().join(format!("rpc_reach_check_{}.wotwr", std::process::id()));
+        fs::write(&seed_file, seed).unwrap();
+        let request = json!({ "jsonrpc": "2.0", "id": 7, "method": "reachCheck", "params": { "seedFile": seed_file, "health": 30, "energy": 3.0, "items": ["s:8"] } });
+        let reply = handle(&graphs, &request.to_string()).unwrap();
+        fs::remove_file(&seed_file).unwrap();
+
+        let reached = reply["result"]["identifiers"].as_array().unwrap();
+        assert!(!reached.is_empty(), "{}", reply);
+        assert!(reached.contains(&json!("MarshSpawn.FirstPickupEX")));
+    }
+}
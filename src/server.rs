@@ -0,0 +1,428 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+use serde::{Serialize, Deserialize};
+use serde_json::{Value, json};
+
+use crate::{
+    error::Error,
+    languages,
+    settings::Settings,
+    spoiler::SeedSpoiler,
+    util::Difficulty,
+    world::graph::Graph,
+};
+
+/// Requests with a larger body are turned away
+const MAX_BODY: usize = 1 << 20;
+/// Requests with a longer request or header line are turned away
+const MAX_LINE: usize = 8 << 10;
+/// Requests with more header lines are turned away
+const MAX_HEADERS: usize = 100;
+
+/// Where the server finds the logic and how much work it takes on
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub areas: PathBuf,
+    pub locations: PathBuf,
+    pub uber_states: PathBuf,
+    /// How long reading a request and generating its seed may take before the client gets a timeout
+    ///
+    /// A generation that times out keeps running in the background and keeps counting against `max_concurrent` until it finishes
+    pub timeout: Duration,
+    /// How many seeds may be generated at once, further requests are turned away
+    pub max_concurrent: usize,
+    /// How many connections may be handled at once, further connections are turned away
+    pub max_connections: usize,
+}
+impl Default for ServerOptions {
+    fn default() -> ServerOptions {
+        ServerOptions {
+            areas: PathBuf::from("areas.wotw"),
+            locations: PathBuf::from("loc_data.csv"),
+            uber_states: PathBuf::from("state_data.csv"),
+            timeout: Duration::from_secs(60),
+            max_concurrent: 4,
+            max_connections: 64,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SeedRequest {
+    /// Settings in any format `Settings::compability_parse` understands
+    #[serde(default)]
+    settings: Option<Value>,
+    /// Inline headers
+    #[serde(default)]
+    headers: Vec<String>,
+    seed: Option<String>,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SeedResponse {
+    slug: String,
+    seeds: Vec<String>,
+    spoilers: Vec<String>,
+    spoiler: SeedSpoiler,
+}
+
+/// Something to answer a request with other than a seed
+pub(crate) struct Failure {
+    pub status: u16,
+    pub message: String,
+}
+impl Failure {
+    fn new(status: u16, message: impl ToString) -> Failure {
+        Failure { status, message: message.to_string() }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "Internal Server Error",
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+fn read_error(err: io::Error) -> Failure {
+    match err.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => Failure::new(408, "Timed out reading the request"),
+        _ => Failure::new(400, err),
+    }
+}
+
+/// Reads a line of at most [`MAX_LINE`] bytes, returns the number of bytes read
+fn read_line<R: BufRead>(input: &mut R, line: &mut String) -> Result<usize, Failure> {
+    let read = Read::take(&mut *input, MAX_LINE as u64).read_line(line).map_err(read_error)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(Failure::new(431, format!("Request lines may be at most {} bytes", MAX_LINE)));
+    }
+    Ok(read)
+}
+
+fn read_request<R: BufRead>(input: &mut R) -> Result<Request, Failure> {
+    let mut request_line = String::new();
+    read_line(input, &mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(Failure::new(400, "Invalid request line")),
+    };
+
+    let mut length = 0;
+    for count in 0.. {
+        let mut header = String::new();
+        if read_line(input, &mut header)? == 0 { break }
+        let header = header.trim();
+        if header.is_empty() { break }
+        if count == MAX_HEADERS {
+            return Err(Failure::new(431, format!("Requests may have at most {} headers", MAX_HEADERS)));
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().map_err(|_| Failure::new(400, format!("Invalid header {}", header)))?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(Failure::new(413, format!("Requests may be at most {} bytes", MAX_BODY)));
+    }
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(read_error)?;
+    Ok(Request { method, path, body })
+}
+
+fn write_response<W: Write>(output: &mut W, status: u16, body: &str) -> io::Result<()> {
+    write!(output, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, reason(status), body.len(), body)?;
+    output.flush()
+}
+
+/// Gives back a generation or connection slot when dropped
+struct Slot(Arc<AtomicUsize>);
+impl Slot {
+    /// Takes a slot from `active` unless `max` are taken already
+    fn take(active: &Arc<AtomicUsize>, max: usize) -> Option<Slot> {
+        if active.fetch_add(1, Ordering::SeqCst) >= max {
+            active.fetch_sub(1, Ordering::SeqCst);
+            None
+        } else {
+            Some(Slot(Arc::clone(active)))
+        }
+    }
+}
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+type GraphKey = (Difficulty, Vec<String>);
+/// Filled in by whoever parses the logic first, others wait on its lock instead of the whole cache
+type GraphSlot = Arc<Mutex<Option<Arc<Graph>>>>;
+
+/// Keeps the logic parsed for every difficulty and set of glitches that came up so far
+pub struct GraphCache {
+    areas: PathBuf,
+    locations: PathBuf,
+    uber_states: PathBuf,
+    graphs: Mutex<FxHashMap<GraphKey, GraphSlot>>,
+}
+impl GraphCache {
+    pub fn new(areas: PathBuf, locations: PathBuf, uber_states: PathBuf) -> GraphCache {
+        GraphCache { areas, locations, uber_states, graphs: Mutex::default() }
+    }
+
+    fn slot(&self, settings: &Settings) -> GraphSlot {
+        let mut glitches = settings.glitches.iter().map(|glitch| format!("{:?}", glitch)).collect::<Vec<_>>();
+        glitches.sort_unstable();
+        glitches.dedup();
+
+        let mut graphs = self.graphs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        Arc::clone(graphs.entry((settings.difficulty, glitches)).or_default())
+    }
+
+    /// The graph for the difficulty and glitches in `settings`, parsing the logic the first time they come up
+    pub fn get(&self, settings: &Settings) -> Result<Arc<Graph>, Error> {
+        let slot = self.slot(settings);
+        let mut slot = slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(graph) = &*slot {
+            return Ok(Arc::clone(graph));
+        }
+
+        let now = Instant::now();
+        let graph = Arc::new(languages::parse_logic(&self.areas, &self.locations, &self.uber_states, settings, false)?);
+        log::info!("Parsed logic for {:?} {:?} in {:?}", settings.difficulty, settings.glitches, now.elapsed());
+        *slot = Some(Arc::clone(&graph));
+        Ok(graph)
+    }
+}
+
+struct State {
+    options: ServerOptions,
+    graphs: GraphCache,
+    active: Arc<AtomicUsize>,
+    connections: Arc<AtomicUsize>,
+}
+
+pub(crate) fn generate(graphs: &GraphCache, request: SeedRequest) -> Result<SeedResponse, Failure> {
+    let settings = match request.settings {
+        Some(settings) => Settings::compability_parse(&settings.to_string()).map_err(|err| Failure::new(400, err))?,
+        None => Settings::default(),
+    };
+    let settings = settings.apply_presets().map_err(|err| Failure::new(422, err))?;
+    let graph = graphs.get(&settings).map_err(|err| Failure::new(500, err))?;
+
+    let (seeds, spoilers, spoiler) = crate::generate_seed(&graph, settings, &request.headers, request.seed).map_err(|err| Failure::new(422, err))?;
+    Ok(SeedResponse { slug: spoiler.slug.clone(), seeds, spoilers, spoiler })
+}
+
+/// Generates on another thread so the request can time out
+fn generate_seed(state: &Arc<State>, body: &[u8]) -> Result<String, Failure> {
+    let request = serde_json::from_slice::<SeedRequest>(body).map_err(|err| Failure::new(400, err))?;
+    let slot = Slot::take(&state.active, state.options.max_concurrent).ok_or_else(|| Failure::new(503, "Too many seeds are being generated right now"))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let worker_state = Arc::clone(state);
+    thread::spawn(move || {
+        let result = generate(&worker_state.graphs, request).and_then(|response| {
+            serde_json::to_string(&response).map_err(|err| Failure::new(500, err))
+        });
+        drop(slot);
+        sender.send(result).unwrap_or_default();
+    });
+
+    receiver.recv_timeout(state.options.timeout).unwrap_or_else(|_| Err(Failure::new(504, "Generating the seed took too long")))
+}
+
+fn respond(state: &Arc<State>, request: &Request) -> Result<String, Failure> {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/health") => Ok(json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }).to_string()),
+        ("POST", "/seed") => generate_seed(state, &request.body),
+        (_, "/health") | (_, "/seed") => Err(Failure::new(405, format!("{} is not allowed on {}", request.method, request.path))),
+        _ => Err(Failure::new(404, format!("Nothing at {}", request.path))),
+    }
+}
+
+fn handle_connection(state: &Arc<State>, stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(state.options.timeout))?;
+    stream.set_write_timeout(Some(state.options.timeout))?;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut output = stream;
+
+    let (status, body, target) = match read_request(&mut input) {
+        Ok(request) => {
+            let target = format!("{} {}", request.method, request.path);
+            match respond(state, &request) {
+                Ok(body) => (200, body, target),
+                Err(failure) => (failure.status, json!({ "error": failure.message }).to_string(), target),
+            }
+        },
+        Err(failure) => (failure.status, json!({ "error": failure.message }).to_string(), String::from("invalid request")),
+    };
+    log::info!("{} -> {}", target, status);
+
+    write_response(&mut output, status, &body)
+}
+
+/// Answers with 503 without looking at the request, this happens on the accepting thread so it only blocks for up to a second
+fn turn_away(stream: &mut TcpStream) -> io::Result<()> {
+    stream.set_write_timeout(Some(Duration::from_millis(100)))?;
+    stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+    let body = json!({ "error": "Too many connections right now" }).to_string();
+    write_response(stream, 503, &body)?;
+    stream.shutdown(Shutdown::Write)?;
+
+    // closing with the request still unread would reset the connection before the client sees the answer
+    let deadline = Instant::now() + Duration::from_secs(1);
+    let mut buffer = [0; 1024];
+    while Instant::now() < deadline {
+        if stream.read(&mut buffer).unwrap_or(0) == 0 { break }
+    }
+    Ok(())
+}
+
+/// A server generating seeds over a local HTTP/JSON API
+///
+/// - `GET /health` answers with `{"status": "ok"}`
+/// - `POST /seed` takes `{"settings": <settings json>, "headers": [<inline headers>], "seed": <optional seed>}`
+///   and answers with the `slug`, the `seeds` and race `spoilers` of every world and the json `spoiler`
+///
+/// Failures are answered with an error status and `{"error": <message>}`
+pub struct Server {
+    listener: TcpListener,
+    state: Arc<State>,
+}
+impl Server {
+    pub fn bind<A: ToSocketAddrs>(address: A, options: ServerOptions) -> Result<Server, Error> {
+        let listener = TcpListener::bind(address).map_err(|err| Error::Server(format!("Failed to bind: {}", err)))?;
+        let graphs = GraphCache::new(options.areas.clone(), options.locations.clone(), options.uber_states.clone());
+        let state = Arc::new(State {
+            options,
+            graphs,
+            active: Arc::default(),
+            connections: Arc::default(),
+        });
+        Ok(Server { listener, state })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener.local_addr().map_err(|err| Error::Server(err.to_string()))
+    }
+
+    /// Handles connections until accepting them fails
+    ///
+    /// Every connection gets its own thread, connections past `max_connections` are answered right away with 503
+    pub fn run(self) -> Result<(), Error> {
+        for stream in self.listener.incoming() {
+            let mut stream = stream.map_err(|err| Error::Server(format!("Failed to accept a connection: {}", err)))?;
+            let slot = match Slot::take(&self.state.connections, self.state.options.max_connections) {
+                Some(slot) => slot,
+                None => {
+                    log::info!("Too many connections -> 503");
+                    turn_away(&mut stream).unwrap_or_else(|err| log::warn!("Failed to turn away a connection: {}", err));
+                    continue;
+                },
+            };
+
+            let state = Arc::clone(&self.state);
+            thread::spawn(move || {
+                handle_connection(&state, stream).unwrap_or_else(|err| log::warn!("Failed to answer a request: {}", err));
+                drop(slot);
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", method, path, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn start(options: ServerOptions) -> SocketAddr {
+        let server = Server::bind("127.0.0.1:0", options).unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run().unwrap());
+        address
+    }
+
+    #[test]
+    fn serve() {
+        let address = start(ServerOptions::default());
+
+        assert_eq!(request(address, "GET", "/health", "").0, 200);
+        assert_eq!(request(address, "GET", "/seed", "").0, 405);
+        assert_eq!(request(address, "GET", "/nothing", "").0, 404);
+        let (status, body) = request(address, "POST", "/seed", "{");
+        assert_eq!(status, 400);
+        assert!(body["error"].is_string());
+
+        let settings = Settings { difficulty: Difficulty::Gorlek, ..Settings::default() };
+        let body = json!({ "settings": settings, "seed": "server test" }).to_string();
+        let (status, response) = request(address, "POST", "/seed", &body);
+        assert_eq!(status, 200, "{}", response);
+        assert_eq!(response["seeds"].as_array().unwrap().len(), 1);
+        assert!(response["seeds"][0].as_str().unwrap().contains("// Config: "));
+        assert_eq!(response["slug"], response["spoiler"]["slug"]);
+        // the same seed with the same settings comes out the same
+        assert_eq!(request(address, "POST", "/seed", &body).1["seeds"], response["seeds"]);
+
+        let address = start(ServerOptions { max_concurrent: 0, ..ServerOptions::default() });
+        assert_eq!(request(address, "POST", "/seed", "{}").0, 503);
+        let address = start(ServerOptions { max_connections: 0, ..ServerOptions::default() });
+        assert_eq!(request(address, "GET", "/health", "").0, 503);
+        // holding the graph's lock makes generating wait as if the logic was still being parsed
+        let server = Server::bind("127.0.0.1:0", ServerOptions { timeout: Duration::from_millis(500), ..ServerOptions::default() }).unwrap();
+        let address = server.local_addr().unwrap();
+        let slot = server.state.graphs.slot(&Settings::default());
+        let parsing = slot.lock().unwrap();
+        thread::spawn(move || server.run().unwrap());
+        assert_eq!(request(address, "POST", "/seed", "{}").0, 504);
+        drop(parsing);
+
+        let mut long_header = format!("GET /health HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(read_request(&mut long_header.as_bytes()).err().unwrap().status, 431);
+        long_header = format!("GET /health HTTP/1.1\r\n{}\r\n", "X-Many: a\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(read_request(&mut long_header.as_bytes()).err().unwrap().status, 431);
+    }
+}
@@ -1,6 +1,9 @@
+pub mod rules;
+pub mod weights;
+
 use std::{
-    io,
-    path::PathBuf,
+    fs, io,
+    path::{Path, PathBuf},
     collections::hash_map::DefaultHasher,
     hash::Hasher,
 };
@@ -9,6 +12,11 @@ use rustc_hash::FxHashSet;
 
 use serde::{Serialize, Deserialize};
 
+use rules::PlacementRule;
+use weights::PlacementWeight;
+
+use crate::error::Error;
+use crate::languages::headers::package::Package;
 use crate::util::{
     self,
     Difficulty, Glitch, GoalMode,
@@ -45,6 +53,10 @@ pub struct Settings {
     pub hard: bool,
     pub header_list: Vec<PathBuf>,
     pub header_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub placement_weights: Vec<PlacementWeight>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub placement_rules: Vec<PlacementRule>,
 }
 impl Default for Settings {
     fn default() -> Settings {
@@ -63,22 +75,24 @@ impl Default for Settings {
             hard: false,
             header_list: Vec::default(),
             header_args: Vec::default(),
+            placement_weights: Vec::default(),
+            placement_rules: Vec::default(),
         }
     }
 }
 impl Settings {
-    pub fn compability_parse(json: &str) -> Result<Settings, String> {
+    pub fn compability_parse(json: &str) -> Result<Settings, Error> {
         serde_json::from_str(json).or_else(|err| {  // current
             read_pre_1_0_10(json).or_else(|_| {  // < 1.0.10
                 read_pre_1_0_0(json).or_else(|_| {  // < 1.0.0
                     read_pre_0_13_2(json).or_else(|_| {  // < 0.13.2
-                        read_pre_rustgen(json).map_err(|_| format!("Failed to read settings: {}", err))  // javagen
+                        read_pre_rustgen(json).map_err(|_| Error::ReadSettings(err.to_string()))  // javagen
                     })
                 })
             })
         })
     }
-    pub fn from_seed(seed: &str) -> Result<Settings, String> {
+    pub fn from_seed(seed: &str) -> Result<Settings, Error> {
         for line in seed.lines() {
             if let Some(config) = line.strip_prefix("// Config: ") {
                 let settings = Settings::compability_parse(config)?;
@@ -88,13 +102,18 @@ impl Settings {
         let settings = Settings::default();
         Ok(settings)
     }
-    pub fn from_preset(mut preset: PathBuf) -> Result<Settings, String> {
+    /// Reads a preset, or the settings selecting a header package if there is one by that name
+    pub fn from_preset(mut preset: PathBuf) -> Result<Settings, Error> {
+        if let Some(package) = Package::find(&preset)? {
+            return Ok(package.settings());
+        }
+
         preset.set_extension("json");
         let content = util::read_file(&preset, "presets")?;
         Settings::compability_parse(&content)
     }
-    pub fn write(&self) -> Result<String, String> {
-        serde_json::to_string(&self).map_err(|err| format!("Invalid Settings: {}", err))
+    pub fn write(&self) -> Result<String, Error> {
+        serde_json::to_string(&self).map_err(|err| Error::Serialize { what: "settings", message: err.to_string() })
     }
 
     fn merge(&mut self, other: Settings) {
@@ -113,6 +132,8 @@ impl Settings {
             hard: other_hard,
             header_list: mut other_header_list,
             header_args: mut other_header_args,
+            placement_weights: mut other_placement_weights,
+            placement_rules: mut other_placement_rules,
         } = other;
 
         if other_version.is_some() {
@@ -139,8 +160,10 @@ impl Settings {
         self.hard = self.hard || other_hard;
         self.header_list.append(&mut other_header_list);
         self.header_args.append(&mut other_header_args);
+        self.placement_weights.append(&mut other_placement_weights);
+        self.placement_rules.append(&mut other_placement_rules);
     }
-    pub fn apply_presets(mut self) -> Result<Settings, String> {
+    pub fn apply_presets(mut self) -> Result<Settings, Error> {
         let mut merged_settings = Settings::default();
 
         for preset in self.presets {
@@ -186,15 +209,32 @@ impl Settings {
     }
 }
 
-pub fn read_spawn(seed: &str) -> Result<String, String> {
-    for line in seed.lines() {
+pub fn read_spawn(seed: &str) -> Result<String, Error> {
+    for (index, line) in seed.lines().enumerate() {
         if let Some(spawn) = line.strip_prefix("Spawn:") {
-            return Ok(spawn[spawn.find("//").ok_or_else(|| String::from("Failed to read Spawn location"))? + 2..].trim().to_string());
+            return Ok(spawn[spawn.find("//").ok_or_else(|| Error::ParseSeed { line: Some(index + 1), message: String::from("Failed to read Spawn location") })? + 2..].trim().to_string());
         }
     }
     Ok(DEFAULT_SPAWN.to_string())
 }
 
+/// The names of the presets in the presets folder
+pub fn list_presets() -> Result<Vec<String>, Error> {
+    let folder = Path::new("presets");
+    let read_error = |err: io::Error| Error::ReadFile { path: folder.to_path_buf(), message: err.to_string() };
+
+    let mut presets = Vec::new();
+    for entry in fs::read_dir(folder).map_err(read_error)? {
+        let path = entry.map_err(read_error)?.path();
+        if path.extension().map_or(false, |extension| extension == "json") {
+            presets.push(path.file_stem().unwrap().to_string_lossy().into_owned());
+        }
+    }
+    presets.sort_unstable();
+
+    Ok(presets)
+}
+
 // Compability settings
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -336,6 +376,8 @@ fn read_pre_0_13_2(json: &str) -> Result<Settings, io::Error> {
         hard: old_settings.hard,
         header_list: old_settings.header_list,
         header_args: old_settings.header_args,
+        placement_weights: Vec::new(),
+        placement_rules: Vec::new(),
     })
 }
 
@@ -375,6 +417,8 @@ fn read_pre_1_0_0(json: &str) -> Result<Settings, io::Error> {
         hard: old_settings.hard,
         header_list: old_settings.header_list,
         header_args: old_settings.header_args,
+        placement_weights: Vec::new(),
+        placement_rules: Vec::new(),
     })
 }
 
@@ -430,6 +474,7 @@ fn read_pre_1_0_10(json: &str) -> Result<Settings, io::Error> {
         hard: old_settings.hard,
         header_list: old_settings.header_list,
         header_args: old_settings.header_args,
+        ..Settings::default()
     })
 }
 
@@ -0,0 +1,185 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    error::Error,
+    item::Item,
+    util::Difficulty,
+    world::{World, graph::Node},
+};
+
+use super::weights::{ItemFilter, LocationFilter};
+
+/// A restriction on where items may be placed
+///
+/// The rules only apply to items from the item pool, placements done by headers are left alone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "rule")]
+pub enum PlacementRule {
+    /// Items matching `item` are never placed on locations matching `location`
+    #[serde(rename_all = "camelCase")]
+    Never {
+        #[serde(default)]
+        item: ItemFilter,
+        location: LocationFilter,
+    },
+    /// Items matching `item` are only placed on locations matching `location`
+    #[serde(rename_all = "camelCase")]
+    Only {
+        #[serde(default)]
+        item: ItemFilter,
+        location: LocationFilter,
+    },
+    /// Locations matching `location` only hold Spirit Light and items that aren't needed for progression
+    #[serde(rename_all = "camelCase")]
+    JunkOnly {
+        location: LocationFilter,
+    },
+}
+impl PlacementRule {
+    fn location(&self) -> &LocationFilter {
+        match self {
+            PlacementRule::Never { location, .. } |
+            PlacementRule::Only { location, .. } |
+            PlacementRule::JunkOnly { location } => location,
+        }
+    }
+
+    /// Whether this rule allows placing `item` on `node`
+    pub fn allows(&self, item: &Item, node: &Node, difficulty: Difficulty) -> bool {
+        match self {
+            PlacementRule::Never { item: filter, location } => !(filter.matches(item) && location.matches(node)),
+            PlacementRule::Only { item: filter, location } => !filter.matches(item) || location.matches(node),
+            PlacementRule::JunkOnly { location } => !location.matches(node) || is_junk(item, difficulty),
+        }
+    }
+
+    /// Whether this rule limits where `item` may go
+    fn restricts(&self, item: &Item, difficulty: Difficulty) -> bool {
+        match self {
+            PlacementRule::Never { item: filter, .. } |
+            PlacementRule::Only { item: filter, .. } => filter.matches(item),
+            PlacementRule::JunkOnly { .. } => !is_junk(item, difficulty),
+        }
+    }
+
+    /// Whether this rule allows granting `item` on spawn, which isn't part of any zone
+    pub fn allows_spawn(&self, item: &Item) -> bool {
+        match self {
+            PlacementRule::Never { item: filter, location } => !(filter.matches(item) && location == &LocationFilter::Any),
+            PlacementRule::Only { item: filter, location } => !filter.matches(item) || location == &LocationFilter::Any,
+            PlacementRule::JunkOnly { .. } => true,
+        }
+    }
+}
+impl fmt::Display for PlacementRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementRule::Never { item, location } => write!(f, "never {} on {}", item, location),
+            PlacementRule::Only { item, location } => write!(f, "only {} on {}", item, location),
+            PlacementRule::JunkOnly { location } => write!(f, "junkOnly on {}", location),
+        }
+    }
+}
+
+fn is_junk(item: &Item, difficulty: Difficulty) -> bool {
+    matches!(item, Item::SpiritLight(_)) || !item.is_progression(difficulty)
+}
+
+/// Whether all of the rules allow placing `item` on `node`
+pub fn allows(rules: &[PlacementRule], item: &Item, node: &Node, difficulty: Difficulty) -> bool {
+    rules.iter().all(|rule| rule.allows(item, node, difficulty))
+}
+
+/// Checks that the rules leave enough locations for every item in the world's pool
+///
+/// This can't guarantee that generation will succeed, but catches rules that contradict each other or the logic
+pub fn check(rules: &[PlacementRule], world: &World, difficulty: Difficulty) -> Result<(), Error> {
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let slots = world.graph.nodes.iter()
+        .filter(|&node| node.can_place() && node.uber_state().map_or(true, |uber_state| !world.preplacements.contains_key(uber_state)))
+        .collect::<Vec<_>>();
+
+    for rule in rules {
+        if !slots.iter().any(|&node| rule.location().matches(node)) {
+            return Err(Error::PlacementRules(format!("{} doesn't match any free location", rule.location())));
+        }
+    }
+
+    let mut items = world.pool.inventory.items.iter()
+        .filter(|(item, _)| !matches!(item, Item::SpiritLight(_)))
+        .collect::<Vec<_>>();
+    items.sort_unstable_by_key(|(item, _)| item.code());
+    for (item, &amount) in &items {
+        let allowed = slots.iter().filter(|&&node| allows(rules, item, node, difficulty)).count();
+        if allowed < usize::from(amount) {
+            return Err(Error::PlacementRules(format!("{} {} have to be placed, but only {} locations allow it", amount, item, allowed)));
+        }
+    }
+
+    // Items restricted by the same rule compete for the same locations
+    for rule in rules {
+        let group = items.iter().filter(|(item, _)| rule.restricts(item, difficulty)).collect::<Vec<_>>();
+        let demand = group.iter().map(|(_, &amount)| usize::from(amount)).sum::<usize>();
+        let allowed = slots.iter()
+            .filter(|&&node| group.iter().any(|(item, _)| allows(rules, item, node, difficulty)))
+            .count();
+        if allowed < demand {
+            return Err(Error::PlacementRules(format!("{} items affected by the rule {} have to be placed, but only {} locations allow them", demand, rule, allowed)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        languages,
+        item::Skill,
+        settings::{Settings, weights::ItemCategory},
+        util::Zone,
+        world::pool::Pool,
+    };
+
+    #[test]
+    fn rules() {
+        let rules: Vec<PlacementRule> = serde_json::from_str(r#"[
+            { "rule": "never", "item": { "code": "2|8" }, "location": "shop" },
+            { "rule": "only", "item": { "category": "skill" }, "location": { "zone": "Depths" } },
+            { "rule": "junkOnly", "location": { "pattern": "MarshSpawn.*" } }
+        ]"#).unwrap();
+
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+        let find = |identifier| graph.nodes.iter().find(|node| node.identifier() == identifier).unwrap();
+        let depths = graph.nodes.iter().find(|node| node.can_place() && node.zone() == Some(Zone::Depths)).unwrap();
+        let marsh = find("MarshSpawn.RockHC");
+
+        let difficulty = settings.difficulty;
+        assert!(allows(&rules, &Item::Skill(Skill::Launch), depths, difficulty));
+        assert!(!allows(&rules, &Item::Skill(Skill::Launch), marsh, difficulty));
+        assert!(!allows(&rules, &Item::Water, marsh, difficulty));
+        assert!(allows(&rules, &Item::SpiritLight(100), marsh, difficulty));
+        assert!(!rules[1].allows_spawn(&Item::Skill(Skill::Launch)));
+
+        let mut world = World::new(&graph);
+        world.pool = Pool::preset();
+        assert!(check(&rules, &world, difficulty).is_ok());
+        let impossible = PlacementRule::Only { item: ItemFilter::Category(ItemCategory::Resource), location: LocationFilter::Location(String::from("MarshSpawn.RockHC")) };
+        assert!(check(&[impossible], &world, difficulty).is_err());
+        // Every skill on its own fits, but not all of them together
+        let crowded = PlacementRule::Only { item: ItemFilter::Category(ItemCategory::Skill), location: LocationFilter::Location(String::from("MarshSpawn.RockHC")) };
+        assert!(check(&[crowded], &world, difficulty).unwrap_err().to_string().contains("items affected by the rule only category skill on location MarshSpawn.RockHC"));
+        let unknown = PlacementRule::JunkOnly { location: LocationFilter::Pattern(String::from("Nowhere.*")) };
+        assert_eq!(check(&[unknown], &world, difficulty).unwrap_err().to_string(), "Invalid placement rules: pattern Nowhere.* doesn't match any free location");
+
+        assert_eq!(rules[0].to_string(), "never code 2|8 on shop");
+        assert_eq!(rules[1].to_string(), "only category skill on zone Depths");
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize, Deserializer, de::Error as _};
+
+use crate::{
+    item::Item,
+    util::{Zone, UberState},
+    world::graph::Node,
+};
+
+/// Makes items matching `item` more or less likely to be placed on locations matching `location`
+///
+/// When multiple weights match, their values are multiplied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementWeight {
+    #[serde(default)]
+    pub item: ItemFilter,
+    #[serde(default)]
+    pub location: LocationFilter,
+    /// A weight of 2 makes the placement twice as likely, a weight of 0 prevents it
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub weight: f64,
+}
+
+fn deserialize_weight<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let weight = f64::deserialize(deserializer)?;
+    if weight.is_finite() && weight >= 0.0 {
+        Ok(weight)
+    } else {
+        Err(D::Error::custom(format!("placement weights have to be finite and not negative, found {}", weight)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ItemFilter {
+    #[default]
+    Any,
+    /// An item by its code, for instance `2|8` for Launch
+    Code(String),
+    Category(ItemCategory),
+}
+impl ItemFilter {
+    pub fn matches(&self, item: &Item) -> bool {
+        match self {
+            ItemFilter::Any => true,
+            ItemFilter::Code(code) => &item.code() == code,
+            ItemFilter::Category(category) => ItemCategory::of(item) == Some(*category),
+        }
+    }
+}
+impl fmt::Display for ItemFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemFilter::Any => write!(f, "any item"),
+            ItemFilter::Code(code) => write!(f, "code {}", code),
+            ItemFilter::Category(category) => write!(f, "category {}", category),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ItemCategory {
+    SpiritLight,
+    Resource,
+    Skill,
+    Shard,
+    Teleporter,
+    Water,
+    Relic,
+    Bonus,
+    UberState,
+}
+impl ItemCategory {
+    pub fn of(item: &Item) -> Option<ItemCategory> {
+        match item {
+            Item::SpiritLight(_) => Some(ItemCategory::SpiritLight),
+            Item::Resource(_) => Some(ItemCategory::Resource),
+            Item::Skill(_) => Some(ItemCategory::Skill),
+            Item::Shard(_) => Some(ItemCategory::Shard),
+            Item::Teleporter(_) => Some(ItemCategory::Teleporter),
+            Item::Water => Some(ItemCategory::Water),
+            Item::Relic(_) => Some(ItemCategory::Relic),
+            Item::BonusItem(_) | Item::BonusUpgrade(_) => Some(ItemCategory::Bonus),
+            Item::UberState(_) => Some(ItemCategory::UberState),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for ItemCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ItemCategory::SpiritLight => "spiritLight",
+            ItemCategory::Resource => "resource",
+            ItemCategory::Skill => "skill",
+            ItemCategory::Shard => "shard",
+            ItemCategory::Teleporter => "teleporter",
+            ItemCategory::Water => "water",
+            ItemCategory::Relic => "relic",
+            ItemCategory::Bonus => "bonus",
+            ItemCategory::UberState => "uberState",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LocationFilter {
+    #[default]
+    Any,
+    Zone(Zone),
+    /// Locations that have to be bought, including Lupo's maps
+    Shop,
+    /// A location by its identifier, for instance `MarshSpawn.RegenTree`
+    Location(String),
+    /// Locations whose identifier matches a pattern where `*` stands for any text, for instance `LowerDepths.*`
+    Pattern(String),
+}
+impl LocationFilter {
+    pub fn matches(&self, node: &Node) -> bool {
+        match self {
+            LocationFilter::Any => true,
+            LocationFilter::Zone(zone) => node.zone() == Some(*zone),
+            LocationFilter::Shop => node.uber_state().map_or(false, UberState::is_purchasable),
+            LocationFilter::Location(identifier) => node.identifier() == identifier,
+            LocationFilter::Pattern(pattern) => matches_pattern(pattern, node.identifier()),
+        }
+    }
+}
+impl fmt::Display for LocationFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocationFilter::Any => write!(f, "any location"),
+            LocationFilter::Zone(zone) => write!(f, "zone {:?}", zone),
+            LocationFilter::Shop => write!(f, "shop"),
+            LocationFilter::Location(identifier) => write!(f, "location {}", identifier),
+            LocationFilter::Pattern(pattern) => write!(f, "pattern {}", pattern),
+        }
+    }
+}
+
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = match parts.pop() {
+        Some(last) => last,
+        None => return rest.is_empty(),
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Combined weight of placing `item` on `node`
+pub fn placement_weight(weights: &[PlacementWeight], item: &Item, node: &Node) -> f64 {
+    weights.iter()
+        .filter(|weight| weight.item.matches(item) && weight.location.matches(node))
+        .map(|weight| weight.weight)
+        .product()
+}
+
+/// Combined weight of placing `item` anywhere, only considering the weights that apply to every location
+pub fn item_weight(weights: &[PlacementWeight], item: &Item) -> f64 {
+    weights.iter()
+        .filter(|weight| weight.location == LocationFilter::Any && weight.item.matches(item))
+        .map(|weight| weight.weight)
+        .product()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        item::Skill,
+        util::{Position, UberState},
+        world::graph::Pickup,
+    };
+
+    #[test]
+    fn weights() {
+        let weights: Vec<PlacementWeight> = serde_json::from_str(r#"[
+            { "item": { "category": "skill" }, "location": { "zone": "Depths" }, "weight": 2 },
+            { "item": { "code": "2|8" }, "location": "shop", "weight": 0 },
+            { "item": { "code": "2|8" }, "weight": 0.5 }
+        ]"#).unwrap();
+
+        let pickup = |zone, uber_state: &str| Node::Pickup(Pickup {
+            identifier: String::from("Test.Pickup"),
+            zone,
+            index: 0,
+            uber_state: UberState::from_str(uber_state).unwrap(),
+            position: Position::default(),
+        });
+        let depths = pickup(Zone::Depths, "37858|12379");
+        let shop = pickup(Zone::Shop, "1|1074");
+
+        assert_eq!(placement_weight(&weights, &Item::Skill(Skill::DoubleJump), &depths), 2.0);
+        assert_eq!(placement_weight(&weights, &Item::Skill(Skill::Launch), &depths), 1.0);
+        assert_eq!(placement_weight(&weights, &Item::Skill(Skill::Launch), &shop), 0.0);
+        assert_eq!(placement_weight(&weights, &Item::SpiritLight(50), &shop), 1.0);
+        assert_eq!(item_weight(&weights, &Item::Skill(Skill::Launch)), 0.5);
+
+        assert!(serde_json::from_str::<PlacementWeight>(r#"{ "weight": -1 }"#).is_err());
+        assert!(serde_json::from_str::<PlacementWeight>(r#"{ "weight": 1e999 }"#).is_err());
+
+        assert!(matches_pattern("LowerDepths.*", "LowerDepths.RightKeystone"));
+        assert!(matches_pattern("*.*Keystone", "LowerDepths.RightKeystone"));
+        assert!(matches_pattern("*Keystone*", "LowerDepths.RightKeystone"));
+        assert!(!matches_pattern("*.Keystone", "LowerDepths.RightKeystone"));
+        assert!(!matches_pattern("Depths", "LowerDepths"));
+    }
+}
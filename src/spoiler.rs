@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    ItemDetails,
+    error::Error,
+    generator::{Placement, Sphere, SphereItem},
+    item::Item,
+    settings::Settings,
+    util::{Position, Zone, UberState},
+    world::graph::Node,
+};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpoilerPlacement {
+    pub node_identifier: Option<String>,
+    pub zone: Option<Zone>,
+    pub uber_state: UberState,
+    pub item_code: String,
+    pub item_name: String,
+    pub position: Option<Position>,
+}
+impl SpoilerPlacement {
+    pub fn new(placement: &Placement, custom_items: &HashMap<String, ItemDetails>) -> SpoilerPlacement {
+        SpoilerPlacement {
+            node_identifier: placement.node.map(|node| node.identifier().to_string()),
+            zone: placement.node.and_then(Node::zone),
+            uber_state: placement.uber_state.clone(),
+            item_code: placement.item.code(),
+            item_name: item_name(&placement.item, custom_items),
+            position: placement.node.and_then(Node::position).cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpoilerSpawn {
+    pub identifier: String,
+    pub position: Option<Position>,
+}
+impl SpoilerSpawn {
+    pub fn new(spawn: &Node) -> SpoilerSpawn {
+        SpoilerSpawn {
+            identifier: spawn.identifier().to_string(),
+            position: spawn.position().cloned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpoilerSphereItem {
+    pub node_identifier: String,
+    pub zone: Option<Zone>,
+    pub target_world_index: usize,
+    pub item_code: String,
+    pub item_name: String,
+}
+impl SpoilerSphereItem {
+    pub fn new(sphere_item: &SphereItem, custom_items: &HashMap<String, ItemDetails>) -> SpoilerSphereItem {
+        SpoilerSphereItem {
+            node_identifier: sphere_item.node.identifier().to_string(),
+            zone: sphere_item.node.zone(),
+            target_world_index: sphere_item.target_world_index,
+            item_code: sphere_item.item.code(),
+            item_name: item_name(&sphere_item.item, custom_items),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpoilerSphere {
+    pub locations: Vec<String>,
+    pub progressions: Vec<SpoilerSphereItem>,
+}
+impl SpoilerSphere {
+    pub fn new(sphere: &Sphere, custom_items: &HashMap<String, ItemDetails>) -> SpoilerSphere {
+        SpoilerSphere {
+            locations: sphere.locations.iter().map(|&node| node.identifier().to_string()).collect(),
+            progressions: sphere.progressions.iter().map(|progression| SpoilerSphereItem::new(progression, custom_items)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorldSpoiler {
+    pub player_name: String,
+    pub spawn: SpoilerSpawn,
+    pub placements: Vec<SpoilerPlacement>,
+    pub spheres: Vec<SpoilerSphere>,
+    /// `None` if no playthrough was calculated, like for multiworld seeds
+    pub playthrough: Option<Vec<SpoilerSphereItem>>,
+}
+
+/// Machine-readable spoiler of a generated seed, covering all of its worlds
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedSpoiler {
+    pub seed: String,
+    pub slug: String,
+    pub attempts: usize,
+    pub flags: Vec<String>,
+    pub sets: Vec<String>,
+    pub settings: Settings,
+    pub worlds: Vec<WorldSpoiler>,
+}
+impl SeedSpoiler {
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::Serialize { what: "spoiler", message: err.to_string() })
+    }
+}
+
+/// Returns the name of an item as it should be shown in spoilers, respecting custom names from headers
+pub fn item_name(item: &Item, custom_items: &HashMap<String, ItemDetails>) -> String {
+    custom_items.get(&item.code())
+        .and_then(|details| details.name.clone())
+        .unwrap_or_else(|| item.to_string())
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    SeedBuilder,
+    error::Error,
+    item::Item,
+    settings::Settings,
+    world::graph::Graph,
+};
+
+/// What a single successfully generated seed contributes to the statistics
+struct Sample {
+    time: Duration,
+    attempts: usize,
+    sphere_depths: Vec<usize>,
+    spirit_light: u64,
+    slots: usize,
+    skills: Vec<(String, String)>,
+}
+
+/// Placement statistics aggregated over many seeds generated with the same settings
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub seeds: usize,
+    pub failures: usize,
+    pub failure_rate: f64,
+    pub average_attempts: f64,
+    /// Average amount of spheres per world
+    pub average_sphere_depth: f64,
+    /// Average Spirit Light per location, counting every location including the ones without Spirit Light
+    pub spirit_light_per_slot: f64,
+    pub average_time_ms: f64,
+    pub max_time_ms: f64,
+    /// How often each skill was placed in each zone
+    pub skill_zones: BTreeMap<String, BTreeMap<String, usize>>,
+}
+impl Stats {
+    /// Writes the statistics as rows of `statistic,skill,zone,value`
+    pub fn to_csv(&self) -> Result<String, Error> {
+        let serialize_error = |err: csv::Error| Error::Serialize { what: "stats", message: err.to_string() };
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        writer.write_record(["statistic", "skill", "zone", "value"]).map_err(serialize_error)?;
+        let summary = [
+            ("seeds", self.seeds as f64),
+            ("failures", self.failures as f64),
+            ("failureRate", self.failure_rate),
+            ("averageAttempts", self.average_attempts),
+            ("averageSphereDepth", self.average_sphere_depth),
+            ("spiritLightPerSlot", self.spirit_light_per_slot),
+            ("averageTimeMs", self.average_time_ms),
+            ("maxTimeMs", self.max_time_ms),
+        ];
+        for (statistic, value) in &summary {
+            writer.write_record([statistic, "", "", &value.to_string()]).map_err(serialize_error)?;
+        }
+        for (skill, zones) in &self.skill_zones {
+            for (zone, count) in zones {
+                writer.write_record(["skillZone", skill, zone, &count.to_string()]).map_err(serialize_error)?;
+            }
+        }
+
+        let bytes = writer.into_inner().map_err(|err| Error::Serialize { what: "stats", message: err.to_string() })?;
+        String::from_utf8(bytes).map_err(|err| Error::Serialize { what: "stats", message: err.to_string() })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::Serialize { what: "stats", message: err.to_string() })
+    }
+
+    fn collect(count: usize, samples: &[Sample]) -> Stats {
+        let mut stats = Stats {
+            seeds: count,
+            failures: count - samples.len(),
+            ..Stats::default()
+        };
+        if count > 0 {
+            stats.failure_rate = stats.failures as f64 / count as f64;
+        }
+        if samples.is_empty() {
+            return stats;
+        }
+
+        let successes = samples.len() as f64;
+        stats.average_attempts = samples.iter().map(|sample| sample.attempts).sum::<usize>() as f64 / successes;
+
+        let worlds = samples.iter().map(|sample| sample.sphere_depths.len()).sum::<usize>().max(1);
+        stats.average_sphere_depth = samples.iter().flat_map(|sample| &sample.sphere_depths).sum::<usize>() as f64 / worlds as f64;
+
+        let slots = samples.iter().map(|sample| sample.slots).sum::<usize>().max(1);
+        stats.spirit_light_per_slot = samples.iter().map(|sample| sample.spirit_light).sum::<u64>() as f64 / slots as f64;
+
+        stats.average_time_ms = samples.iter().map(|sample| sample.time.as_secs_f64()).sum::<f64>() * 1000.0 / successes;
+        stats.max_time_ms = samples.iter().map(|sample| sample.time.as_secs_f64() * 1000.0).fold(0.0, f64::max);
+
+        for (skill, zone) in samples.iter().flat_map(|sample| &sample.skills) {
+            *stats.skill_zones.entry(skill.clone()).or_default().entry(zone.clone()).or_default() += 1;
+        }
+
+        stats
+    }
+}
+
+fn sample(graph: &Graph, settings: &Settings, inline_headers: &[String], seed: String) -> Result<Option<Sample>, Error> {
+    let now = Instant::now();
+
+    let mut headers = SeedBuilder::new(graph, settings.clone())?
+        .seed(seed)
+        .inline_headers(inline_headers.to_vec())
+        .parse_headers()?;
+    let generated = match headers.generate_without_playthrough() {
+        Ok(generated) => generated,
+        Err(err @ Error::GenerationFailed { .. }) => {
+            log::warn!("{}", err);
+            return Ok(None);
+        },
+        Err(err) => return Err(err),
+    };
+    let time = now.elapsed();
+
+    let mut spirit_light = 0;
+    let mut slots = 0;
+    let mut skills = Vec::new();
+    for placement in generated.placements.iter().flatten() {
+        let node = match placement.node {
+            Some(node) => node,
+            None => continue,
+        };
+        slots += 1;
+
+        match &placement.item {
+            Item::SpiritLight(amount) => spirit_light += u64::from(*amount),
+            Item::Skill(skill) => {
+                let zone = node.zone().map_or_else(|| String::from("Unknown"), |zone| zone.to_string());
+                skills.push((skill.to_string(), zone));
+            },
+            _ => {},
+        }
+    }
+
+    Ok(Some(Sample {
+        time,
+        attempts: generated.attempts,
+        sphere_depths: generated.spheres.iter().map(Vec::len).collect(),
+        spirit_light,
+        slots,
+        skills,
+    }))
+}
+
+/// Generates `count` seeds named `<seed>_<index>` on all of the given threads and aggregates statistics about their placements
+///
+/// Seeds that fail to generate after all retries count as failures, any other error aborts
+pub fn generate_stats(graph: &Graph, settings: &Settings, inline_headers: &[String], seed: &str, count: usize, threads: usize) -> Result<Stats, Error> {
+    let settings = settings.clone().apply_presets()?;
+    let next_index = AtomicUsize::new(0);
+
+    let mut results = thread::scope(|scope| {
+        let workers = (0..threads.max(1)).map(|_| scope.spawn(|| {
+            let mut results = Vec::new();
+            loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                if index >= count { break results; }
+
+                let result = sample(graph, &settings, inline_headers, format!("{}_{}", seed, index));
+                let failed = result.is_err();
+                results.push((index, result));
+                if failed {
+                    // Make the other threads stop as well
+                    next_index.store(count, Ordering::Relaxed);
+                }
+            }
+        })).collect::<Vec<_>>();
+
+        workers.into_iter()
+            .flat_map(|worker| worker.join().expect("stats worker panicked"))
+            .collect::<Vec<_>>()
+    });
+
+    results.sort_unstable_by_key(|(index, _)| *index);
+    let mut samples = Vec::with_capacity(count);
+    for (_, result) in results {
+        if let Some(sample) = result? {
+            samples.push(sample);
+        }
+    }
+
+    Ok(Stats::collect(count, &samples))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages;
+
+    #[test]
+    fn parallel_stats() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let stats = generate_stats(&graph, &settings, &[], "stats", 2, 2).unwrap();
+        assert_eq!(stats.seeds, 2);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(stats.failure_rate, 0.0);
+        assert!(stats.average_attempts >= 1.0);
+        assert!(stats.average_sphere_depth > 0.0);
+        assert!(stats.spirit_light_per_slot > 0.0 && stats.spirit_light_per_slot < 200.0);
+        // every skill is placed once per seed
+        assert!(!stats.skill_zones.is_empty());
+        for (skill, zones) in &stats.skill_zones {
+            assert_eq!(zones.values().sum::<usize>(), 2, "{} wasn't placed once per seed", skill);
+        }
+
+        let csv = stats.to_csv().unwrap();
+        assert!(csv.starts_with("statistic,skill,zone,value\nseeds,,,2\n"));
+    }
+}
@@ -13,7 +13,9 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Serializer, Deserialize, Deserializer, ser::SerializeStruct};
+
+use crate::error::Error;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum Difficulty {
@@ -101,7 +103,7 @@ macro_rules! auto_display {
     };
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromPrimitive)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, FromPrimitive, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum Zone {
     Marsh = 0,
@@ -122,6 +124,30 @@ pub enum Zone {
     Void = 13,
 }
 auto_display!(Zone);
+impl std::str::FromStr for Zone {
+    type Err = String;
+
+    fn from_str(zone: &str) -> Result<Zone, String> {
+        match &zone.to_lowercase()[..] {
+            "marsh" => Ok(Zone::Marsh),
+            "hollow" => Ok(Zone::Hollow),
+            "glades" => Ok(Zone::Glades),
+            "wellspring" => Ok(Zone::Wellspring),
+            "woods" => Ok(Zone::Woods),
+            "reach" => Ok(Zone::Reach),
+            "depths" => Ok(Zone::Depths),
+            "pools" => Ok(Zone::Pools),
+            "wastes" => Ok(Zone::Wastes),
+            "ruins" => Ok(Zone::Ruins),
+            "willow" => Ok(Zone::Willow),
+            "burrows" => Ok(Zone::Burrows),
+            "spawn" => Ok(Zone::Spawn),
+            "shop" => Ok(Zone::Shop),
+            "void" => Ok(Zone::Void),
+            _ => Err(format!("Unknown zone {}", zone)),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Icon {
@@ -147,7 +173,7 @@ impl fmt::Display for Icon {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum Enemy {
     Mantis,
     Slug,
@@ -214,14 +240,14 @@ impl Enemy {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum RefillType {
     Full,
     Checkpoint,
     Health(f32),
     Energy(f32),
 }
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum NodeType {
     Anchor,
     Pickup,
@@ -240,6 +266,27 @@ impl Position {
     }
 }
 
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut position = serializer.serialize_struct("Position", 2)?;
+        position.serialize_field("x", &self.x.into_inner())?;
+        position.serialize_field("y", &self.y.into_inner())?;
+        position.end()
+    }
+}
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Coordinates {
+            x: f32,
+            y: f32,
+        }
+
+        let Coordinates { x, y } = Coordinates::deserialize(deserializer)?;
+        Ok(Position { x: R32::from(x), y: R32::from(y) })
+    }
+}
+
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}, {}", self.x, self.y)
@@ -248,8 +295,8 @@ impl fmt::Display for Position {
 
 fn in_folder<P1: AsRef<Path>, P2: AsRef<Path>>(file: P1, folder: P2) -> Result<Option<PathBuf>, String> {
     let file = file.as_ref();
-    let file_name = file.file_name().ok_or_else(|| format!("Invalid file path {}", file.display()))?;
-    let file_location = file.parent().ok_or_else(|| format!("Invalid file path {}", file.display()))?;
+    let file_name = file.file_name().ok_or_else(|| String::from("Invalid file path"))?;
+    let file_location = file.parent().ok_or_else(|| String::from("Invalid file path"))?;
 
     if let Some(file_folder) = file_location.file_name() {
         if folder.as_ref() == file_folder {
@@ -264,11 +311,12 @@ fn in_folder<P1: AsRef<Path>, P2: AsRef<Path>>(file: P1, folder: P2) -> Result<O
     Ok(Some(in_folder))
 }
 
-pub fn read_file<P1: AsRef<Path>, P2: AsRef<Path>>(file: P1, default_folder: P2) -> Result<String, String> {
-    in_folder(&file, default_folder)?.map_or_else(
-        || fs::read_to_string(&file).map_err(|err| format!("Failed to read file {}: {}", file.as_ref().display(), err)),
+pub fn read_file<P1: AsRef<Path>, P2: AsRef<Path>>(file: P1, default_folder: P2) -> Result<String, Error> {
+    let read_error = |message: String| Error::ReadFile { path: file.as_ref().to_path_buf(), message };
+    in_folder(&file, default_folder).map_err(read_error)?.map_or_else(
+        || fs::read_to_string(&file).map_err(|err| read_error(err.to_string())),
         |in_folder| fs::read_to_string(in_folder).or_else(|_| {
-            fs::read_to_string(&file).map_err(|err| format!("Failed to read file {}: {}", file.as_ref().display(), err))
+            fs::read_to_string(&file).map_err(|err| read_error(err.to_string()))
         })
     )
 }
@@ -300,11 +348,12 @@ fn create_in_folder<P: AsRef<Path>>(file: P, contents: &str, create_new: bool) -
             }
     }
 }
-pub fn create_file<P1: AsRef<Path>, P2: AsRef<Path>>(file: P1, contents: &str, default_folder: P2, create_new: bool) -> Result<PathBuf, String> {
-    in_folder(&file, default_folder)?.map_or_else(
-        || create_in_folder(&file, contents, create_new).map_err(|err| format!("Failed to create {}: {}", file.as_ref().display(), err)),
+pub fn create_file<P1: AsRef<Path>, P2: AsRef<Path>>(file: P1, contents: &str, default_folder: P2, create_new: bool) -> Result<PathBuf, Error> {
+    let create_error = |message: String| Error::CreateFile { path: file.as_ref().to_path_buf(), message };
+    in_folder(&file, default_folder).map_err(create_error)?.map_or_else(
+        || create_in_folder(&file, contents, create_new).map_err(|err| create_error(err.to_string())),
         |in_folder| create_in_folder(&in_folder, contents, create_new).or_else(|_| {
-            create_in_folder(&file, contents, create_new).map_err(|err| format!("Failed to create {}: {}", file.as_ref().display(), err))
+            create_in_folder(&file, contents, create_new).map_err(|err| create_error(err.to_string()))
         })
     )
 }
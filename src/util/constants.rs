@@ -5,6 +5,7 @@ use ansi_term::Colour;
 
 pub const DEFAULT_SPAWN: &str = "MarshSpawn.Main";
 pub const TP_ANCHOR: &str = "Teleporters";
+pub const GOAL_ANCHOR: &str = "WillowsEnd.Upper";  // Reaching this anchor allows to finish the game
 pub const MOKI_SPAWNS: &[&str] = &[
     "MarshSpawn.Main",
     "HowlsDen.Teleporter",
@@ -83,7 +84,7 @@ pub const WISP_STATES: &[UberIdentifier] = &[
 
 pub const RESERVE_SLOTS: usize = 1;  // how many slots to reserve after random placements for the next iteration
 pub const PLACEHOLDER_SLOTS: usize = 25;  // how many slots to keep as placeholders for bigger progressions
-pub const RETRIES: u16 = 10;  // How many retries to allow when generating a seed
+pub const RETRIES: usize = 10;  // How many attempts to allow when generating a seed, unless configured otherwise
 pub const RANDOM_PROGRESSION: f64 = 0.4;  // How likely to choose a progression item as random placement
 
 pub const HEADER_INDENT: usize = 24;  // Which column to align header descriptions on
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
 use crate::item::{Item, UberStateItem, UberStateOperator};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
@@ -35,7 +37,8 @@ impl std::str::FromStr for UberType {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct UberIdentifier {
     pub uber_group: u16,
     pub uber_id: u16,
@@ -56,7 +59,7 @@ impl fmt::Display for UberIdentifier {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct UberState {
     pub identifier: UberIdentifier,
     pub value: String,
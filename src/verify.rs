@@ -0,0 +1,269 @@
+use std::{
+    convert::TryFrom,
+    str::FromStr,
+};
+
+use rustc_hash::FxHashSet;
+use serde::Deserialize;
+
+use crate::{
+    error::Error,
+    generator,
+    inventory::Inventory,
+    item::{Item, Resource, Skill, Shard, Teleporter},
+    languages::headers::parser,
+    settings::{self, Settings},
+    util::{self, UberState, constants::WISP_STATES},
+    world::{
+        World,
+        graph::{Graph, Node, Progressions},
+        pool::Pool,
+    },
+};
+
+/// The point at which replaying a seed got stuck
+#[derive(Debug, Clone)]
+pub struct Softlock {
+    /// Everything the player had collected when getting stuck
+    pub inventory: Inventory,
+    /// Sets of items of which any one would have allowed to progress further
+    pub missing: Vec<Inventory>,
+}
+
+/// Result of replaying a seed through the logic
+#[derive(Debug, Clone)]
+pub struct Verification<'a> {
+    /// Locations that are in logic on the seed's settings, but can't be reached with its placements
+    pub unreachable: Vec<&'a Node>,
+    /// Locations that are out of logic on the seed's settings regardless of the placements
+    pub out_of_logic: Vec<&'a Node>,
+    pub softlock: Option<Softlock>,
+}
+impl Verification<'_> {
+    pub fn is_beatable(&self) -> bool {
+        self.unreachable.is_empty()
+    }
+}
+
+/// Reads the states from the `// Sets:` line of a seed
+pub fn read_sets(seed: &str, graph: &Graph) -> Result<Vec<usize>, Error> {
+    let mut node_sets = Vec::new();
+
+    for line in seed.lines() {
+        if let Some(sets) = line.strip_prefix("// Sets: ") {
+            if !sets.is_empty() {
+                for identifier in sets.split(',').map(str::trim) {
+                    let node = graph.nodes.iter().find(|&node| node.identifier() == identifier).ok_or_else(|| Error::UnknownState { identifier: identifier.to_string() })?;
+                    log::trace!("Setting state {}", identifier);
+                    node_sets.push(node.index());
+                }
+            }
+
+            break;
+        }
+    }
+
+    Ok(node_sets)
+}
+
+/// Reads all pickups from a seed, including the ones added by headers
+pub fn read_placements(seed: &str) -> Result<Vec<(UberState, Item)>, Error> {
+    let mut placements = Vec::new();
+
+    for (index, line) in seed.lines().enumerate() {
+        let line = line.find("//").map_or(line, |comment| &line[..comment]).trim();
+
+        if line.is_empty() || line.starts_with("Flags:") || line.starts_with("Spawn:") || line.starts_with("timer:") {
+            continue;
+        }
+
+        let placement = parser::parse_pickup(line).map_err(|message| Error::ParseSeed { line: Some(index + 1), message })?;
+        placements.push(placement);
+    }
+
+    Ok(placements)
+}
+
+/// What the player has collected so far, to check which locations they can reach with it
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReachState {
+    /// one orb is 10 health
+    pub health: u16,
+    /// one orb is 1 energy
+    pub energy: f32,
+    pub keystones: u16,
+    pub ore: u16,
+    pub spirit_light: u32,
+    /// any additional items in the format s:<skill id>, t:<teleporter id>, sh:<shard id>, w:<world event id> or u:<ubergroup>,<uberid>
+    pub items: Vec<String>,
+}
+
+/// The locations of `seed` that are in logic with what the player has
+pub fn reach_check<'a>(graph: &'a Graph, settings: &Settings, seed: &str, state: &ReachState) -> Result<Vec<&'a Node>, String> {
+    let mut world = World::new(graph);
+
+    world.player.apply_settings(settings);
+
+    world.player.inventory.grant(Item::Resource(Resource::Health), state.health / 5);
+    #[allow(clippy::cast_possible_truncation)]
+    world.player.inventory.grant(Item::Resource(Resource::Energy), util::float_to_int(state.energy * 2.0).map_err(|_| format!("Invalid energy parameter {}", state.energy))?);
+    world.player.inventory.grant(Item::Resource(Resource::Keystone), state.keystones);
+    world.player.inventory.grant(Item::Resource(Resource::Ore), state.ore);
+    world.player.inventory.grant(Item::SpiritLight(1), u16::try_from(state.spirit_light).unwrap_or(u16::MAX));  // Higher amounts of Spirit Light are irrelevant, just want to accept high values in case the player has that much);
+
+    for item in &state.items {
+        if let Some(skill) = item.strip_prefix("s:") {
+            let id: u8 = skill.parse().map_err(|_| format!("expected numeric skill id in {}", item))?;
+            world.player.inventory.grant(Item::Skill(Skill::try_from(id).map_err(|_| format!("{} is not a valid skill id", id))?), 1);
+        }
+        else if let Some(teleporter) = item.strip_prefix("t:") {
+            let id: u8 = teleporter.parse().map_err(|_| format!("expected numeric teleporter id in {}", item))?;
+            world.player.inventory.grant(Item::Teleporter(Teleporter::try_from(id).map_err(|_| format!("{} is not a valid teleporter id", id))?), 1);
+        }
+        else if let Some(shard) = item.strip_prefix("sh:") {
+            let id: u8 = shard.parse().map_err(|_| format!("expected numeric shard id in {}", item))?;
+            world.player.inventory.grant(Item::Shard(Shard::try_from(id).map_err(|_| format!("{} is not a valid shard id", id))?), 1);
+        }
+        else if let Some(world_event) = item.strip_prefix("w:") {
+            let id: u8 = world_event.parse().map_err(|_| format!("expected numeric world event id in {}", item))?;
+            if id != 0 { return Err(format!("{} is not a valid world event id (only 0 is)", id)); }
+            world.player.inventory.grant(Item::Water, 1);
+        }
+        else if let Some(uber_state) = item.strip_prefix("u:") {
+            let uber_state = UberState::from_str(uber_state).map_err(|err| format!("failed to parse uber state in {}: {}", item, err))?;
+
+            world.uber_states.insert(uber_state.identifier, uber_state.value);
+        }
+        else {
+            return Err(format!("items have to start with s:, t:, sh:, w: or u: (for skill, teleporter, shard, world event or uber state), except found {}", item));
+        }
+    }
+
+    world.sets = read_sets(seed, world.graph).map_err(|err| err.to_string())?;
+
+    let spawn = settings::read_spawn(seed).map_err(|err| err.to_string())?;
+    let spawn = world.graph.find_spawn(&spawn).map_err(|err| err.to_string())?;
+
+    let mut reached = world.graph.reached_locations(&world.player, spawn, &world.uber_states, &world.sets).map_err(|err| err.to_string())?;
+    reached.retain(|&node| node.can_place());
+
+    Ok(reached)
+}
+
+fn is_set(world: &World, uber_state: &UberState) -> bool {
+    world.uber_states.get(&uber_state.identifier).map_or(false, |value|
+        if uber_state.value.is_empty() { value == "true" } else { value == &uber_state.value }
+    )
+}
+
+fn missing_items(world: &World, progressions: &Progressions, reached: &FxHashSet<usize>) -> Vec<Inventory> {
+    let owned_states = reached.iter().copied().collect::<Vec<_>>();
+    let mut itemsets = Vec::new();
+
+    for (requirement, best_orbs) in progressions {
+        for (mut needed, orb_cost) in requirement.items_needed(&world.player, &owned_states) {
+            world.player.missing_items(&mut needed);
+
+            for orbs in best_orbs {
+                itemsets.extend(world.player.missing_for_orbs(&needed, orb_cost, *orbs).into_iter()
+                    .filter(|missing| !missing.items.is_empty()));
+            }
+        }
+    }
+
+    generator::filter_itemsets(&mut itemsets);
+    itemsets
+}
+
+/// Replays a finished seed through the logic, collecting its pickups until every location is reached or nothing new can be reached
+pub fn verify<'a>(graph: &'a Graph, settings: &Settings, seed: &str) -> Result<Verification<'a>, Error> {
+    if settings.worlds > 1 {
+        return Err(Error::Multiworld("verified, since each world relies on items from the others"));
+    }
+
+    let mut world = World::new(graph);
+    world.pool = Pool::preset();
+    world.player.spawn(settings);
+    world.sets = read_sets(seed, graph)?;
+
+    let spawn = settings::read_spawn(seed)?;
+    let spawn = graph.find_spawn(&spawn)?;
+
+    let in_logic = generator::total_reach_check(&world, "Verify")?.into_iter()
+        .map(Node::index)
+        .collect::<FxHashSet<_>>();
+
+    for (uber_state, item) in read_placements(seed)? {
+        world.preplace(uber_state, item);
+    }
+    world.collect_preplacements(&UberState::spawn());
+
+    // Headers may complete locations on spawn, which leaves them empty
+    let mut reached = graph.nodes.iter()
+        .filter(|node| node.can_place() && node.uber_state().map_or(false, |uber_state| is_set(&world, uber_state)))
+        .map(Node::index)
+        .collect::<FxHashSet<_>>();
+
+    let progressions = loop {
+        let (world_reached, progressions) = graph.reached_and_progressions(&world.player, spawn, &world.uber_states, &world.sets)?;
+
+        let mut progressed = false;
+        for node in world_reached {
+            if !reached.insert(node.index()) {
+                continue;
+            }
+
+            if let Some(uber_state) = node.uber_state() {
+                let granted = world.collect_preplacements(uber_state);
+                progressed |= granted || WISP_STATES.contains(&uber_state.identifier);
+            }
+        }
+
+        if !progressed { break progressions; }
+    };
+
+    let (unreachable, out_of_logic): (Vec<_>, Vec<_>) = graph.nodes.iter()
+        .filter(|node| node.can_place() && !reached.contains(&node.index()))
+        .partition(|node| in_logic.contains(&node.index()));
+
+    let softlock = if unreachable.is_empty() { None } else {
+        Some(Softlock {
+            missing: missing_items(&world, &progressions, &reached),
+            inventory: world.player.inventory,
+        })
+    };
+
+    Ok(Verification {
+        unreachable,
+        out_of_logic,
+        softlock,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages;
+
+    #[test]
+    fn generated_seed_is_beatable() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let (seeds, _, _) = crate::generate_seed(&graph, settings.clone(), &Vec::new(), None).unwrap();
+        let settings = Settings::from_seed(&seeds[0]).unwrap();
+
+        let verification = verify(&graph, &settings, &seeds[0]).unwrap();
+        assert!(verification.is_beatable());
+        assert!(verification.softlock.is_none());
+
+        let broken = seeds[0].lines()
+            .filter(|line| !matches!(line.split("//").next().and_then(|pickup| parser::parse_pickup(pickup).ok()), Some((_, Item::Skill(_)))))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let verification = verify(&graph, &settings, &broken).unwrap();
+        assert!(!verification.is_beatable());
+        assert!(verification.softlock.is_some());
+    }
+}
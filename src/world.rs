@@ -1,4 +1,7 @@
 pub mod graph;
+pub mod export;
+pub mod diff;
+pub mod path;
 pub mod pool;
 pub mod player;
 pub mod requirements;
@@ -0,0 +1,270 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fmt,
+    iter,
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    graph::{Graph, Node},
+    player::Player,
+    requirements::Requirement,
+};
+use crate::{
+    error::Error,
+    inventory::Inventory,
+    settings::Settings,
+    util::{constants::TP_ANCHOR, orbs::Orbs},
+};
+
+/// How many item sets are kept for one node, further ones are dropped to keep the search from exploding
+pub const MAX_ITEMSETS: usize = 24;
+
+/// The minimal ways to meet something, as items along with the health and energy they cost
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Itemsets {
+    pub sets: Vec<(Inventory, Orbs)>,
+    /// Whether there were more than [`MAX_ITEMSETS`] minimal sets and some got dropped
+    pub truncated: bool,
+}
+impl Itemsets {
+    fn free() -> Itemsets {
+        Itemsets { sets: vec![(Inventory::default(), Orbs::default())], truncated: false }
+    }
+
+    /// Adds `itemset` unless one needing a subset of its items at no higher cost is already known, removing any it makes redundant
+    ///
+    /// Returns whether the item sets changed
+    fn insert(&mut self, itemset: (Inventory, Orbs)) -> bool {
+        let covers = |(inventory, orbs): &(Inventory, Orbs), (other_inventory, other_orbs): &(Inventory, Orbs)|
+            other_inventory.contains(inventory) && orbs.health >= other_orbs.health && orbs.energy >= other_orbs.energy;
+
+        if self.sets.iter().any(|known| covers(known, &itemset)) {
+            return false;
+        }
+        self.sets.retain(|known| !covers(&itemset, known));
+        if self.sets.len() >= MAX_ITEMSETS {
+            self.truncated = true;
+            return false;
+        }
+        self.sets.push(itemset);
+        true
+    }
+
+    fn minimize(itemsets: impl IntoIterator<Item = (Inventory, Orbs)>, truncated: bool) -> Itemsets {
+        let mut minimal = Itemsets { sets: Vec::new(), truncated };
+        for itemset in itemsets {
+            minimal.insert(itemset);
+        }
+        minimal
+    }
+
+    /// The item sets meeting both `self` and `other`, with their costs merged by `orbs`
+    fn combine(&self, other: &Itemsets, orbs: impl Fn(Orbs, Orbs) -> Orbs) -> Itemsets {
+        let orbs = &orbs;
+        let itemsets = self.sets.iter().flat_map(|(left, left_orbs)| {
+            other.sets.iter().map(move |(right, right_orbs)| (left.merge(right), orbs(*left_orbs, *right_orbs)))
+        }).collect::<Vec<_>>();
+        Itemsets::minimize(itemsets, self.truncated || other.truncated)
+    }
+}
+
+/// The higher of two costs, for connections that can be taken one after another with refills between them
+fn peak(left: Orbs, right: Orbs) -> Orbs {
+    Orbs { health: left.health.min(right.health), energy: left.energy.min(right.energy) }
+}
+
+/// The item sets that meet `requirement`, states need the items that reach them
+fn needed(requirement: &Requirement, player: &Player, reached: &[Itemsets]) -> Itemsets {
+    match requirement {
+        Requirement::State(state) => reached[*state].clone(),
+        Requirement::And(ands) => ands.iter().fold(Itemsets::free(), |acc, and| {
+            if acc.sets.is_empty() { return acc }
+            acc.combine(&needed(and, player, reached), |left, right| left + right)
+        }),
+        Requirement::Or(ors) => {
+            let ors = ors.iter().map(|or| needed(or, player, reached)).collect::<Vec<_>>();
+            let truncated = ors.iter().any(|or| or.truncated);
+            Itemsets::minimize(ors.into_iter().flat_map(|or| or.sets), truncated)
+        },
+        _ => Itemsets::minimize(requirement.items_needed(player, &[]), false),
+    }
+}
+
+/// The smallest sets of items that reach each node from `spawn`, indexed like `graph.nodes`
+///
+/// Health and energy costs add up within a connection, along a path only the most expensive connection counts.
+/// Unreachable nodes have no item sets, nodes that need nothing have one empty item set
+pub fn minimal_itemsets(graph: &Graph, settings: &Settings, spawn: &str) -> Result<Vec<Itemsets>, Error> {
+    let spawn = graph.find_spawn(spawn)?;
+    let mut player = Player::default();
+    player.apply_settings(settings);
+
+    let mut reached = vec![Itemsets::default(); graph.nodes.len()];
+    reached[spawn.index()] = Itemsets::free();
+    if let Some(teleporters) = graph.nodes.iter().find(|node| node.identifier() == TP_ANCHOR) {
+        reached[teleporters.index()] = Itemsets::free();
+    }
+
+    // anchors to revisit once a state they depend on is reached in a new way
+    let mut dependents = FxHashMap::<usize, Vec<usize>>::default();
+    // requirements without states always need the same items
+    let mut cached = FxHashMap::<(usize, usize), Itemsets>::default();
+    for node in &graph.nodes {
+        if let Node::Anchor(anchor) = node {
+            for (index, connection) in anchor.connections.iter().enumerate() {
+                let states = connection.requirement.contained_states();
+                if states.is_empty() {
+                    cached.insert((anchor.index, index), needed(&connection.requirement, &player, &reached));
+                }
+                for state in states {
+                    dependents.entry(state).or_default().push(anchor.index);
+                }
+            }
+        }
+    }
+
+    let mut queued = reached.iter().map(|itemsets| !itemsets.sets.is_empty()).collect::<Vec<_>>();
+    let mut queue = (0..reached.len()).filter(|&index| queued[index]).collect::<VecDeque<_>>();
+    while let Some(index) = queue.pop_front() {
+        queued[index] = false;
+        let anchor = match &graph.nodes[index] {
+            Node::Anchor(anchor) => anchor,
+            _ => continue,
+        };
+
+        for (connection_index, connection) in anchor.connections.iter().enumerate() {
+            let needed = match cached.get(&(index, connection_index)) {
+                Some(needed) => Cow::Borrowed(needed),
+                None => Cow::Owned(needed(&connection.requirement, &player, &reached)),
+            };
+
+            let combined = reached[index].combine(&needed, peak);
+            let target = &mut reached[connection.to];
+            let mut changed = false;
+            for itemset in combined.sets {
+                changed |= target.insert(itemset);
+            }
+            // sets dropped further up only matter if the ones that were kept still added something
+            target.truncated |= changed && combined.truncated;
+            if changed {
+                for &next in iter::once(&connection.to).chain(dependents.get(&connection.to).into_iter().flatten()) {
+                    if !queued[next] {
+                        queued[next] = true;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(reached)
+}
+
+fn describe_itemset((inventory, orbs): &(Inventory, Orbs)) -> String {
+    let mut parts = inventory.item_names();
+    if orbs.health < 0.0 { parts.push(format!("{} health", -orbs.health)) }
+    if orbs.energy < 0.0 { parts.push(format!("{} energy", -orbs.energy)) }
+    format!("[{}]", parts.join(", "))
+}
+
+/// The described minimal item sets for every pickup in a stable order, along with whether they were truncated
+fn pickup_itemsets(graph: &Graph, settings: &Settings, spawn: &str) -> Result<FxHashMap<String, (Vec<String>, bool)>, Error> {
+    let reached = minimal_itemsets(graph, settings, spawn)?;
+
+    Ok(graph.nodes.iter()
+        .filter(|node| node.can_place())
+        .map(|node| {
+            let reached = &reached[node.index()];
+            let mut itemsets = reached.sets.iter().map(describe_itemset).collect::<Vec<_>>();
+            itemsets.sort_unstable();
+            (node.identifier().to_string(), (itemsets, reached.truncated))
+        })
+        .collect())
+}
+
+/// How the items needed to reach a pickup differ between two versions of the logic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PickupChange {
+    pub identifier: String,
+    /// The minimal item sets in the old logic, empty if the pickup was unreachable
+    pub before: Vec<String>,
+    /// The minimal item sets in the new logic, empty if the pickup is unreachable
+    pub after: Vec<String>,
+    /// Whether either side had more than [`MAX_ITEMSETS`] minimal item sets, so not all of them are listed
+    pub truncated: bool,
+}
+impl fmt::Display for PickupChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.before.is_empty() {
+            write!(f, "{} became reachable with {}", self.identifier, self.after.join(" or "))?;
+        } else if self.after.is_empty() {
+            write!(f, "{} became unreachable, it needed {}", self.identifier, self.before.join(" or "))?;
+        } else {
+            write!(f, "{} changed from {} to {}", self.identifier, self.before.join(" or "), self.after.join(" or "))?;
+        }
+        if self.truncated {
+            write!(f, " (only the first {} item sets were kept)", MAX_ITEMSETS)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the minimal item sets for every pickup between two graphs emitted with the same settings
+pub fn diff(old: &Graph, new: &Graph, settings: &Settings, spawn: &str) -> Result<Vec<PickupChange>, Error> {
+    let mut before = pickup_itemsets(old, settings, spawn)?;
+    let after = pickup_itemsets(new, settings, spawn)?;
+
+    let mut changes = Vec::new();
+    for (identifier, (after, after_truncated)) in after {
+        let (before, before_truncated) = before.remove(&identifier).unwrap_or_default();
+        if before != after {
+            changes.push(PickupChange { identifier, before, after, truncated: before_truncated || after_truncated });
+        }
+    }
+    for (identifier, (before, truncated)) in before {
+        if !before.is_empty() {
+            changes.push(PickupChange { identifier, before, after: Vec::new(), truncated });
+        }
+    }
+
+    changes.sort_unstable_by(|a, b| a.identifier.cmp(&b.identifier));
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::languages;
+
+    #[test]
+    fn logic_diff() {
+        let settings = Settings::default();
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let reached = minimal_itemsets(&graph, &settings, "MarshSpawn.Main").unwrap();
+        let find = |identifier| graph.nodes.iter().find(|node| node.identifier() == identifier).unwrap().index();
+        assert_eq!(reached[find("MarshSpawn.RockHC")].sets, [(Inventory::default(), Orbs::default())]);
+        assert!(reached[find("MarshSpawn.GrappleHC")].sets.iter().all(|(itemset, _)| itemset.has(&crate::item::Item::Skill(crate::item::Skill::Grapple), 1)));
+
+        assert!(diff(&graph, &graph, &settings, "MarshSpawn.Main").unwrap().is_empty());
+
+        let areas = std::fs::read_to_string("areas.wotw").unwrap();
+        let changed = areas.replacen("pickup MarshSpawn.RockHC: free", "pickup MarshSpawn.RockHC:\n    moki: Bash, Damage=20", 1);
+        assert_ne!(areas, changed);
+        let path = std::env::temp_dir().join("seedgen_logic_diff.wotw");
+        std::fs::write(&path, changed).unwrap();
+        let new = languages::parse_logic(&path, "loc_data.csv", "state_data.csv", &settings, false).unwrap();
+
+        let changes = diff(&graph, &new, &settings, "MarshSpawn.Main").unwrap();
+        let change = changes.iter().find(|change| change.identifier == "MarshSpawn.RockHC").unwrap();
+        assert_eq!(change.before, ["[]"]);
+        assert_eq!(change.after, ["[Bash, 20 health]"]);
+        assert_eq!(change.to_string(), "MarshSpawn.RockHC changed from [] to [Bash, 20 health]");
+
+        let truncated = PickupChange { truncated: true, ..change.clone() };
+        assert_eq!(truncated.to_string(), format!("MarshSpawn.RockHC changed from [] to [Bash, 20 health] (only the first {} item sets were kept)", MAX_ITEMSETS));
+    }
+}
@@ -0,0 +1,222 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+
+use super::graph::{Graph, Node};
+use crate::{
+    error::Error,
+    util::{Position, RefillType, Zone},
+};
+
+/// Which parts of the graph to export, an empty filter exports everything
+///
+/// Regions are the part of an identifier before the first `.`.
+/// Anchors and states don't have a zone themselves, they take the zone of the pickups in their region
+#[derive(Debug, Default, Clone)]
+pub struct ExportFilter {
+    pub regions: Vec<String>,
+    pub zones: Vec<Zone>,
+}
+
+fn region(identifier: &str) -> &str {
+    identifier.split('.').next().unwrap_or(identifier)
+}
+
+/// The nodes passing the filter, by index
+fn included(graph: &Graph, filter: &ExportFilter) -> FxHashSet<usize> {
+    let mut region_zones = FxHashMap::default();
+    for node in &graph.nodes {
+        if let Some(zone) = node.zone() {
+            region_zones.entry(region(node.identifier())).or_insert(zone);
+        }
+    }
+
+    graph.nodes.iter()
+        .filter(|node| {
+            let node_region = region(node.identifier());
+            let zone = node.zone().or_else(|| region_zones.get(node_region).copied());
+            (filter.regions.is_empty() || filter.regions.iter().any(|region| region == node_region)) &&
+            (filter.zones.is_empty() || zone.map_or(false, |zone| filter.zones.contains(&zone)))
+        })
+        .map(Node::index)
+        .collect()
+}
+
+fn refill_name(refill: RefillType) -> String {
+    match refill {
+        RefillType::Full => String::from("Full"),
+        RefillType::Checkpoint => String::from("Checkpoint"),
+        RefillType::Health(amount) => format!("Health={}", amount),
+        RefillType::Energy(amount) => format!("Energy={}", amount),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonRefill {
+    refill: String,
+    requirement: String,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonNode<'a> {
+    identifier: &'a str,
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zone: Option<Zone>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<&'a Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uber_state: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    refills: Vec<JsonRefill>,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonConnection<'a> {
+    from: &'a str,
+    to: &'a str,
+    requirement: String,
+}
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonGraph<'a> {
+    nodes: Vec<JsonNode<'a>>,
+    connections: Vec<JsonConnection<'a>>,
+}
+
+/// Exports the graph as json, listing the nodes and the connections leaving the included anchors
+///
+/// Connections may lead to nodes outside of the filter, those aren't listed in the nodes
+pub fn to_json(graph: &Graph, filter: &ExportFilter) -> Result<String, Error> {
+    let included = included(graph, filter);
+
+    let mut nodes = Vec::new();
+    let mut connections = Vec::new();
+    for node in graph.nodes.iter().filter(|node| included.contains(&node.index())) {
+        let mut refills = Vec::new();
+        if let Node::Anchor(anchor) = node {
+            for refill in &anchor.refills {
+                refills.push(JsonRefill {
+                    refill: refill_name(refill.name),
+                    requirement: refill.requirement.describe(graph),
+                });
+            }
+            for connection in &anchor.connections {
+                connections.push(JsonConnection {
+                    from: node.identifier(),
+                    to: graph.nodes[connection.to].identifier(),
+                    requirement: connection.requirement.describe(graph),
+                });
+            }
+        }
+
+        nodes.push(JsonNode {
+            identifier: node.identifier(),
+            kind: format!("{:?}", node.node_type()),
+            zone: node.zone(),
+            position: node.position(),
+            uber_state: node.uber_state().map(|uber_state| uber_state.to_string()),
+            refills,
+        });
+    }
+
+    serde_json::to_string_pretty(&JsonGraph { nodes, connections }).map_err(|err| Error::Serialize { what: "logic graph", message: err.to_string() })
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn dot_shape(node: &Node) -> &'static str {
+    match node {
+        Node::Anchor(_) => "box",
+        Node::Pickup(_) => "ellipse",
+        Node::State(_) => "diamond",
+        Node::Quest(_) => "hexagon",
+    }
+}
+
+/// Exports the graph in the Graphviz dot language, labelling connections with their requirements
+///
+/// Nodes outside of the filter that included anchors connect to are drawn dashed
+pub fn to_dot(graph: &Graph, filter: &ExportFilter) -> String {
+    let included = included(graph, filter);
+
+    let mut nodes = String::new();
+    let mut edges = String::new();
+    let mut external = FxHashSet::default();
+    for node in graph.nodes.iter().filter(|node| included.contains(&node.index())) {
+        nodes += &format!("  {} [shape={}];\n", quote(node.identifier()), dot_shape(node));
+
+        if let Node::Anchor(anchor) = node {
+            for connection in &anchor.connections {
+                let target = &graph.nodes[connection.to];
+                if !included.contains(&connection.to) && external.insert(connection.to) {
+                    nodes += &format!("  {} [shape={}, style=dashed];\n", quote(target.identifier()), dot_shape(target));
+                }
+
+                edges += &format!("  {} -> {}", quote(node.identifier()), quote(target.identifier()));
+                let requirement = connection.requirement.describe(graph);
+                if requirement != "free" {
+                    edges += &format!(" [label={}]", quote(&requirement));
+                }
+                edges += ";\n";
+            }
+        }
+    }
+
+    format!("digraph logic {{\n{}{}}}\n", nodes, edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{languages, settings::Settings};
+
+    #[test]
+    fn export() {
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &Settings::default(), false).unwrap();
+
+        let filter = ExportFilter { regions: vec![String::from("MarshSpawn")], zones: Vec::new() };
+        let json: serde_json::Value = serde_json::from_str(&to_json(&graph, &filter).unwrap()).unwrap();
+        let nodes = json["nodes"].as_array().unwrap();
+        assert!(nodes.iter().all(|node| node["identifier"].as_str().unwrap().starts_with("MarshSpawn.")));
+        assert!(nodes.iter().any(|node| node["identifier"] == "MarshSpawn.Main" && node["kind"] == "Anchor"));
+        assert!(nodes.iter().any(|node| node["identifier"] == "MarshSpawn.RockHC" && node["zone"] == "Marsh"));
+        let connections = json["connections"].as_array().unwrap();
+        assert!(connections.iter().all(|connection| connection["from"].as_str().unwrap().starts_with("MarshSpawn.")));
+        assert!(connections.iter().any(|connection| !connection["to"].as_str().unwrap().starts_with("MarshSpawn.")));
+
+        let dot = to_dot(&graph, &filter);
+        assert!(dot.starts_with("digraph logic {\n"));
+        assert!(dot.contains("  \"MarshSpawn.Main\" [shape=box];\n"));
+        assert!(dot.contains("style=dashed"));
+        assert!(dot.lines().filter(|line| line.contains("->")).count() == connections.len());
+
+        let filter = ExportFilter { regions: Vec::new(), zones: vec![Zone::Depths] };
+        let json: serde_json::Value = serde_json::from_str(&to_json(&graph, &filter).unwrap()).unwrap();
+        let nodes = json["nodes"].as_array().unwrap();
+        assert!(nodes.iter().any(|node| node["kind"] == "Anchor"));
+        assert!(nodes.iter().all(|node| node.get("zone").map_or(true, |zone| zone == "Depths")));
+    }
+
+    #[test]
+    fn describe_requirements() {
+        use crate::{item::Skill, util::Enemy, world::requirements::Requirement};
+        use smallvec::smallvec;
+
+        let graph = Graph::default();
+        let requirement = Requirement::Or(vec![
+            Requirement::And(vec![Requirement::Skill(Skill::DoubleJump), Requirement::EnergySkill(Skill::Grenade, 2.0)]),
+            Requirement::Combat(smallvec![(Enemy::Mantis, 2), (Enemy::Slug, 1)]),
+        ]);
+        assert_eq!(requirement.describe(&graph), "(DoubleJump, Grenade=2) OR Combat=2xMantis+Slug");
+
+        let requirement = Requirement::Or(vec![
+            Requirement::NonConsumingEnergySkill(Skill::Flash),
+            Requirement::And(vec![Requirement::Skill(Skill::Dash), Requirement::NonConsumingEnergySkill(Skill::Regenerate)]),
+            Requirement::And(vec![Requirement::Skill(Skill::Bash), Requirement::NonConsumingEnergySkill(Skill::Grenade)]),
+        ]);
+        assert_eq!(requirement.describe(&graph), "FlashSwap OR WaveDash OR (Bash, GrenadeCancel)");
+    }
+}
@@ -1,28 +1,30 @@
 use std::fmt;
 
 use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Serialize, Deserialize};
 use smallvec::{SmallVec, smallvec};
 
 use super::{player::Player, requirements::Requirement};
+use crate::error::Error;
 use crate::util::{
     RefillType, NodeType, Position, Zone, UberState, UberIdentifier,
     orbs::{self, Orbs},
     constants::TP_ANCHOR,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Refill {
     pub name: RefillType,
     pub requirement: Requirement,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Connection {
     pub to: usize,
     pub requirement: Requirement,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Anchor {
     pub identifier: String,
     pub position: Option<Position>,
@@ -31,7 +33,7 @@ pub struct Anchor {
     pub refills: Vec<Refill>,
     pub connections: Vec<Connection>,
 }
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Pickup {
     pub identifier: String,
     pub position: Position,
@@ -39,13 +41,13 @@ pub struct Pickup {
     pub index: usize,
     pub uber_state: UberState,
 }
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct State {
     pub identifier: String,
     pub index: usize,
     pub uber_state: Option<UberState>,
 }
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Quest {
     pub identifier: String,
     pub position: Position,
@@ -54,7 +56,7 @@ pub struct Quest {
     pub uber_state: UberState,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub enum Node {
     Anchor(Anchor),
     Pickup(Pickup),
@@ -136,7 +138,7 @@ struct ReachContext<'a, 'b> {
     world_state: FxHashMap<usize, SmallVec<[Orbs; 3]>>
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Graph {
     pub nodes: Vec<Node>,
 }
@@ -279,13 +281,13 @@ impl Graph {
     }
 
     #[inline]
-    pub fn find_spawn(&self, spawn: &str) -> Result<&Node, String> {
-        let entry = self.nodes.iter().find(|&node| node.identifier() == spawn).ok_or_else(|| format!("Spawn {} not found", spawn))?;
-        if !matches!(entry, Node::Anchor(_)) { return Err(format!("Spawn has to be an anchor, {} is a {:?}", spawn, entry.node_type())); }
+    pub fn find_spawn(&self, spawn: &str) -> Result<&Node, Error> {
+        let entry = self.nodes.iter().find(|&node| node.identifier() == spawn).ok_or_else(|| Error::UnknownSpawn { identifier: spawn.to_string() })?;
+        if !matches!(entry, Node::Anchor(_)) { return Err(Error::InvalidSpawn { identifier: spawn.to_string(), node_type: entry.node_type() }); }
         Ok(entry)
     }
 
-    pub fn reached_locations<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<Reached<'a>, String> {
+    pub fn reached_locations<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<Reached<'a>, Error> {
         let mut context = ReachContext {
             player,
             progression_check: false,
@@ -298,7 +300,25 @@ impl Graph {
 
         Ok(reached)
     }
-    pub fn reached_and_progressions<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<(Reached<'a>, Progressions<'a>), String> {
+    pub fn reached_and_visited<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<(Reached<'a>, Vec<&'a Node>), Error> {
+        let mut context = ReachContext {
+            player,
+            progression_check: false,
+            states: self.collect_extra_states(extra_states, sets),
+            state_progressions: FxHashMap::default(),
+            world_state: FxHashMap::default(),
+        };
+
+        let (reached, _) = self.reach_recursion(spawn, true, smallvec![player.max_orbs()], &mut context);
+
+        let visited = context.world_state.keys()
+            .map(|&index| &self.nodes[index])
+            .filter(|node| matches!(node, Node::Anchor(_)))
+            .collect();
+
+        Ok((reached, visited))
+    }
+    pub fn reached_and_progressions<'a>(&'a self, player: &Player, spawn: &'a Node, extra_states: &FxHashMap<UberIdentifier, String>, sets: &[usize]) -> Result<(Reached<'a>, Progressions<'a>), Error> {
         let mut context = ReachContext {
             player,
             progression_check: true,
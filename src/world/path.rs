@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+
+use super::{
+    graph::{Graph, Node},
+    player::Player,
+    requirements::Requirement,
+};
+use crate::{
+    error::Error,
+    inventory::Inventory,
+    util::{RefillType, orbs::Orbs},
+};
+
+/// How many routes are kept for one node, the ones needing the most are dropped to keep the search from exploding
+pub const MAX_ROUTES: usize = 16;
+
+/// One way to travel between two nodes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    /// The items needed on top of the starting inventory
+    pub items: Inventory,
+    /// The states that have to be met, as node indices
+    pub states: Vec<usize>,
+    /// The most health and energy spent at once, refills that are always available start over
+    pub orbs: Orbs,
+    /// The nodes passed through, as node indices from start to target
+    pub path: Vec<usize>,
+    /// What has been spent since the last refill, negative like the costs from `Requirement::items_needed`
+    spent: Orbs,
+}
+impl Route {
+    /// Whether this route is at least as good as `other` in every way
+    fn dominates(&self, other: &Route) -> bool {
+        other.items.contains(&self.items) &&
+        self.states.iter().all(|state| other.states.contains(state)) &&
+        self.orbs.health <= other.orbs.health && self.orbs.energy <= other.orbs.energy &&
+        self.spent.health >= other.spent.health && self.spent.energy >= other.spent.energy
+    }
+
+    /// How much the route needs, smaller routes are kept over larger ones once there are too many
+    fn size(&self) -> (usize, usize) {
+        (self.items.item_count() + self.states.len(), self.path.len())
+    }
+
+    /// Lists what the route needs and where it goes, with names looked up in `graph`
+    pub fn describe(&self, graph: &Graph) -> String {
+        let mut needs = self.items.item_names();
+        needs.extend(self.states.iter().map(|&state| format!("state {}", graph.nodes[state].identifier())));
+        let mut description = format!("[{}]", needs.join(", "));
+
+        if self.orbs.health > 0.0 || self.orbs.energy > 0.0 {
+            description += &format!(", spending up to {} health and {} energy", self.orbs.health, self.orbs.energy);
+        }
+        let path = self.path.iter().map(|&index| graph.nodes[index].identifier()).collect::<Vec<_>>();
+        description += &format!("\n  {}", path.join(" -> "));
+
+        description
+    }
+}
+
+/// The ways to meet `requirement` as items, states and orb cost
+fn options(requirement: &Requirement, player: &Player) -> Vec<(Inventory, Vec<usize>, Orbs)> {
+    match requirement {
+        Requirement::State(state) => vec![(Inventory::default(), vec![*state], Orbs::default())],
+        Requirement::And(ands) => ands.iter().fold(vec![(Inventory::default(), Vec::new(), Orbs::default())], |acc, and| {
+            let next = options(and, player);
+            acc.iter().flat_map(|(items, states, orbs)| next.iter().map(move |(other_items, other_states, other_orbs)| {
+                let mut states = states.clone();
+                states.extend(other_states.iter().filter(|state| !states.contains(state)).collect::<Vec<_>>());
+                (items.merge(other_items), states, *orbs + *other_orbs)
+            })).collect()
+        }),
+        Requirement::Or(ors) => ors.iter().flat_map(|or| options(or, player)).collect(),
+        _ => requirement.items_needed(player, &[]).into_iter().map(|(items, orbs)| (items, Vec::new(), orbs)).collect(),
+    }
+}
+
+/// Adds `route` unless a known route is at least as good, removing the known routes it beats
+///
+/// Past [`MAX_ROUTES`] the route needing the most is dropped, which sets `truncated`
+fn insert_route(routes: &mut Vec<Route>, route: Route, truncated: &mut bool) -> bool {
+    if routes.iter().any(|known| known.dominates(&route)) {
+        return false;
+    }
+    routes.retain(|known| !route.dominates(known));
+    if routes.len() >= MAX_ROUTES {
+        *truncated = true;
+        let largest = routes.iter().enumerate().max_by_key(|(_, known)| known.size()).map(|(index, known)| (index, known.size()));
+        match largest {
+            Some((index, size)) if route.size() < size => { routes.swap_remove(index); },
+            _ => return false,
+        }
+    }
+    routes.push(route);
+    true
+}
+
+/// The routes found to a node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Routes {
+    /// Sorted from the ones needing the least
+    pub routes: Vec<Route>,
+    /// Whether routes had to be dropped somewhere along the way, so some ways to the node may be missing
+    pub truncated: bool,
+}
+
+/// Finds the minimal routes from the anchor `from` to the node `to` for a player starting with `player`'s inventory
+///
+/// Only refills that have no requirements reset the orb cost, so the reported health and energy are an upper bound
+pub fn find_routes(graph: &Graph, player: &Player, from: &str, to: &str) -> Result<Routes, Error> {
+    let start = graph.nodes.iter().find(|node| node.identifier() == from).ok_or_else(|| Error::UnknownNode { identifier: from.to_string() })?;
+    if !matches!(start, Node::Anchor(_)) {
+        return Err(Error::InvalidSpawn { identifier: from.to_string(), node_type: start.node_type() });
+    }
+    let target = graph.nodes.iter().find(|node| node.identifier() == to).ok_or_else(|| Error::UnknownNode { identifier: to.to_string() })?.index();
+
+    let mut routes = vec![Vec::<Route>::new(); graph.nodes.len()];
+    routes[start.index()].push(Route {
+        items: Inventory::default(),
+        states: Vec::new(),
+        orbs: Orbs::default(),
+        path: vec![start.index()],
+        spent: Orbs::default(),
+    });
+
+    let mut truncated = vec![false; graph.nodes.len()];
+    let mut queued = vec![false; graph.nodes.len()];
+    let mut queue = VecDeque::from(vec![start.index()]);
+    while let Some(index) = queue.pop_front() {
+        queued[index] = false;
+        let anchor = match &graph.nodes[index] {
+            Node::Anchor(anchor) if index != target => anchor,
+            _ => continue,
+        };
+
+        for connection in &anchor.connections {
+            let refills = match &graph.nodes[connection.to] {
+                Node::Anchor(next) => next.refills.iter().any(|refill| matches!(refill.name, RefillType::Full | RefillType::Checkpoint) && matches!(refill.requirement, Requirement::Free)),
+                _ => false,
+            };
+
+            let mut changed = false;
+            for (items, states, orbs) in options(&connection.requirement, player) {
+                for route in routes[index].clone() {
+                    if route.path.contains(&connection.to) { continue }
+
+                    let mut items = route.items.merge(&items);
+                    items.items.retain(|item, amount| {
+                        *amount = amount.saturating_sub(player.inventory.get(item));
+                        *amount > 0
+                    });
+                    let mut states = states.clone();
+                    states.extend(route.states.iter().filter(|state| !states.contains(state)).collect::<Vec<_>>());
+                    states.sort_unstable();
+
+                    let spent = route.spent + orbs;
+                    let orbs = Orbs { health: route.orbs.health.max(-spent.health), energy: route.orbs.energy.max(-spent.energy) };
+                    let mut path = route.path.clone();
+                    path.push(connection.to);
+
+                    let spent = if refills { Orbs::default() } else { spent };
+                    changed |= insert_route(&mut routes[connection.to], Route { items, states, orbs, path, spent }, &mut truncated[connection.to]);
+                }
+            }
+            // routes dropped further up only matter if the ones that were kept still led somewhere new
+            truncated[connection.to] |= changed && truncated[index];
+
+            if changed && !queued[connection.to] {
+                queued[connection.to] = true;
+                queue.push_back(connection.to);
+            }
+        }
+    }
+
+    let mut found = routes.swap_remove(target);
+    found.sort_by_key(Route::size);
+    Ok(Routes { routes: found, truncated: truncated[target] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{languages, settings::Settings, item::{Item, Resource, Skill}};
+
+    #[test]
+    fn routes() {
+        let graph = languages::parse_logic("areas.wotw", "loc_data.csv", "state_data.csv", &Settings::default(), false).unwrap();
+        let player = Player::default();
+
+        let routes = find_routes(&graph, &player, "MarshSpawn.Main", "MarshSpawn.RockHC").unwrap().routes;
+        assert_eq!(routes[0].describe(&graph), "[]\n  MarshSpawn.Main -> MarshSpawn.RockHC");
+
+        let routes = find_routes(&graph, &player, "MarshSpawn.Main", "MarshSpawn.GrappleHC").unwrap().routes;
+        assert!(!routes.is_empty());
+        assert!(routes.iter().all(|route| route.items.has(&Item::Skill(Skill::Grapple), 1)));
+
+        let mut player = Player::default();
+        player.inventory.grant(Item::Skill(Skill::Grapple), 1);
+        let routes = find_routes(&graph, &player, "MarshSpawn.Main", "MarshSpawn.GrappleHC").unwrap().routes;
+        assert!(routes.iter().all(|route| !route.items.has(&Item::Skill(Skill::Grapple), 1)));
+
+        player.inventory.grant(Item::Resource(Resource::Keystone), 1);
+        let routes = find_routes(&graph, &player, "MarshSpawn.CaveEntrance", "MarshSpawn.KeystoneDoor").unwrap();
+        assert!(!routes.truncated);
+        assert_eq!(routes.routes[0].items.get(&Item::Resource(Resource::Keystone)), 1, "should only need the keystone that isn't owned yet");
+
+        assert_eq!(find_routes(&graph, &player, "MarshSpawn.RockHC", "MarshSpawn.Main"), Err(Error::InvalidSpawn { identifier: String::from("MarshSpawn.RockHC"), node_type: crate::util::NodeType::Pickup }));
+        assert_eq!(find_routes(&graph, &player, "MarshSpawn.Main", "Nowhere"), Err(Error::UnknownNode { identifier: String::from("Nowhere") }));
+    }
+}
@@ -121,9 +121,11 @@ impl Pool {
         true
     }
 
-    pub fn choose_random<R>(&self, multiworld_spread: bool, rng: &mut R) -> Option<&Item>
+    /// Chooses a random item, with each item's chance scaled by its amount and `weight`
+    pub fn choose_random<R, W>(&self, multiworld_spread: bool, weight: W, rng: &mut R) -> Option<&Item>
     where
-        R: Rng
+        R: Rng,
+        W: Fn(&Item) -> f64,
     {
         let mut items = self.inventory.items.iter().collect::<Vec<_>>();
         if multiworld_spread {
@@ -131,7 +133,7 @@ impl Pool {
         }
 
         loop {
-            let (item, _) = *items.choose_weighted(rng, |&(_, amount)| amount).ok()?;
+            let (item, _) = *items.choose_weighted(rng, |&(item, amount)| f64::from(*amount) * weight(item)).ok()?;
             let cost = item.cost();
 
             if cost > 10000 {
@@ -1,14 +1,15 @@
 use rustc_hash::FxHashSet;
+use serde::{Serialize, Deserialize};
 use smallvec::{SmallVec, smallvec};
 
-use super::player::Player;
+use super::{graph::Graph, player::Player};
 use crate::inventory::Inventory;
 use crate::item::{Item, Resource, Skill, Shard, Teleporter};
 use crate::util::{Difficulty, Enemy, orbs::{self, Orbs}};
 
 type Itemset = Vec<(Inventory, Orbs)>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Requirement {
     Free,
     Impossible,
@@ -504,6 +505,51 @@ impl Requirement {
             _ => vec![],
         }
     }
+
+    /// Writes the requirement close to how the logic file spells it, with states named after their nodes in `graph`
+    ///
+    /// Groups nested in a different kind of group are put in parentheses since the emitted requirement doesn't keep the original line structure
+    pub fn describe(&self, graph: &Graph) -> String {
+        let describe_nested = |requirement: &Requirement| {
+            let description = requirement.describe(graph);
+            match (self, requirement) {
+                (Requirement::And(_), Requirement::Or(_)) if description.contains(" OR ") => format!("({})", description),
+                (Requirement::Or(_), Requirement::And(_)) if description.contains(", ") => format!("({})", description),
+                _ => description,
+            }
+        };
+
+        match self {
+            Requirement::Free => String::from("free"),
+            Requirement::Impossible => String::from("impossible"),
+            Requirement::Skill(skill) => format!("{:?}", skill),
+            Requirement::EnergySkill(skill, amount) => format!("{:?}={}", skill, amount),
+            Requirement::NonConsumingEnergySkill(Skill::Flash) => String::from("FlashSwap"),
+            Requirement::NonConsumingEnergySkill(Skill::Grenade) => String::from("GrenadeCancel"),
+            Requirement::NonConsumingEnergySkill(skill) => format!("{:?}", skill),
+            Requirement::SpiritLight(amount) => format!("SpiritLight={}", amount),
+            Requirement::Resource(resource, amount) => format!("{:?}={}", resource, amount),
+            Requirement::Shard(shard) => format!("{:?}", shard),
+            Requirement::Teleporter(teleporter) => format!("{:?}TP", teleporter),
+            Requirement::Water => String::from("Water"),
+            Requirement::State(state) => graph.nodes.get(*state).map_or_else(|| format!("state {}", state), |node| node.identifier().to_string()),
+            Requirement::Damage(amount) => format!("Damage={}", amount),
+            Requirement::Danger(amount) => format!("Danger={}", amount),
+            Requirement::Combat(enemies) => {
+                let enemies = enemies.iter().map(|(enemy, amount)| {
+                    if *amount == 1 { format!("{:?}", enemy) } else { format!("{}x{:?}", amount, enemy) }
+                }).collect::<Vec<_>>();
+                format!("Combat={}", enemies.join("+"))
+            },
+            Requirement::Boss(health) => format!("Boss={}", health),
+            Requirement::BreakWall(health) => format!("BreakWall={}", health),
+            Requirement::ShurikenBreak(health) => format!("ShurikenBreak={}", health),
+            Requirement::SentryBreak(health) => format!("SentryBreak={}", health),
+            Requirement::And(ands) if matches!(ands[..], [Requirement::Skill(Skill::Dash), Requirement::NonConsumingEnergySkill(Skill::Regenerate)]) => String::from("WaveDash"),
+            Requirement::And(ands) => ands.iter().map(describe_nested).collect::<Vec<_>>().join(", "),
+            Requirement::Or(ors) => ors.iter().map(describe_nested).collect::<Vec<_>>().join(" OR "),
+        }
+    }
 }
 
 #[cfg(test)]